@@ -0,0 +1,237 @@
+use {
+    crate::types::AccountCacheStats,
+    solana_account::{AccountSharedData, ReadableAccount},
+    solana_pubkey::Pubkey,
+    std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        sync::Mutex,
+    },
+};
+
+/// Maps a key, such as an owner, to the accounts it applies to.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AccountIndex(HashMap<Pubkey, HashSet<Pubkey>>);
+
+impl AccountIndex {
+    pub(crate) fn insert(&mut self, key: Pubkey, pubkey: Pubkey) {
+        self.0.entry(key).or_default().insert(pubkey);
+    }
+
+    pub(crate) fn remove(&mut self, key: &Pubkey, pubkey: &Pubkey) {
+        if let Some(pubkeys) = self.0.get_mut(key) {
+            pubkeys.remove(pubkey);
+            if pubkeys.is_empty() {
+                self.0.remove(key);
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, key: &Pubkey) -> Vec<Pubkey> {
+        self.0
+            .get(key)
+            .map(|pubkeys| pubkeys.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// The accounts held in memory, in front of the store.
+///
+/// By default every account written is kept. Once a capacity is set, the least recently used
+/// accounts are evicted past it, and accounts read from the store are cached as well.
+/// Sysvar accounts are never evicted and don't count towards the capacity.
+///
+/// Reads update the recency and the hit/miss counters, so the state sits behind a mutex.
+#[derive(Default)]
+pub(crate) struct AccountCache {
+    state: Mutex<CacheState>,
+}
+
+#[derive(Clone, Default)]
+struct CacheState {
+    /// Each account along with when it was last used, if it can be evicted.
+    accounts: HashMap<Pubkey, (AccountSharedData, Option<u64>)>,
+    /// Maps each owner to the cached accounts it owns.
+    owner_index: AccountIndex,
+    /// The evictable accounts by when they were last used, only tracked with a capacity set.
+    recency: BTreeMap<u64, Pubkey>,
+    clock: u64,
+    capacity: Option<usize>,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl Clone for AccountCache {
+    fn clone(&self) -> Self {
+        Self {
+            state: Mutex::new(self.state.lock().unwrap().clone()),
+        }
+    }
+}
+
+fn is_pinned(account: &AccountSharedData) -> bool {
+    account.owner() == &solana_sdk_ids::sysvar::id()
+}
+
+impl CacheState {
+    fn touch(&mut self, pubkey: &Pubkey) {
+        if self.capacity.is_none() {
+            return;
+        }
+        let Some((account, last_used)) = self.accounts.get_mut(pubkey) else {
+            return;
+        };
+        if is_pinned(account) {
+            return;
+        }
+        if let Some(previous) = last_used.take() {
+            self.recency.remove(&previous);
+        }
+        self.clock += 1;
+        *last_used = Some(self.clock);
+        self.recency.insert(self.clock, *pubkey);
+    }
+
+    fn insert(&mut self, pubkey: Pubkey, account: AccountSharedData) -> Option<AccountSharedData> {
+        let previous = self.remove(&pubkey);
+        self.owner_index.insert(*account.owner(), pubkey);
+        self.accounts.insert(pubkey, (account, None));
+        self.touch(&pubkey);
+        self.evict();
+        previous
+    }
+
+    fn remove(&mut self, pubkey: &Pubkey) -> Option<AccountSharedData> {
+        let (account, last_used) = self.accounts.remove(pubkey)?;
+        if let Some(last_used) = last_used {
+            self.recency.remove(&last_used);
+        }
+        self.owner_index.remove(account.owner(), pubkey);
+        Some(account)
+    }
+
+    fn evict(&mut self) {
+        let Some(capacity) = self.capacity else {
+            return;
+        };
+        while self.recency.len() > capacity {
+            let Some((_, pubkey)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some((account, _)) = self.accounts.remove(&pubkey) {
+                self.owner_index.remove(account.owner(), &pubkey);
+                self.evictions += 1;
+            }
+        }
+    }
+}
+
+impl AccountCache {
+    /// Returns the cached account, recording a hit or a miss.
+    pub(crate) fn get(&self, pubkey: &Pubkey) -> Option<AccountSharedData> {
+        let mut state = self.state.lock().unwrap();
+        match state.accounts.get(pubkey) {
+            Some((account, _)) => {
+                let account = account.clone();
+                state.hits += 1;
+                state.touch(pubkey);
+                Some(account)
+            }
+            None => {
+                state.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Returns whether the account is cached, without counting a hit or a miss.
+    pub(crate) fn contains(&self, pubkey: &Pubkey) -> bool {
+        self.state.lock().unwrap().accounts.contains_key(pubkey)
+    }
+
+    /// Caches an account just read from the store, if a capacity is set.
+    ///
+    /// Without a capacity, only the accounts written are kept in memory.
+    pub(crate) fn fill(&self, pubkey: Pubkey, account: AccountSharedData) {
+        let mut state = self.state.lock().unwrap();
+        if state.capacity.is_some() {
+            state.insert(pubkey, account);
+        }
+    }
+
+    /// Caches an account that was written, returning the previously cached one.
+    pub(crate) fn insert(
+        &mut self,
+        pubkey: Pubkey,
+        account: AccountSharedData,
+    ) -> Option<AccountSharedData> {
+        self.state.get_mut().unwrap().insert(pubkey, account)
+    }
+
+    pub(crate) fn remove(&mut self, pubkey: &Pubkey) -> Option<AccountSharedData> {
+        self.state.get_mut().unwrap().remove(pubkey)
+    }
+
+    /// Returns the cached accounts owned by `owner`.
+    pub(crate) fn owned_by(&self, owner: &Pubkey) -> Vec<Pubkey> {
+        self.state.lock().unwrap().owner_index.get(owner)
+    }
+
+    /// Returns a copy of every cached account.
+    pub(crate) fn accounts(&self) -> Vec<(Pubkey, AccountSharedData)> {
+        let state = self.state.lock().unwrap();
+        state
+            .accounts
+            .iter()
+            .map(|(pubkey, (account, _))| (*pubkey, account.clone()))
+            .collect()
+    }
+
+    /// Returns a copy of the cached sysvar accounts, which are never evicted.
+    pub(crate) fn sysvars(&self) -> HashMap<Pubkey, AccountSharedData> {
+        let state = self.state.lock().unwrap();
+        state
+            .owner_index
+            .get(&solana_sdk_ids::sysvar::id())
+            .into_iter()
+            .filter_map(|pubkey| {
+                state
+                    .accounts
+                    .get(&pubkey)
+                    .map(|(account, _)| (pubkey, account.clone()))
+            })
+            .collect()
+    }
+
+    /// Bounds the number of evictable accounts kept, evicting the least recently used ones
+    /// right away if there are more. `None` keeps every account.
+    pub(crate) fn set_capacity(&mut self, capacity: Option<usize>) {
+        let state = self.state.get_mut().unwrap();
+        let tracked = state.capacity.is_some();
+        state.capacity = capacity;
+        if capacity.is_none() {
+            state.recency.clear();
+            for (_, last_used) in state.accounts.values_mut() {
+                *last_used = None;
+            }
+        } else if !tracked {
+            // the accounts kept so far have no recency yet, so they're ranked in arbitrary order
+            let keys = state.accounts.keys().copied().collect::<Vec<_>>();
+            for pubkey in keys {
+                state.touch(&pubkey);
+            }
+        }
+        state.evict();
+    }
+
+    pub(crate) fn stats(&self) -> AccountCacheStats {
+        let state = self.state.lock().unwrap();
+        AccountCacheStats {
+            hits: state.hits,
+            misses: state.misses,
+            evictions: state.evictions,
+            len: state.accounts.len(),
+            capacity: state.capacity,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use {
+    crate::error::LiteSVMError,
+    serde::{Deserialize, Serialize},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    std::path::Path,
+};
+
+/// An account in the JSON shape printed by `solana account --output json`.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct KeyedAccountJson {
+    #[serde(with = "crate::utils::serde_with_str")]
+    pub(crate) pubkey: Pubkey,
+    pub(crate) account: AccountJson,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AccountJson {
+    lamports: u64,
+    #[serde(with = "base64_data")]
+    data: Vec<u8>,
+    #[serde(with = "crate::utils::serde_with_str")]
+    owner: Pubkey,
+    executable: bool,
+    rent_epoch: u64,
+    /// Always written, but older CLI versions don't print it.
+    #[serde(default)]
+    space: Option<u64>,
+}
+
+impl KeyedAccountJson {
+    pub(crate) fn new(pubkey: &Pubkey, account: &Account) -> Self {
+        Self {
+            pubkey: *pubkey,
+            account: AccountJson {
+                lamports: account.lamports,
+                data: account.data.clone(),
+                owner: account.owner,
+                executable: account.executable,
+                rent_epoch: account.rent_epoch,
+                space: Some(account.data.len() as u64),
+            },
+        }
+    }
+}
+
+impl From<AccountJson> for Account {
+    fn from(value: AccountJson) -> Self {
+        Self {
+            lamports: value.lamports,
+            data: value.data,
+            owner: value.owner,
+            executable: value.executable,
+            rent_epoch: value.rent_epoch,
+        }
+    }
+}
+
+/// Reads an account file written by `solana account --output json`.
+pub(crate) fn read_account_file(path: &Path) -> Result<(Pubkey, Account), LiteSVMError> {
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let keyed: KeyedAccountJson =
+        serde_json::from_reader(file).map_err(|source| LiteSVMError::InvalidAccountFile {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    Ok((keyed.pubkey, keyed.account.into()))
+}
+
+/// Account data as a `[data, encoding]` pair. Only the base64 encoding is supported.
+mod base64_data {
+    use {
+        base64::{prelude::BASE64_STANDARD, Engine},
+        serde::{de, Deserialize, Deserializer, Serialize, Serializer},
+    };
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        (BASE64_STANDARD.encode(data), "base64").serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let (data, encoding) = <(String, String)>::deserialize(deserializer)?;
+        if encoding != "base64" {
+            return Err(de::Error::custom(format!(
+                "unsupported account data encoding: {encoding}"
+            )));
+        }
+        BASE64_STANDARD
+            .decode(data)
+            .map_err(|e| de::Error::custom(format!("invalid base64 account data: {e}")))
+    }
+}
@@ -0,0 +1,81 @@
+use {solana_account::AccountSharedData, solana_pubkey::Pubkey, thiserror::Error};
+#[cfg(feature = "rpc")]
+use {
+    solana_rpc_client::rpc_client::RpcClient,
+    solana_rpc_client_api::{client_error::Error as ClientError, request::MAX_MULTIPLE_ACCOUNTS},
+};
+
+#[derive(Error, Debug)]
+pub enum AccountSourceError {
+    #[cfg(feature = "rpc")]
+    #[error("RPC request failed: {0}")]
+    Rpc(#[from] Box<ClientError>),
+    #[error("{0}")]
+    Other(String),
+}
+
+pub type Result<T> = std::result::Result<T, AccountSourceError>;
+
+/// Provides the accounts missing from the store.
+///
+/// Once set with [`LiteSVM::with_account_source`](crate::LiteSVM::with_account_source),
+/// reading an account that's neither in memory nor in the store asks the source for it,
+/// and the accounts it returns are written to the store. Each address is only asked about once,
+/// so accounts it doesn't have, or that were removed since, aren't fetched again.
+pub trait AccountSource: Send + Sync {
+    /// Returns the account at `pubkey`, or `None` if it doesn't exist.
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<AccountSharedData>> {
+        Ok(self.get_multiple_accounts(&[*pubkey])?.pop().flatten())
+    }
+
+    /// Returns the account at each address, in the same order, with `None` for those
+    /// that don't exist.
+    fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<AccountSharedData>>>;
+}
+
+/// Fetches accounts from a JSON-RPC endpoint with `getAccountInfo` and `getMultipleAccounts`,
+/// at the commitment level of its client.
+#[cfg(feature = "rpc")]
+pub struct RpcAccountSource {
+    client: RpcClient,
+}
+
+#[cfg(feature = "rpc")]
+impl RpcAccountSource {
+    pub fn new(url: impl ToString) -> Self {
+        Self::from_client(RpcClient::new(url))
+    }
+
+    /// Uses a client configured with its own commitment level or timeouts.
+    pub fn from_client(client: RpcClient) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(feature = "rpc")]
+impl AccountSource for RpcAccountSource {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<AccountSharedData>> {
+        let response = self
+            .client
+            .get_account_with_commitment(pubkey, self.client.commitment())
+            .map_err(Box::new)?;
+        Ok(response.value.map(Into::into))
+    }
+
+    fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<AccountSharedData>>> {
+        let mut accounts = Vec::with_capacity(pubkeys.len());
+        for chunk in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let response = self
+                .client
+                .get_multiple_accounts_with_commitment(chunk, self.client.commitment())
+                .map_err(Box::new)?;
+            accounts.extend(
+                response
+                    .value
+                    .into_iter()
+                    .map(|account| account.map(Into::into)),
+            );
+        }
+        Ok(accounts)
+    }
+}
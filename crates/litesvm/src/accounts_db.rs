@@ -1,6 +1,12 @@
 use {
+    crate::account_cache::{AccountCache, AccountIndex},
+    crate::account_source::AccountSource,
     crate::error::{InvalidSysvarDataError, LiteSVMError},
-    crate::storage::RocksDBStore, // 引入持久化存储
+    crate::spl::{token_account_keys, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID},
+    crate::storage::{AccountStore, ForkableStore, MemoryStore, NullStore, StoreError},
+    crate::types::AccountCacheStats,
+    indexmap::IndexMap,
+    itertools::Itertools,
     log::error,
     solana_account::{state_traits::StateMut, AccountSharedData, ReadableAccount, WritableAccount},
     solana_address_lookup_table_interface::{error::AddressLookupError, state::AddressLookupTable},
@@ -14,7 +20,10 @@ use {
     },
     solana_nonce as nonce,
     solana_program_runtime::{
-        loaded_programs::{LoadProgramMetrics, ProgramCacheEntry, ProgramCacheForTxBatch},
+        loaded_programs::{
+            LoadProgramMetrics, ProgramCacheEntry, ProgramCacheEntryOwner, ProgramCacheEntryType,
+            ProgramCacheForTxBatch,
+        },
         sysvar_cache::SysvarCache,
     },
     solana_pubkey::Pubkey,
@@ -30,10 +39,15 @@ use {
     solana_system_program::{get_system_account_kind, SystemAccountKind},
     solana_sysvar::Sysvar,
     solana_transaction_error::TransactionError,
-    std::{collections::HashMap, sync::Arc},
-    tempfile::TempDir,
+    std::{
+        collections::{HashMap, HashSet},
+        sync::{Arc, Mutex},
+    },
 };
 
+/// How many accounts are written to a store at once when copying every account into it.
+const COPY_CHUNK_SIZE: usize = 1024;
+
 const FEES_ID: Pubkey = solana_pubkey::pubkey!("SysvarFees111111111111111111111111111111111");
 const RECENT_BLOCKHASHES_ID: Pubkey =
     solana_pubkey::pubkey!("SysvarRecentB1ockHashes11111111111111111111");
@@ -59,58 +73,557 @@ where
     Ok(())
 }
 
-#[derive(Clone)]
-pub(crate) struct AccountsDb {
-    inner: HashMap<Pubkey, AccountSharedData>,
-    pub(crate) programs_cache: ProgramCacheForTxBatch,
-    pub(crate) sysvar_cache: SysvarCache,
-    pub(crate) store: Arc<RocksDBStore>, // 新增 RocksDB 存储
-    _temp_dir: Option<Arc<TempDir>>,
+/// The store writes deferred while a batch is open, see [`AccountsDb::start_batch`].
+struct WriteBatch {
+    /// The latest state of each account written, or `None` if it was removed.
+    writes: IndexMap<Pubkey, Option<AccountSharedData>>,
+    /// The state of each written account before the batch, to restore memory if it fails.
+    previous: Vec<(Pubkey, Option<AccountSharedData>)>,
+    programs_cache: Arc<ProgramCacheForTxBatch>,
+    sysvar_cache: Arc<SysvarCache>,
+    undo_log_len: Option<usize>,
+    /// The undo log entries a [`AccountsDb::rollback`] in the batch took off, to put back if
+    /// it fails.
+    rolled_back: Vec<(Pubkey, Option<AccountSharedData>)>,
 }
 
-impl Default for AccountsDb {
-    fn default() -> Self {
-        // 每次构造时都新建一个临时目录
-        let tmp = tempfile::tempdir().expect("create tempdir for RocksDB");
+pub(crate) struct AccountsDb {
+    /// Also indexes its accounts by owner. The store keeps its own index for the accounts it holds.
+    inner: AccountCache,
+    /// Maps each wallet to its token accounts, including the ones only held in the store.
+    token_owner_index: AccountIndex,
+    /// Maps each mint to its token accounts, including the ones only held in the store.
+    token_mint_index: AccountIndex,
+    /// Shared with the checkpoints, and copied on the first write after taking one.
+    pub(crate) programs_cache: Arc<ProgramCacheForTxBatch>,
+    /// Shared with the checkpoints, and copied on the first write after taking one.
+    pub(crate) sysvar_cache: Arc<SysvarCache>,
+    pub(crate) store: ForkableStore,
+    /// Whether the store is the [`NullStore`] every instance starts with, which is replaced
+    /// with a [`MemoryStore`] by the features that read accounts back from the store.
+    default_store: bool,
+    /// The previous state of every account written since the oldest live checkpoint,
+    /// or `None` when there are no checkpoints to revert to.
+    undo_log: Option<Vec<(Pubkey, Option<AccountSharedData>)>>,
+    /// Whether every transaction's account writes are recorded as versions in the store.
+    record_account_history: bool,
+    /// Asked for the accounts missing from the store, if set.
+    source: Option<Arc<dyn AccountSource>>,
+    /// The addresses the source was already asked about, along with the accounts removed since
+    /// it was set, which it's never asked about again.
+    fetched: Mutex<HashSet<Pubkey>>,
+    /// The open batch, if any.
+    batch: Option<WriteBatch>,
+}
 
-        let store = Arc::new(RocksDBStore::open(tmp.path()).expect("open RocksDB in tempdir"));
-        // 调用 new 并且把 tmp 放进去保存
-        let mut db = Self::new(store);
-        db._temp_dir = Some(Arc::new(tmp));
-        db
+impl Clone for AccountsDb {
+    /// The clone gets its own copy-on-write overlay on top of this store, which keeps the state
+    /// this store overwrites afterwards, so neither instance sees what the other writes.
+    /// The default [`NullStore`] holds nothing to share, so the clone gets its own.
+    fn clone(&self) -> Self {
+        let store: Arc<dyn AccountStore> = if self.default_store {
+            Arc::new(NullStore)
+        } else {
+            self.store.fork()
+        };
+        Self {
+            inner: self.inner.clone(),
+            token_owner_index: self.token_owner_index.clone(),
+            token_mint_index: self.token_mint_index.clone(),
+            programs_cache: self.programs_cache.clone(),
+            sysvar_cache: self.sysvar_cache.clone(),
+            store: ForkableStore::new(store),
+            default_store: self.default_store,
+            undo_log: self.undo_log.clone(),
+            record_account_history: self.record_account_history,
+            source: self.source.clone(),
+            fetched: Mutex::new(self.fetched.lock().unwrap().clone()),
+            batch: None,
+        }
     }
 }
 
-impl Drop for AccountsDb {
-    fn drop(&mut self) {
-        // db.close() 自动完成，但你可以 log 一下
-        eprintln!("AccountsDB dropped and RocksDB closed");
+impl Default for AccountsDb {
+    fn default() -> Self {
+        Self {
+            default_store: true,
+            ..Self::new(Arc::new(NullStore))
+        }
     }
 }
 
 impl AccountsDb {
-    pub fn new(store: Arc<RocksDBStore>) -> Self {
+    pub fn new(store: Arc<dyn AccountStore>) -> Self {
         Self {
-            inner: HashMap::new(),
-            programs_cache: ProgramCacheForTxBatch::default(),
-            sysvar_cache: SysvarCache::default(),
-            store,
-            _temp_dir: None,
+            inner: AccountCache::default(),
+            token_owner_index: AccountIndex::default(),
+            token_mint_index: AccountIndex::default(),
+            programs_cache: Arc::default(),
+            sysvar_cache: Arc::default(),
+            store: ForkableStore::new(store),
+            default_store: false,
+            undo_log: None,
+            record_account_history: false,
+            source: None,
+            fetched: Mutex::default(),
+            batch: None,
+        }
+    }
+
+    /// Switches to a different store, copying every account currently known into it.
+    pub(crate) fn set_store(&mut self, store: Arc<dyn AccountStore>) -> Result<(), LiteSVMError> {
+        self.copy_accounts_to(store.as_ref())?;
+        self.store = ForkableStore::new(store);
+        self.default_store = false;
+        Ok(())
+    }
+
+    /// Replaces the default [`NullStore`] with a [`MemoryStore`], for the features that read
+    /// accounts back from the store. A store that was set explicitly is kept.
+    fn require_store(&mut self) {
+        if !self.default_store {
+            return;
+        }
+        // nothing is evicted without a store to read it back from, so every account is cached
+        self.store =
+            ForkableStore::new(Arc::new(MemoryStore::from_accounts(self.inner.accounts())));
+        self.default_store = false;
+    }
+
+    pub(crate) fn set_account_source(&mut self, source: Arc<dyn AccountSource>) {
+        self.require_store();
+        self.source = Some(source);
+    }
+
+    pub(crate) fn set_cache_size(&mut self, size: Option<usize>) {
+        if size.is_some() {
+            self.require_store();
+        }
+        self.inner.set_capacity(size);
+    }
+
+    pub(crate) fn set_record_account_history(&mut self, enabled: bool) {
+        if enabled {
+            self.require_store();
+        }
+        self.record_account_history = enabled;
+    }
+
+    pub(crate) fn cache_stats(&self) -> AccountCacheStats {
+        self.inner.stats()
+    }
+
+    /// Iterates over every account known, whether it's held in memory or only in the store.
+    ///
+    /// The accounts held in memory come first, followed by the ones an open batch wrote that
+    /// were evicted since. The ones only held in the store follow, read from the store as the
+    /// iteration goes.
+    pub(crate) fn iter_accounts(
+        &self,
+    ) -> Result<
+        impl Iterator<Item = Result<(Pubkey, AccountSharedData), StoreError>> + '_,
+        StoreError,
+    > {
+        let mut accounts = self.inner.accounts();
+        let mut known_keys = accounts
+            .iter()
+            .map(|(pubkey, _)| *pubkey)
+            .collect::<HashSet<_>>();
+        if let Some(batch) = &self.batch {
+            for (pubkey, account) in &batch.writes {
+                if known_keys.insert(*pubkey) {
+                    accounts.extend(account.clone().map(|account| (*pubkey, account)));
+                }
+            }
+        }
+        let stored = self.store.iter_accounts()?.filter(move |item| {
+            item.as_ref()
+                .map_or(true, |(pubkey, _)| !known_keys.contains(pubkey))
+        });
+        Ok(accounts.into_iter().map(Ok).chain(stored))
+    }
+
+    /// Writes every account known to `store`, [`COPY_CHUNK_SIZE`] accounts at a time.
+    pub(crate) fn copy_accounts_to(&self, store: &dyn AccountStore) -> Result<(), LiteSVMError> {
+        for chunk in &self.iter_accounts()?.chunks(COPY_CHUNK_SIZE) {
+            store.put_accounts(&chunk.collect::<Result<Vec<_>, _>>()?)?;
+        }
+        Ok(())
+    }
+
+    /// Starts recording account writes if it isn't already,
+    /// and returns the position a later [`rollback`](Self::rollback) can return to.
+    pub(crate) fn undo_log_position(&mut self) -> usize {
+        self.undo_log.get_or_insert_with(Vec::new).len()
+    }
+
+    /// Stops recording account writes and drops the recorded history.
+    pub(crate) fn clear_undo_log(&mut self) {
+        self.undo_log = None;
+    }
+
+    /// Defers the store writes of [`add_account`](Self::add_account),
+    /// [`add_account_no_checks`](Self::add_account_no_checks) and
+    /// [`remove_account`](Self::remove_account) until [`commit_batch`](Self::commit_batch)
+    /// writes them all at once. Memory is updated right away, so reads see the writes.
+    pub(crate) fn start_batch(&mut self) {
+        debug_assert!(self.batch.is_none(), "batches can't be nested");
+        self.batch = Some(WriteBatch {
+            writes: IndexMap::new(),
+            previous: Vec::new(),
+            programs_cache: self.programs_cache.clone(),
+            sysvar_cache: self.sysvar_cache.clone(),
+            undo_log_len: self.undo_log.as_ref().map(Vec::len),
+            rolled_back: Vec::new(),
+        });
+    }
+
+    /// Writes the accounts written since [`start_batch`](Self::start_batch) to the store
+    /// along with `metadata`, in a single
+    /// [`write_accounts_with_metadata`](AccountStore::write_accounts_with_metadata) call.
+    ///
+    /// If the store fails, the batch is aborted as with [`abort_batch`](Self::abort_batch).
+    pub(crate) fn commit_batch(&mut self, metadata: &[u8]) -> Result<(), LiteSVMError> {
+        let Some(batch) = self.batch.take() else {
+            return Ok(());
+        };
+        let writes = batch
+            .writes
+            .iter()
+            .map(|(pubkey, account)| (*pubkey, account.clone()))
+            .collect::<Vec<_>>();
+        if let Err(e) = self.store.write_accounts_with_metadata(&writes, metadata) {
+            self.restore_batch(batch);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Drops the writes made since [`start_batch`](Self::start_batch), restoring the accounts
+    /// held in memory, the caches and the undo log as they were.
+    pub(crate) fn abort_batch(&mut self) {
+        if let Some(batch) = self.batch.take() {
+            self.restore_batch(batch);
         }
     }
 
+    fn restore_batch(&mut self, batch: WriteBatch) {
+        for (pubkey, previous) in batch.previous.into_iter().rev() {
+            match previous {
+                Some(account) => self.insert_inner(pubkey, account),
+                None => self.remove_inner(&pubkey),
+            }
+        }
+        self.programs_cache = batch.programs_cache;
+        self.sysvar_cache = batch.sysvar_cache;
+        if let (Some(undo_log), Some(len)) = (self.undo_log.as_mut(), batch.undo_log_len) {
+            undo_log.truncate(len - batch.rolled_back.len());
+            undo_log.extend(batch.rolled_back);
+        }
+    }
+
+    /// Writes an account to the store, or removes it for `None`, unless a batch is open,
+    /// in which case the write is added to the batch.
+    fn write_to_store(
+        &mut self,
+        pubkey: &Pubkey,
+        account: Option<&AccountSharedData>,
+    ) -> Result<(), LiteSVMError> {
+        if self.batch.is_none() {
+            match account {
+                Some(account) => self.store.put_account(pubkey, account)?,
+                None => self.store.delete_account(pubkey)?,
+            }
+            return Ok(());
+        }
+        let first_write = !self.batch.as_ref().unwrap().writes.contains_key(pubkey);
+        let previous = if first_write {
+            Some(self.get_local_account(pubkey)?)
+        } else {
+            None
+        };
+        let batch = self.batch.as_mut().unwrap();
+        if let Some(previous) = previous {
+            batch.previous.push((*pubkey, previous));
+        }
+        batch.writes.insert(*pubkey, account.cloned());
+        Ok(())
+    }
+
+    fn record_undo(&mut self, pubkey: &Pubkey) -> Result<(), LiteSVMError> {
+        if self.undo_log.is_some() {
+            // an account never fetched from the source is fetched again after reverting
+            let previous = self.get_local_account(pubkey)?;
+            if let Some(undo_log) = self.undo_log.as_mut() {
+                undo_log.push((*pubkey, previous));
+            }
+        }
+        Ok(())
+    }
+
+    /// Undoes every account write recorded after `position`, newest first.
+    ///
+    /// The caches are left untouched, so the caller is responsible for restoring them.
+    /// In a batch, the rollback must come before any write.
+    pub(crate) fn rollback(&mut self, position: usize) -> Result<(), LiteSVMError> {
+        let Some(undo_log) = self.undo_log.as_mut() else {
+            return Ok(());
+        };
+        debug_assert!(self
+            .batch
+            .as_ref()
+            .map_or(true, |batch| batch.writes.is_empty()));
+        let entries = undo_log.split_off(position.min(undo_log.len()));
+        let result = entries.iter().rev().try_for_each(|(pubkey, previous)| {
+            self.write_to_store(pubkey, previous.as_ref())?;
+            match previous {
+                Some(account) => self.insert_inner(*pubkey, account.clone()),
+                None => self.remove_inner(pubkey),
+            }
+            Ok(())
+        });
+        if let Some(batch) = self.batch.as_mut() {
+            batch.rolled_back = entries;
+        }
+        result
+    }
+
+    fn insert_inner(&mut self, pubkey: Pubkey, account: AccountSharedData) {
+        self.remove_inner(&pubkey);
+        self.index_token_account(&pubkey, &account);
+        self.inner.insert(pubkey, account);
+    }
+
+    fn remove_inner(&mut self, pubkey: &Pubkey) {
+        if let Some(previous) = self.inner.remove(pubkey) {
+            if let Some((mint, owner)) = token_account_keys(&previous) {
+                self.token_mint_index.remove(&mint, pubkey);
+                self.token_owner_index.remove(&owner, pubkey);
+            }
+        }
+    }
+
+    fn index_token_account(&mut self, pubkey: &Pubkey, account: &AccountSharedData) {
+        if let Some((mint, owner)) = token_account_keys(account) {
+            self.token_mint_index.insert(mint, *pubkey);
+            self.token_owner_index.insert(owner, *pubkey);
+        }
+    }
+
+    /// Returns every account owned by `owner`, sorted by address.
+    pub(crate) fn get_program_accounts(
+        &self,
+        owner: &Pubkey,
+    ) -> Result<Vec<(Pubkey, AccountSharedData)>, LiteSVMError> {
+        let mut keys = self.inner.owned_by(owner);
+        keys.extend(self.store.get_program_account_keys(owner)?);
+        if let Some(batch) = &self.batch {
+            keys.extend(batch.writes.keys());
+        }
+        keys.sort_unstable();
+        keys.dedup();
+        // the store's index can be stale for accounts that are also held in memory
+        Ok(keys
+            .into_iter()
+            .filter_map(|pubkey| {
+                self.get_account(&pubkey)
+                    .filter(|account| account.owner() == owner)
+                    .map(|account| (pubkey, account))
+            })
+            .collect())
+    }
+
+    /// Returns the SPL Token and Token-2022 accounts owned by `owner`, sorted by address.
+    pub(crate) fn get_token_accounts_by_owner(
+        &self,
+        owner: &Pubkey,
+    ) -> Vec<(Pubkey, AccountSharedData)> {
+        self.get_token_accounts(&self.token_owner_index, |(_, o)| o == *owner, owner)
+    }
+
+    /// Returns the SPL Token and Token-2022 accounts holding `mint`, sorted by address.
+    pub(crate) fn get_token_accounts_by_mint(
+        &self,
+        mint: &Pubkey,
+    ) -> Vec<(Pubkey, AccountSharedData)> {
+        self.get_token_accounts(&self.token_mint_index, |(m, _)| m == *mint, mint)
+    }
+
+    fn get_token_accounts(
+        &self,
+        index: &AccountIndex,
+        matches: impl Fn((Pubkey, Pubkey)) -> bool,
+        key: &Pubkey,
+    ) -> Vec<(Pubkey, AccountSharedData)> {
+        let mut keys = index.get(key);
+        keys.sort_unstable();
+        // accounts indexed while loading the store may have changed since
+        keys.into_iter()
+            .filter_map(|pubkey| {
+                self.get_account(&pubkey)
+                    .filter(|account| token_account_keys(account).is_some_and(&matches))
+                    .map(|account| (pubkey, account))
+            })
+            .collect()
+    }
+
+    /// Returns the account at `pubkey`, asking the account source if it's missing locally.
+    ///
+    /// Store read failures are logged and treated as a missing account.
     pub(crate) fn get_account(&self, pubkey: &Pubkey) -> Option<AccountSharedData> {
-        self.inner
-            .get(pubkey)
-            .cloned()
-            .or_else(|| self.store.get_account(pubkey).ok().flatten())
+        match self.get_local_account(pubkey) {
+            Ok(Some(account)) => Some(account),
+            Ok(None) => self.fetch_account(pubkey),
+            Err(e) => {
+                error!("Failed to read account {pubkey} from the store: {e}");
+                None
+            }
+        }
+    }
+
+    /// Returns the account held in memory or in the store, without asking the account source.
+    fn get_local_account(&self, pubkey: &Pubkey) -> Result<Option<AccountSharedData>, StoreError> {
+        if let Some(account) = self.inner.get(pubkey) {
+            return Ok(Some(account));
+        }
+        // the account may have been evicted before the batch writing it reached the store
+        if let Some(account) = self
+            .batch
+            .as_ref()
+            .and_then(|batch| batch.writes.get(pubkey))
+        {
+            return Ok(account.clone());
+        }
+        let account = self.store.get_account(pubkey)?;
+        if let Some(account) = &account {
+            self.inner.fill(*pubkey, account.clone());
+        }
+        Ok(account)
+    }
+
+    /// Asks the account source for an account missing from the store, and writes it to the store.
+    ///
+    /// The program data of an upgradeable program is fetched along with it. Since reads can't
+    /// update the program cache, a program fetched here is loaded by each transaction using it
+    /// until it's written or prefetched by [`prefetch_accounts`](Self::prefetch_accounts).
+    fn fetch_account(&self, pubkey: &Pubkey) -> Option<AccountSharedData> {
+        let source = self.source.as_ref()?;
+        if !self.fetched.lock().unwrap().insert(*pubkey) {
+            return None;
+        }
+        let account = match source.get_account(pubkey) {
+            Ok(account) => account?,
+            Err(e) => {
+                error!("Failed to fetch account {pubkey}: {e}");
+                // try again on the next read
+                self.fetched.lock().unwrap().remove(pubkey);
+                return None;
+            }
+        };
+        if let Err(e) = self.store.put_account(pubkey, &account) {
+            error!("Failed to store fetched account {pubkey}: {e}");
+        }
+        self.inner.fill(*pubkey, account.clone());
+        if let Some(programdata_address) = programdata_address(&account) {
+            self.get_account(&programdata_address);
+        }
+        Some(account)
+    }
+
+    /// Fetches the accounts among `pubkeys` that are missing from the store with a single
+    /// request to the account source, along with the program data of the upgradeable programs
+    /// among them, and loads the programs into the program cache.
+    ///
+    /// Failures are only logged, since the accounts are fetched one by one when read otherwise.
+    pub(crate) fn prefetch_accounts(&mut self, pubkeys: &[Pubkey]) {
+        let Some(source) = self.source.clone() else {
+            return;
+        };
+        let mut writes = self.fetch_missing(source.as_ref(), pubkeys);
+        let programdata = writes
+            .iter()
+            .filter_map(|(_, account)| account.as_ref().and_then(programdata_address))
+            .collect::<Vec<_>>();
+        writes.extend(self.fetch_missing(source.as_ref(), &programdata));
+        if let Err(e) = self.write_and_load_accounts(writes, false) {
+            error!("Failed to store fetched accounts: {e}");
+        }
+    }
+
+    fn fetch_missing(
+        &mut self,
+        source: &dyn AccountSource,
+        pubkeys: &[Pubkey],
+    ) -> Vec<(Pubkey, Option<AccountSharedData>)> {
+        let missing = pubkeys
+            .iter()
+            .copied()
+            .unique()
+            .filter(|pubkey| {
+                !self.fetched.get_mut().unwrap().contains(pubkey)
+                    && !self.inner.contains(pubkey)
+                    && match self.store.get_account(pubkey) {
+                        Ok(account) => account.is_none(),
+                        Err(e) => {
+                            // don't overwrite an account the store failed to read
+                            error!("Failed to read account {pubkey} from the store: {e}");
+                            false
+                        }
+                    }
+            })
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            return vec![];
+        }
+        match source.get_multiple_accounts(&missing) {
+            Ok(accounts) => {
+                self.fetched.get_mut().unwrap().extend(&missing);
+                missing
+                    .into_iter()
+                    .zip(accounts)
+                    .filter(|(_, account)| account.is_some())
+                    .collect()
+            }
+            Err(e) => {
+                error!("Failed to fetch accounts: {e}");
+                vec![]
+            }
+        }
+    }
+
+    /// Loads the programs among `accounts` that are missing from `programs_cache`,
+    /// which happens for programs fetched from the account source by a read.
+    pub(crate) fn load_fetched_programs(
+        &self,
+        accounts: &[(Pubkey, AccountSharedData)],
+        programs_cache: &mut ProgramCacheForTxBatch,
+    ) {
+        if self.source.is_none() {
+            return;
+        }
+        for (pubkey, account) in accounts {
+            if is_loadable_program(pubkey, account) && programs_cache.find(pubkey).is_none() {
+                match self.load_program(account) {
+                    Ok(loaded_program) => {
+                        programs_cache.replenish(*pubkey, Arc::new(loaded_program));
+                    }
+                    Err(e) => error!("Failed to load fetched program {pubkey}: {e}"),
+                }
+            }
+        }
     }
 
     /// We should only use this when we know we're not touching any executable or sysvar accounts,
     /// or have already handled such cases.
-    pub(crate) fn add_account_no_checks(&mut self, pubkey: Pubkey, account: AccountSharedData) {
-        self.store.put_account(&pubkey, &account).ok();
-        self.inner.insert(pubkey, account);
+    pub(crate) fn add_account_no_checks(
+        &mut self,
+        pubkey: Pubkey,
+        account: AccountSharedData,
+    ) -> Result<(), LiteSVMError> {
+        self.record_undo(&pubkey)?;
+        self.write_to_store(&pubkey, Some(&account))?;
+        self.insert_inner(pubkey, account);
+        Ok(())
     }
 
     pub(crate) fn add_account(
@@ -118,20 +631,79 @@ impl AccountsDb {
         pubkey: Pubkey,
         account: AccountSharedData,
     ) -> Result<(), LiteSVMError> {
-        if account.executable()
-            && pubkey != Pubkey::default()
-            && account.owner() != &native_loader::ID
-        {
-            let loaded_program = self.load_program(&account)?;
-            self.programs_cache
-                .replenish(pubkey, Arc::new(loaded_program));
+        self.update_caches(pubkey, &account)?;
+        self.add_account_no_checks(pubkey, account)
+    }
+
+    /// Loads the account into the program cache if it's a program,
+    /// or into the sysvar cache if it's a sysvar.
+    fn update_caches(
+        &mut self,
+        pubkey: Pubkey,
+        account: &AccountSharedData,
+    ) -> Result<(), LiteSVMError> {
+        if is_loadable_program(&pubkey, account) {
+            let loaded_program = self.load_program(account)?;
+            Arc::make_mut(&mut self.programs_cache).replenish(pubkey, Arc::new(loaded_program));
         } else {
+            self.maybe_handle_sysvar_account(pubkey, account)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the sysvar and program caches and the token indexes from the accounts held in the store.
+    ///
+    /// Only the accounts owned by the sysvar program, the loaders and the token programs are read,
+    /// looked up through the store's owner index. A program that fails to load is logged and left
+    /// out of the program cache, so transactions invoking it fail instead of the whole store.
+    ///
+    /// The builtins and program runtime environments must already be set up.
+    pub(crate) fn load_caches_from_store(&mut self) -> Result<(), LiteSVMError> {
+        // sysvars go first so the clock is available when loading programs
+        for (pubkey, account) in self.stored_accounts_owned_by(&solana_sdk_ids::sysvar::id())? {
             self.maybe_handle_sysvar_account(pubkey, &account)?;
+            self.insert_inner(pubkey, account);
+        }
+        for loader in [
+            bpf_loader::id(),
+            bpf_loader_deprecated::id(),
+            bpf_loader_upgradeable::id(),
+            loader_v4::id(),
+        ] {
+            for (pubkey, account) in self.stored_accounts_owned_by(&loader)? {
+                if !is_loadable_program(&pubkey, &account) {
+                    continue;
+                }
+                match self.load_program(&account) {
+                    Ok(loaded_program) => {
+                        Arc::make_mut(&mut self.programs_cache)
+                            .replenish(pubkey, Arc::new(loaded_program));
+                    }
+                    Err(e) => error!("Failed to load program {pubkey}, skipping it: {e}"),
+                }
+            }
+        }
+        for token_program in [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID] {
+            for (pubkey, account) in self.stored_accounts_owned_by(&token_program)? {
+                self.index_token_account(&pubkey, &account);
+            }
         }
-        self.add_account_no_checks(pubkey, account);
         Ok(())
     }
 
+    fn stored_accounts_owned_by(
+        &self,
+        owner: &Pubkey,
+    ) -> Result<Vec<(Pubkey, AccountSharedData)>, StoreError> {
+        let mut accounts = Vec::new();
+        for pubkey in self.store.get_program_account_keys(owner)? {
+            if let Some(account) = self.store.get_account(&pubkey)? {
+                accounts.push((pubkey, account));
+            }
+        }
+        Ok(accounts)
+    }
+
     fn maybe_handle_sysvar_account(
         &mut self,
         pubkey: Pubkey,
@@ -141,14 +713,14 @@ impl AccountsDb {
             EpochRewards, EpochSchedule, Fees, LastRestartSlot, RecentBlockhashes, Rent,
             SlotHashes, StakeHistory,
         };
-        let cache = &mut self.sysvar_cache;
+        let cache = Arc::make_mut(&mut self.sysvar_cache);
         #[allow(deprecated)]
         match pubkey {
             CLOCK_ID => {
                 let parsed: Clock = bincode::deserialize(account.data())
                     .map_err(|_| InvalidSysvarDataError::Clock)?;
-                self.programs_cache.set_slot_for_tests(parsed.slot);
-                let mut accounts_clone = self.inner.clone();
+                Arc::make_mut(&mut self.programs_cache).set_slot_for_tests(parsed.slot);
+                let mut accounts_clone = self.inner.sysvars();
                 accounts_clone.insert(pubkey, account.clone());
                 cache.reset();
                 cache.fill_missing_entries(|pubkey, set_sysvar| {
@@ -162,7 +734,7 @@ impl AccountsDb {
                     cache,
                     EpochRewards,
                     account,
-                    self.inner.clone(),
+                    self.inner.sysvars(),
                     pubkey,
                 )?;
             }
@@ -171,7 +743,7 @@ impl AccountsDb {
                     cache,
                     EpochSchedule,
                     account,
-                    self.inner.clone(),
+                    self.inner.sysvars(),
                     pubkey,
                 )?;
             }
@@ -180,7 +752,7 @@ impl AccountsDb {
                     cache,
                     Fees,
                     account,
-                    self.inner.clone(),
+                    self.inner.sysvars(),
                     pubkey,
                 )?;
             }
@@ -189,7 +761,7 @@ impl AccountsDb {
                     cache,
                     LastRestartSlot,
                     account,
-                    self.inner.clone(),
+                    self.inner.sysvars(),
                     pubkey,
                 )?;
             }
@@ -198,7 +770,7 @@ impl AccountsDb {
                     cache,
                     RecentBlockhashes,
                     account,
-                    self.inner.clone(),
+                    self.inner.sysvars(),
                     pubkey,
                 )?;
             }
@@ -207,7 +779,7 @@ impl AccountsDb {
                     cache,
                     Rent,
                     account,
-                    self.inner.clone(),
+                    self.inner.sysvars(),
                     pubkey,
                 )?;
             }
@@ -216,7 +788,7 @@ impl AccountsDb {
                     cache,
                     SlotHashes,
                     account,
-                    self.inner.clone(),
+                    self.inner.sysvars(),
                     pubkey,
                 )?;
             }
@@ -225,7 +797,7 @@ impl AccountsDb {
                     cache,
                     StakeHistory,
                     account,
-                    self.inner.clone(),
+                    self.inner.sysvars(),
                     pubkey,
                 )?;
             }
@@ -235,32 +807,223 @@ impl AccountsDb {
     }
 
     /// Skip the executable() checks for builtin accounts
-    pub(crate) fn add_builtin_account(&mut self, pubkey: Pubkey, data: AccountSharedData) {
-        self.store.put_account(&pubkey, &data).ok();
-        self.inner.insert(pubkey, data);
+    pub(crate) fn add_builtin_account(
+        &mut self,
+        pubkey: Pubkey,
+        data: AccountSharedData,
+    ) -> Result<(), LiteSVMError> {
+        self.record_undo(&pubkey)?;
+        self.write_to_store(&pubkey, Some(&data))?;
+        self.insert_inner(pubkey, data);
+        Ok(())
     }
 
+    /// Commits the accounts written by a transaction.
+    ///
+    /// They're written to the store in a single [`write_accounts`](AccountStore::write_accounts)
+    /// call, so stores that support it apply all of them or none. The programs and sysvars among
+    /// them are loaded first, and memory is only updated once the store write succeeded, so a
+    /// failure leaves both the store and memory as they were.
     pub(crate) fn sync_accounts(
         &mut self,
-        mut accounts: Vec<(Pubkey, AccountSharedData)>,
+        accounts: Vec<(Pubkey, AccountSharedData)>,
+    ) -> Result<(), LiteSVMError> {
+        // read the previous state before the store gets overwritten
+        let previous = accounts
+            .iter()
+            .map(|(pubkey, _)| self.get_local_account(pubkey))
+            .collect::<Result<Vec<_>, _>>()?;
+        // like the validator, don't keep accounts whose lamports were drained
+        let writes = accounts
+            .into_iter()
+            .map(|(pubkey, acc)| (pubkey, (acc.lamports() != 0).then_some(acc)))
+            .collect::<Vec<_>>();
+        let loaded_programs = writes
+            .iter()
+            .filter_map(|(pubkey, acc)| {
+                let acc = acc
+                    .as_ref()
+                    .filter(|acc| is_loadable_program(pubkey, acc))?;
+                Some(
+                    self.load_program_with(acc, &writes)
+                        .map(|loaded_program| (*pubkey, loaded_program)),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let sysvar_cache = writes
+            .iter()
+            .any(|(_, acc)| {
+                acc.as_ref()
+                    .is_some_and(|acc| acc.owner() == &solana_sdk_ids::sysvar::id())
+            })
+            .then(|| self.sysvar_cache.clone());
+        let result = writes
+            .iter()
+            .try_for_each(|(pubkey, acc)| match acc {
+                Some(acc) if acc.owner() == &solana_sdk_ids::sysvar::id() => {
+                    self.maybe_handle_sysvar_account(*pubkey, acc)
+                }
+                _ => Ok(()),
+            })
+            .map_err(LiteSVMError::from)
+            .and_then(|()| Ok(self.store.write_accounts(&writes)?));
+        if let Err(e) = result {
+            if let Some(sysvar_cache) = sysvar_cache {
+                self.sysvar_cache = sysvar_cache;
+            }
+            return Err(e);
+        }
+        if self.record_account_history {
+            let slot = self.sysvar_cache.get_clock().unwrap_or_default().slot;
+            // the transaction is already committed, so losing its history isn't worth failing it
+            if let Err(e) = self.store.put_account_versions(slot, &writes) {
+                error!("Failed to record account history: {e}");
+            }
+        }
+        if let Some(undo_log) = self.undo_log.as_mut() {
+            undo_log.extend(
+                writes
+                    .iter()
+                    .map(|(pubkey, _)| *pubkey)
+                    .zip(previous.iter().cloned()),
+            );
+        }
+        for (pubkey, loaded_program) in loaded_programs {
+            Arc::make_mut(&mut self.programs_cache).replenish(pubkey, Arc::new(loaded_program));
+        }
+        for ((pubkey, acc), previous) in writes.into_iter().zip(previous) {
+            match (acc, previous) {
+                (Some(acc), _) => self.insert_inner(pubkey, acc),
+                (None, Some(previous)) => self.forget_account(&pubkey, &previous),
+                (None, None) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes accounts imported from elsewhere, or deletes them for `None`, in a single store
+    /// write, then loads the sysvars and programs among them.
+    #[cfg(feature = "agave-snapshot")]
+    pub(crate) fn import_accounts(
+        &mut self,
+        writes: Vec<(Pubkey, Option<AccountSharedData>)>,
     ) -> Result<(), LiteSVMError> {
-        // need to add programdata accounts first if there are any
-        itertools::partition(&mut accounts, |x| {
-            x.1.owner() == &bpf_loader_upgradeable::id()
-                && x.1.data().first().is_some_and(|byte| *byte == 3)
+        self.write_and_load_accounts(writes, true)
+    }
+
+    /// Writes many accounts, or deletes them for `None`, in a single store write,
+    /// then loads the sysvars and programs among them.
+    ///
+    /// Programs that fail to load are still written but left out of the program cache,
+    /// since accounts from a real cluster can hold programs the current verifier rejects.
+    ///
+    /// The writes are only recorded in the undo log if `record_undo` is set. Accounts fetched
+    /// from the account source aren't, since they were already there as far as the
+    /// transactions are concerned.
+    fn write_and_load_accounts(
+        &mut self,
+        mut writes: Vec<(Pubkey, Option<AccountSharedData>)>,
+        record_undo: bool,
+    ) -> Result<(), LiteSVMError> {
+        // sysvars go first so the clock is set when loading programs, and programs go last
+        // so their program data is found
+        writes.sort_by_key(|(_, account)| {
+            account.as_ref().map_or((true, false), |account| {
+                (
+                    account.owner() != &solana_sdk_ids::sysvar::id(),
+                    account.executable(),
+                )
+            })
         });
-        for (pubkey, acc) in &accounts {
-            self.store.put_account(pubkey, acc).ok();
+        let previous = writes
+            .iter()
+            .map(|(pubkey, _)| self.get_local_account(pubkey))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.store.write_accounts(&writes)?;
+        if let Some(undo_log) = self.undo_log.as_mut().filter(|_| record_undo) {
+            undo_log.extend(
+                writes
+                    .iter()
+                    .map(|(pubkey, _)| *pubkey)
+                    .zip(previous.iter().cloned()),
+            );
         }
-        for (pubkey, acc) in accounts {
-            self.add_account(pubkey, acc)?;
+        for ((pubkey, account), previous) in writes.into_iter().zip(previous) {
+            match (account, previous) {
+                (Some(account), _) if is_loadable_program(&pubkey, &account) => {
+                    match self.load_program(&account) {
+                        Ok(loaded_program) => {
+                            Arc::make_mut(&mut self.programs_cache)
+                                .replenish(pubkey, Arc::new(loaded_program));
+                        }
+                        Err(e) => error!("Failed to load imported program {pubkey}: {e}"),
+                    }
+                    self.insert_inner(pubkey, account);
+                }
+                (Some(account), _) => {
+                    self.maybe_handle_sysvar_account(pubkey, &account)?;
+                    self.insert_inner(pubkey, account);
+                }
+                (None, Some(previous)) => self.forget_account(&pubkey, &previous),
+                (None, None) => {}
+            }
         }
         Ok(())
     }
 
+    /// Removes an account from memory and from the store.
+    ///
+    /// The account source isn't asked for it, but it's never fetched from the source afterwards.
+    pub(crate) fn remove_account(&mut self, pubkey: &Pubkey) -> Result<(), LiteSVMError> {
+        let Some(account) = self.get_local_account(pubkey)? else {
+            if self.source.is_some() {
+                self.fetched.get_mut().unwrap().insert(*pubkey);
+            }
+            return Ok(());
+        };
+        self.record_undo(pubkey)?;
+        self.write_to_store(pubkey, None)?;
+        self.forget_account(pubkey, &account);
+        Ok(())
+    }
+
+    /// Removes an account that was deleted from the store from memory.
+    ///
+    /// If the account was a program, its program cache entry is replaced with a tombstone.
+    fn forget_account(&mut self, pubkey: &Pubkey, account: &AccountSharedData) {
+        self.remove_inner(pubkey);
+        if self.source.is_some() {
+            // don't bring it back from the source
+            self.fetched.get_mut().unwrap().insert(*pubkey);
+        }
+        if is_loadable_program(pubkey, account) {
+            if let Ok(owner) = ProgramCacheEntryOwner::try_from(account.owner()) {
+                let slot = self.sysvar_cache.get_clock().unwrap_or_default().slot;
+                Arc::make_mut(&mut self.programs_cache).replenish(
+                    *pubkey,
+                    Arc::new(ProgramCacheEntry::new_tombstone(
+                        slot,
+                        owner,
+                        ProgramCacheEntryType::Closed,
+                    )),
+                );
+            }
+        }
+    }
+
     fn load_program(
         &self,
         program_account: &AccountSharedData,
+    ) -> Result<ProgramCacheEntry, InstructionError> {
+        self.load_program_with(program_account, &[])
+    }
+
+    /// Loads a program whose program data may be among the `pending` writes,
+    /// which take precedence over the accounts already committed.
+    fn load_program_with(
+        &self,
+        program_account: &AccountSharedData,
+        pending: &[(Pubkey, Option<AccountSharedData>)],
     ) -> Result<ProgramCacheEntry, InstructionError> {
         let metrics = &mut LoadProgramMetrics::default();
 
@@ -292,7 +1055,14 @@ impl AccountsDb {
                 );
                 return Err(InstructionError::InvalidAccountData);
             };
-            let programdata_account = self.get_account(&programdata_address).ok_or_else(|| {
+            let programdata_account = match pending
+                .iter()
+                .find(|(pubkey, _)| pubkey == &programdata_address)
+            {
+                Some((_, account)) => account.clone(),
+                None => self.get_account(&programdata_address),
+            }
+            .ok_or_else(|| {
                 error!("Program data account {programdata_address} not found");
                 InstructionError::MissingAccount
             })?;
@@ -377,14 +1147,16 @@ impl AccountsDb {
         }
     }
 
+    /// Returns the account at `pubkey` with the fee of a failed transaction charged,
+    /// to be committed with [`sync_accounts`](Self::sync_accounts).
     pub(crate) fn withdraw(
-        &mut self,
+        &self,
         pubkey: &Pubkey,
         lamports: u64,
-    ) -> solana_transaction_error::TransactionResult<()> {
-        match self.inner.get_mut(pubkey) {
-            Some(account) => {
-                let min_balance = match get_system_account_kind(account) {
+    ) -> solana_transaction_error::TransactionResult<AccountSharedData> {
+        match self.get_account(pubkey) {
+            Some(mut account) => {
+                let min_balance = match get_system_account_kind(&account) {
                     Some(SystemAccountKind::Nonce) => self
                         .sysvar_cache
                         .get_rent()
@@ -401,8 +1173,7 @@ impl AccountsDb {
                     .checked_sub_lamports(lamports)
                     .map_err(|_| TransactionError::InsufficientFundsForFee)?;
 
-                self.store.put_account(pubkey, account).ok();
-                Ok(())
+                Ok(account)
             }
             None => {
                 error!("Account {pubkey} not found when trying to withdraw fee.");
@@ -412,6 +1183,23 @@ impl AccountsDb {
     }
 }
 
+fn is_loadable_program(pubkey: &Pubkey, account: &AccountSharedData) -> bool {
+    account.executable() && pubkey != &Pubkey::default() && account.owner() != &native_loader::ID
+}
+
+/// Returns the address of the program data account if this is an upgradeable program.
+fn programdata_address(account: &AccountSharedData) -> Option<Pubkey> {
+    if !bpf_loader_upgradeable::check_id(account.owner()) {
+        return None;
+    }
+    match account.state() {
+        Ok(UpgradeableLoaderState::Program {
+            programdata_address,
+        }) => Some(programdata_address),
+        _ => None,
+    }
+}
+
 fn into_address_loader_error(err: AddressLookupError) -> AddressLoaderError {
     match err {
         AddressLookupError::LookupTableAccountNotFound => {
@@ -0,0 +1,204 @@
+use {
+    crate::{error::LiteSVMError, types::SnapshotFilter},
+    agave_feature_set::FeatureSet,
+    solana_account::{AccountSharedData, WritableAccount},
+    solana_clock::Slot,
+    solana_feature_gate_interface::Feature,
+    solana_pubkey::Pubkey,
+    solana_system_interface::MAX_PERMITTED_DATA_LENGTH,
+    std::{
+        collections::HashMap,
+        fs::File,
+        io::{self, Read},
+        path::{Component, Path},
+    },
+};
+
+/// Each account in an append vec starts with a header made of the stored meta (write version,
+/// data length, pubkey), the account meta (lamports, rent epoch, owner, executable, padded to
+/// 8 bytes) and an obsolete account hash. The data follows, padded to 8 bytes.
+const STORED_META_LEN: usize = 48;
+const ACCOUNT_META_LEN: usize = 56;
+const HEADER_LEN: usize = STORED_META_LEN + ACCOUNT_META_LEN + 32;
+const ALIGNMENT: u64 = 8;
+
+type Versions = HashMap<Pubkey, (Slot, Option<AccountSharedData>)>;
+
+/// The newest version of the selected accounts and of the feature accounts in a set of
+/// snapshot archives, along with the slot each was written in. Closed accounts are `None`.
+#[derive(Default)]
+pub(crate) struct SnapshotAccounts {
+    pub(crate) slot: Slot,
+    accounts: Versions,
+    features: Versions,
+}
+
+impl SnapshotAccounts {
+    /// Reads the archives one after the other, so an incremental snapshot can come
+    /// along with the full snapshot it's based on.
+    pub(crate) fn read<P: AsRef<Path>>(
+        archives: &[P],
+        filter: &SnapshotFilter,
+    ) -> Result<Self, LiteSVMError> {
+        let mut snapshot = Self::default();
+        for path in archives {
+            snapshot.read_archive(path.as_ref(), filter)?;
+        }
+        Ok(snapshot)
+    }
+
+    fn read_archive(&mut self, path: &Path, filter: &SnapshotFilter) -> Result<(), LiteSVMError> {
+        let invalid = |reason: String| LiteSVMError::InvalidSnapshot {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let decoder = zstd::Decoder::new(File::open(path)?).map_err(|e| invalid(e.to_string()))?;
+        let mut archive = tar::Archive::new(decoder);
+        let mut bank_slot = None;
+        for entry in archive.entries().map_err(|e| invalid(e.to_string()))? {
+            let entry = entry.map_err(|e| invalid(e.to_string()))?;
+            let entry_path = entry
+                .path()
+                .map_err(|e| invalid(e.to_string()))?
+                .into_owned();
+            let mut components = entry_path
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => name.to_str(),
+                    _ => None,
+                });
+            match (components.next(), components.next()) {
+                // account storages are named `<slot>.<id>`
+                (Some("accounts"), Some(name)) => {
+                    let slot = name
+                        .split('.')
+                        .next()
+                        .and_then(|slot| slot.parse().ok())
+                        .ok_or_else(|| invalid(format!("unexpected storage {name}")))?;
+                    self.read_storage(entry, slot, filter)
+                        .map_err(|e| invalid(format!("storage {name}: {e}")))?;
+                }
+                // the bank is serialized under `snapshots/<slot>/`, next to the status cache
+                (Some("snapshots"), Some(name)) => {
+                    if let Ok(slot) = name.parse::<Slot>() {
+                        bank_slot = bank_slot.max(Some(slot));
+                    }
+                }
+                _ => {}
+            }
+        }
+        let bank_slot = bank_slot.ok_or_else(|| invalid("no bank snapshot found".to_string()))?;
+        self.slot = self.slot.max(bank_slot);
+        Ok(())
+    }
+
+    /// Reads every account in an append vec, keeping the selected ones and the feature accounts.
+    fn read_storage(
+        &mut self,
+        mut reader: impl Read,
+        slot: Slot,
+        filter: &SnapshotFilter,
+    ) -> io::Result<()> {
+        let mut header = [0; HEADER_LEN];
+        while read_header(&mut reader, &mut header)? {
+            // storages can be larger than the accounts written to them
+            if header[..STORED_META_LEN + ACCOUNT_META_LEN]
+                .iter()
+                .all(|byte| *byte == 0)
+            {
+                break;
+            }
+            let field =
+                |offset: usize| u64::from_le_bytes(header[offset..offset + 8].try_into().unwrap());
+            let key = |offset: usize| Pubkey::try_from(&header[offset..offset + 32]).unwrap();
+            let data_len = field(8);
+            if data_len > MAX_PERMITTED_DATA_LENGTH {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("account data length {data_len} is too large"),
+                ));
+            }
+            let pubkey = key(16);
+            let lamports = field(STORED_META_LEN);
+            let rent_epoch = field(STORED_META_LEN + 8);
+            let owner = key(STORED_META_LEN + 16);
+            let executable = header[STORED_META_LEN + 48] != 0;
+
+            let is_feature = owner == solana_sdk_ids::feature::id();
+            let padding = data_len.next_multiple_of(ALIGNMENT) - data_len;
+            if !is_feature && !filter.matches(&pubkey, &owner) {
+                io::copy(&mut (&mut reader).take(data_len + padding), &mut io::sink())?;
+                continue;
+            }
+            let mut data = vec![0; data_len as usize];
+            reader.read_exact(&mut data)?;
+            // the last account doesn't have to be padded
+            io::copy(&mut (&mut reader).take(padding), &mut io::sink())?;
+
+            let account = (lamports != 0)
+                .then(|| AccountSharedData::create(lamports, data, owner, executable, rent_epoch));
+            if is_feature {
+                insert_version(&mut self.features, pubkey, slot, account.clone());
+            }
+            if filter.matches(&pubkey, &owner) {
+                insert_version(&mut self.accounts, pubkey, slot, account);
+            }
+        }
+        Ok(())
+    }
+
+    /// The features activated in the snapshots. Every other feature is inactive.
+    pub(crate) fn feature_set(&self) -> FeatureSet {
+        let mut feature_set = FeatureSet::default();
+        for (feature_id, (_, account)) in &self.features {
+            if let Some(Feature {
+                activated_at: Some(slot),
+            }) = account
+                .as_ref()
+                .and_then(solana_feature_gate_interface::from_account)
+            {
+                feature_set.activate(feature_id, slot);
+            }
+        }
+        feature_set
+    }
+
+    pub(crate) fn into_writes(self) -> Vec<(Pubkey, Option<AccountSharedData>)> {
+        self.accounts
+            .into_iter()
+            .map(|(pubkey, (_, account))| (pubkey, account))
+            .collect()
+    }
+}
+
+/// Keeps the account unless a newer version was already read.
+///
+/// Within a storage, later entries are newer.
+fn insert_version(
+    versions: &mut Versions,
+    pubkey: Pubkey,
+    slot: Slot,
+    account: Option<AccountSharedData>,
+) {
+    if versions
+        .get(&pubkey)
+        .map_or(true, |(newest, _)| slot >= *newest)
+    {
+        versions.insert(pubkey, (slot, account));
+    }
+}
+
+/// Fills `header`, returning `false` at the end of the storage.
+fn read_header(reader: &mut impl Read, header: &mut [u8]) -> io::Result<bool> {
+    let mut read = 0;
+    while read < header.len() {
+        match reader.read(&mut header[read..]) {
+            Ok(0) if read == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
@@ -0,0 +1,117 @@
+use {solana_hash::Hash, std::collections::VecDeque};
+
+/// How many blocks a blockhash stays valid for by default, like `MAX_PROCESSING_AGE` on a cluster.
+pub(crate) const DEFAULT_MAX_AGE: u64 = 150;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BlockhashEntry {
+    pub(crate) blockhash: Hash,
+    /// The block height at which this was the latest blockhash.
+    pub(crate) block_height: u64,
+    pub(crate) lamports_per_signature: u64,
+}
+
+/// The blockhashes transactions can be signed with, like the blockhash queue of a bank.
+///
+/// Each new blockhash increments the block height, and a blockhash stays valid until the block
+/// height is more than `max_age` past the height it was registered at.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BlockhashQueue {
+    /// Oldest first, so the last entry is the latest blockhash.
+    entries: VecDeque<BlockhashEntry>,
+    max_age: u64,
+}
+
+impl BlockhashQueue {
+    pub(crate) fn new(genesis_blockhash: Hash, lamports_per_signature: u64) -> Self {
+        Self::from_entries(
+            [BlockhashEntry {
+                blockhash: genesis_blockhash,
+                block_height: 0,
+                lamports_per_signature,
+            }],
+            DEFAULT_MAX_AGE,
+        )
+    }
+
+    /// Rebuilds a queue from its entries, oldest first. There must be at least one.
+    pub(crate) fn from_entries(
+        entries: impl IntoIterator<Item = BlockhashEntry>,
+        max_age: u64,
+    ) -> Self {
+        let mut queue = Self {
+            entries: entries.into_iter().collect(),
+            max_age,
+        };
+        assert!(
+            !queue.entries.is_empty(),
+            "a blockhash queue can't be empty"
+        );
+        queue.prune();
+        queue
+    }
+
+    pub(crate) fn latest(&self) -> &BlockhashEntry {
+        self.entries.back().expect("the queue is never empty")
+    }
+
+    pub(crate) fn block_height(&self) -> u64 {
+        self.latest().block_height
+    }
+
+    pub(crate) fn max_age(&self) -> u64 {
+        self.max_age
+    }
+
+    /// The last block height at which the latest blockhash is still valid.
+    pub(crate) fn last_valid_block_height(&self) -> u64 {
+        self.block_height().saturating_add(self.max_age)
+    }
+
+    /// Shrinking the max age drops the blockhashes that are now too old. Growing it doesn't
+    /// bring back the ones already dropped.
+    pub(crate) fn set_max_age(&mut self, max_age: u64) {
+        self.max_age = max_age;
+        self.prune();
+    }
+
+    /// Returns the entry of `blockhash` if it's still valid.
+    pub(crate) fn get(&self, blockhash: &Hash) -> Option<&BlockhashEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| &entry.blockhash == blockhash)
+    }
+
+    /// Registers the blockhash of the next block.
+    pub(crate) fn register(&mut self, blockhash: Hash, lamports_per_signature: u64) {
+        let block_height = self.block_height() + 1;
+        self.entries.push_back(BlockhashEntry {
+            blockhash,
+            block_height,
+            lamports_per_signature,
+        });
+        self.prune();
+    }
+
+    /// Registers the blockhash of the next block, expiring every other one.
+    pub(crate) fn replace(&mut self, blockhash: Hash, lamports_per_signature: u64) {
+        self.register(blockhash, lamports_per_signature);
+        self.entries.drain(..self.entries.len() - 1);
+    }
+
+    /// The valid blockhashes, newest first.
+    pub(crate) fn iter(&self) -> impl DoubleEndedIterator<Item = &BlockhashEntry> {
+        self.entries.iter().rev()
+    }
+
+    fn prune(&mut self) {
+        let block_height = self.block_height();
+        while let Some(oldest) = self.entries.front() {
+            if block_height.saturating_sub(oldest.block_height) <= self.max_age {
+                break;
+            }
+            self.entries.pop_front();
+        }
+    }
+}
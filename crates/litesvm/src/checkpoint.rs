@@ -0,0 +1,25 @@
+use {
+    crate::{
+        blockhash_queue::BlockhashQueue,
+        stakes::{EpochStakes, RewardsDistribution},
+        types::CheckpointId,
+    },
+    solana_program_runtime::{loaded_programs::ProgramCacheForTxBatch, sysvar_cache::SysvarCache},
+    std::sync::Arc,
+};
+
+/// The state that [`LiteSVM::revert`](crate::LiteSVM::revert) restores
+/// on top of the account writes it undoes.
+#[derive(Clone)]
+pub(crate) struct Checkpoint {
+    pub(crate) id: CheckpointId,
+    /// Length of the accounts undo log when the checkpoint was taken.
+    pub(crate) undo_log_position: usize,
+    pub(crate) programs_cache: Arc<ProgramCacheForTxBatch>,
+    pub(crate) sysvar_cache: Arc<SysvarCache>,
+    pub(crate) blockhash_queue: BlockhashQueue,
+    pub(crate) epoch_rewards: Option<RewardsDistribution>,
+    pub(crate) epoch_stakes: EpochStakes,
+    /// Length of the transaction history's undo log when the checkpoint was taken.
+    pub(crate) history_position: usize,
+}
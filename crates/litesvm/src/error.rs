@@ -1,4 +1,8 @@
-use {solana_instruction::error::InstructionError, thiserror::Error};
+use {
+    crate::{storage::StoreError, types::CheckpointId},
+    solana_instruction::error::InstructionError,
+    thiserror::Error,
+};
 
 #[derive(Error, Debug)]
 pub enum InvalidSysvarDataError {
@@ -28,4 +32,46 @@ pub enum LiteSVMError {
     InvalidSysvarData(#[from] InvalidSysvarDataError),
     #[error("{0}")]
     Instruction(#[from] InstructionError),
+    #[error("{0}")]
+    Store(#[from] StoreError),
+    #[error("Checkpoint {0} does not exist or was discarded.")]
+    CheckpointNotFound(CheckpointId),
+    #[error("The burn percentage {0} exceeds 100.")]
+    InvalidBurnPercent(u8),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[cfg(feature = "serde")]
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[cfg(feature = "serde")]
+    #[error("Invalid account file {}: {source}", path.display())]
+    InvalidAccountFile {
+        path: std::path::PathBuf,
+        source: serde_json::Error,
+    },
+    #[cfg(feature = "serde")]
+    #[error("Invalid replay file {}: {reason}", path.display())]
+    InvalidReplayFile {
+        path: std::path::PathBuf,
+        reason: String,
+    },
+    #[cfg(feature = "serde")]
+    #[error("Invalid session recording {} at line {line}: {reason}", path.display())]
+    InvalidSession {
+        path: std::path::PathBuf,
+        line: usize,
+        reason: String,
+    },
+    #[cfg(feature = "serde")]
+    #[error("Replaying {} diverged at line {line}: the transaction gave a different result than recorded.", path.display())]
+    SessionDiverged {
+        path: std::path::PathBuf,
+        line: usize,
+    },
+    #[cfg(feature = "agave-snapshot")]
+    #[error("Invalid snapshot archive {}: {reason}", path.display())]
+    InvalidSnapshot {
+        path: std::path::PathBuf,
+        reason: String,
+    },
 }
@@ -1,37 +1,156 @@
 use {crate::types::TransactionResult, indexmap::IndexMap, solana_signature::Signature};
 
+pub struct TransactionHistory {
+    transactions: IndexMap<Signature, TransactionResult>,
+    /// The changes made since the oldest live checkpoint, or `None` when there are
+    /// no checkpoints to revert to.
+    undo_log: Option<Vec<HistoryChange>>,
+}
+
+/// What it takes to undo a single change to the history.
 #[derive(Clone)]
-pub struct TransactionHistory(IndexMap<Signature, TransactionResult>);
+enum HistoryChange {
+    Added {
+        signature: Signature,
+        /// The result stored under the same signature before, which keeps its position.
+        replaced: Option<Box<TransactionResult>>,
+        /// The oldest transaction, pushed out to make room.
+        evicted: Option<Box<(Signature, TransactionResult)>>,
+    },
+    Resized {
+        capacity: usize,
+        /// The newest transactions, cut off by a smaller capacity.
+        truncated: Vec<(Signature, TransactionResult)>,
+    },
+}
+
+impl Clone for TransactionHistory {
+    /// The capacity doubles as the history limit, so it has to survive the clone.
+    fn clone(&self) -> Self {
+        let mut transactions = IndexMap::with_capacity(self.transactions.capacity());
+        transactions.extend(self.transactions.iter().map(|(k, v)| (*k, v.clone())));
+        TransactionHistory {
+            transactions,
+            undo_log: self.undo_log.clone(),
+        }
+    }
+}
 
 impl TransactionHistory {
     pub fn new() -> Self {
-        TransactionHistory(IndexMap::with_capacity(500))
+        TransactionHistory {
+            transactions: IndexMap::with_capacity(500),
+            undo_log: None,
+        }
     }
 
     pub fn set_capacity(&mut self, new_cap: usize) {
-        if new_cap <= self.0.capacity() {
-            self.0.truncate(new_cap);
-            self.0.shrink_to_fit();
+        let capacity = self.transactions.capacity();
+        let truncated = if new_cap <= capacity {
+            let len = self.transactions.len();
+            let truncated = self.transactions.drain(new_cap.min(len)..).collect();
+            self.transactions.shrink_to_fit();
+            truncated
         } else {
-            self.0.reserve(new_cap - self.0.capacity())
+            self.transactions.reserve(new_cap - capacity);
+            Vec::new()
+        };
+        if let Some(undo_log) = self.undo_log.as_mut() {
+            undo_log.push(HistoryChange::Resized {
+                capacity,
+                truncated,
+            });
         }
     }
 
     pub fn get_transaction(&self, signature: &Signature) -> Option<&TransactionResult> {
-        self.0.get(signature)
+        self.transactions.get(signature)
     }
 
     pub fn add_new_transaction(&mut self, signature: Signature, result: TransactionResult) {
-        let capacity = self.0.capacity();
+        let capacity = self.transactions.capacity();
         if capacity != 0 {
-            if self.0.len() == capacity {
-                self.0.shift_remove_index(0);
+            let evicted = if self.transactions.len() == capacity {
+                self.transactions.shift_remove_index(0).map(Box::new)
+            } else {
+                None
+            };
+            let replaced = self.transactions.insert(signature, result);
+            if let Some(undo_log) = self.undo_log.as_mut() {
+                undo_log.push(HistoryChange::Added {
+                    signature,
+                    replaced: replaced.map(Box::new),
+                    evicted,
+                });
             }
-            self.0.insert(signature, result);
         }
     }
 
     pub fn check_transaction(&self, signature: &Signature) -> bool {
-        self.0.contains_key(signature)
+        self.transactions.contains_key(signature)
+    }
+
+    /// Starts recording changes if it isn't already,
+    /// and returns the position a later [`rollback`](Self::rollback) can return to.
+    pub fn undo_log_position(&mut self) -> usize {
+        self.undo_log.get_or_insert_with(Vec::new).len()
+    }
+
+    /// Stops recording changes and drops the recorded ones.
+    pub fn clear_undo_log(&mut self) {
+        self.undo_log = None;
+    }
+
+    /// Undoes every change recorded after `position`, newest first.
+    pub fn rollback(&mut self, position: usize) {
+        let Some(undo_log) = self.undo_log.as_mut() else {
+            return;
+        };
+        let changes = undo_log.split_off(position.min(undo_log.len()));
+        for change in changes.into_iter().rev() {
+            match change {
+                HistoryChange::Added {
+                    signature,
+                    replaced,
+                    evicted,
+                } => {
+                    match replaced {
+                        Some(result) => {
+                            self.transactions.insert(signature, *result);
+                        }
+                        None => {
+                            self.transactions.shift_remove(&signature);
+                        }
+                    }
+                    if let Some(evicted) = evicted {
+                        let (signature, result) = *evicted;
+                        self.transactions.shift_insert(0, signature, result);
+                    }
+                }
+                HistoryChange::Resized {
+                    capacity,
+                    truncated,
+                } => {
+                    let current = self.transactions.capacity();
+                    if capacity > current {
+                        self.transactions.reserve(capacity - current);
+                    } else {
+                        self.transactions.shrink_to(capacity);
+                    }
+                    self.transactions.extend(truncated);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "rocksdb")]
+    pub fn capacity(&self) -> usize {
+        self.transactions.capacity()
+    }
+
+    /// Iterates over the stored transactions, oldest first.
+    #[cfg(feature = "rocksdb")]
+    pub fn iter(&self) -> impl Iterator<Item = (&Signature, &TransactionResult)> {
+        self.transactions.iter()
     }
 }
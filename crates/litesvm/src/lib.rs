@@ -165,7 +165,9 @@ fn test_set_clock() {
 
 ```
 
-See also: [`warp_to_slot`](LiteSVM::warp_to_slot), which lets you jump to a future slot.
+See also: [`warp_to_slot`](LiteSVM::warp_to_slot), which lets you jump to a future slot,
+and [`produce_block`](LiteSVM::produce_block), which advances the slot, blockhash, slot hashes
+and clock together like a validator would.
 
 ## Writing arbitrary accounts
 
@@ -262,12 +264,17 @@ use solana_sysvar::{fees::Fees, recent_blockhashes::RecentBlockhashes};
 use {
     crate::{
         accounts_db::AccountsDb,
+        blockhash_queue::BlockhashQueue,
+        checkpoint::Checkpoint,
         error::LiteSVMError,
         history::TransactionHistory,
         message_processor::process_message,
         spl::load_spl_programs,
+        stakes::{EpochRewardsCalculation, EpochStakes, RewardsDistribution},
         types::{
-            ExecutionResult, FailedTransactionMetadata, TransactionMetadata, TransactionResult,
+            AccountCacheStats, AccountFilter, AccountVersion, CheckpointId, DataSlice,
+            ExecutionResult, FailedTransactionMetadata, FeeBreakdown, TransactionMetadata,
+            TransactionResult,
         },
         utils::{create_blockhash, rent::RentState},
     },
@@ -276,22 +283,27 @@ use {
     itertools::Itertools,
     log::error,
     precompiles::load_precompiles,
-    solana_account::{Account, AccountSharedData, ReadableAccount, WritableAccount},
+    solana_account::{
+        state_traits::StateMut, Account, AccountSharedData, ReadableAccount, WritableAccount,
+    },
     solana_bpf_loader_program::syscalls::{
         create_program_runtime_environment_v1, create_program_runtime_environment_v2,
     },
     solana_builtins::BUILTINS,
-    solana_clock::Clock,
+    solana_clock::{Clock, Epoch, DEFAULT_MS_PER_SLOT},
     solana_compute_budget::{
         compute_budget::ComputeBudget, compute_budget_limits::ComputeBudgetLimits,
     },
     solana_compute_budget_instruction::instructions_processor::process_compute_budget_instructions,
     solana_epoch_rewards::EpochRewards,
     solana_epoch_schedule::EpochSchedule,
-    solana_fee_structure::FeeStructure,
+    solana_fee_calculator::DEFAULT_BURN_PERCENT,
+    solana_fee_structure::{FeeDetails, FeeStructure},
     solana_hash::Hash,
+    solana_inflation::Inflation,
     solana_keypair::Keypair,
     solana_last_restart_slot::LastRestartSlot,
+    solana_loader_v3_interface::{get_program_data_address, state::UpgradeableLoaderState},
     solana_log_collector::LogCollector,
     solana_message::{inner_instruction::InnerInstructionsList, SanitizedMessage},
     solana_native_token::LAMPORTS_PER_SOL,
@@ -299,11 +311,11 @@ use {
     solana_program_runtime::{
         execution_budget::{SVMTransactionExecutionBudget, SVMTransactionExecutionCost},
         invoke_context::{BuiltinFunctionWithContext, EnvironmentConfig, InvokeContext},
-        loaded_programs::{LoadProgramMetrics, ProgramCacheEntry},
+        loaded_programs::ProgramCacheEntry,
     },
     solana_pubkey::Pubkey,
     solana_rent::Rent,
-    solana_sdk_ids::{bpf_loader, native_loader, system_program},
+    solana_sdk_ids::{bpf_loader, bpf_loader_upgradeable, native_loader, system_program},
     solana_signature::Signature,
     solana_signer::Signer,
     solana_slot_hashes::SlotHashes,
@@ -322,12 +334,8 @@ use {
     },
     solana_transaction_context::{ExecutionRecord, IndexOfAccount, TransactionContext},
     solana_transaction_error::TransactionError,
-    std::{
-        cell::RefCell,
-        path::{Path, PathBuf},
-        rc::Rc,
-        sync::Arc,
-    },
+    solana_vote_program::vote_state::VoteState,
+    std::{cell::RefCell, collections::HashMap, path::Path, rc::Rc, sync::Arc, time::Duration},
     types::SimulatedTransactionInfo,
     utils::{
         construct_instructions_account,
@@ -338,80 +346,463 @@ use {
 pub mod error;
 pub mod types;
 
+mod account_cache;
+#[cfg(feature = "serde")]
+mod account_json;
 mod accounts_db;
+#[cfg(feature = "agave-snapshot")]
+mod agave_snapshot;
+mod blockhash_queue;
+mod checkpoint;
 mod format_logs;
 mod history;
 mod message_processor;
 mod precompiles;
+#[cfg(feature = "serde")]
+mod replay;
+#[cfg(feature = "serde")]
+mod session;
 mod spl;
+mod stakes;
 mod utils;
 
+pub mod account_source;
 pub mod storage;
 
-use crate::storage::RocksDBStore;
+use crate::account_source::AccountSource;
+use crate::storage::{AccountStore, SvmMetadata};
+#[cfg(feature = "rocksdb")]
+use crate::storage::{OverlayStore, RocksDBStore, SnapshotManifest, StoreError, ACCOUNTS_DIR};
 
 #[derive(Clone)]
 pub struct LiteSVM {
     accounts: AccountsDb,
     airdrop_kp: [u8; 64],
     pub feature_set: FeatureSet,
-    latest_blockhash: Hash,
+    blockhash_queue: BlockhashQueue,
     history: TransactionHistory,
     compute_budget: Option<ComputeBudget>,
     sigverify: bool,
     blockhash_check: bool,
     fee_structure: FeeStructure,
+    /// The account credited with the part of the fees that isn't burned, like a leader.
+    fee_collector: Option<Pubkey>,
+    fee_burn_percent: u8,
     log_bytes_limit: Option<usize>,
+    checkpoints: Vec<Checkpoint>,
+    next_checkpoint_id: u64,
+    slot_duration: Duration,
+    auto_produce_blocks: bool,
+    /// The time since the last whole second of the clock, carried over by `produce_block`.
+    unix_timestamp_remainder: Duration,
+    inflation: Option<Inflation>,
+    /// The stake rewards still to be paid, while the `EpochRewards` sysvar is active.
+    epoch_rewards: Option<RewardsDistribution>,
+    epoch_stakes: EpochStakes,
+    #[cfg(feature = "serde")]
+    recorder: session::SessionRecorder,
 }
 
 impl Default for LiteSVM {
     fn default() -> Self {
-        Self {
-            accounts: Default::default(),
-            airdrop_kp: Keypair::new().to_bytes(),
-            feature_set: Default::default(),
-            latest_blockhash: create_blockhash(b"genesis"),
-            history: TransactionHistory::new(),
-            compute_budget: None,
-            sigverify: false,
-            blockhash_check: false,
-            fee_structure: FeeStructure::default(),
-            log_bytes_limit: Some(10_000),
-        }
+        Self::from_accounts_db(AccountsDb::default())
     }
 }
 
 impl LiteSVM {
     /// Creates the basic test environment.
-    pub fn new_with_db_path<P: Into<PathBuf>>(path: P) -> Self {
+    ///
+    /// # Panics
+    ///
+    /// Panics if the RocksDB directory can't be opened or written to.
+    /// Use [`try_new_with_db_path`](LiteSVM::try_new_with_db_path) to handle the error instead.
+    #[cfg(feature = "rocksdb")]
+    pub fn new_with_db_path<P: Into<std::path::PathBuf>>(path: P) -> Self {
+        Self::try_new_with_db_path(path).expect("Failed to set up LiteSVM in RocksDB")
+    }
+
+    /// Like [`new_with_db_path`](LiteSVM::new_with_db_path), but returns the store error
+    /// instead of panicking.
+    #[cfg(feature = "rocksdb")]
+    pub fn try_new_with_db_path<P: Into<std::path::PathBuf>>(
+        path: P,
+    ) -> Result<Self, LiteSVMError> {
         let path = path.into();
-        let store = Arc::new(RocksDBStore::open(&path).expect("Failed to open RocksDB"));
-        let accounts_db = AccountsDb::new(store.clone());
+        let svm = Self::from_store(Arc::new(RocksDBStore::open(&path)?));
+        svm.save_metadata()?;
+        Ok(svm)
+    }
+
+    /// Reopens a LiteSVM instance persisted in the RocksDB directory at `path`.
+    ///
+    /// See [`open_store`](LiteSVM::open_store) for what is restored.
+    #[cfg(feature = "rocksdb")]
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, LiteSVMError> {
+        Self::open_store(RocksDBStore::open(path.as_ref())?)
+    }
+
+    /// Opens a LiteSVM instance persisted in the RocksDB directory at `path` without modifying it.
+    ///
+    /// The database is opened with [`RocksDBStore::open_read_only`], so any number of processes
+    /// can share one prepared state at once. Each instance writes to its own in-memory
+    /// [`OverlayStore`] on top of it, which is discarded when the instance is dropped.
+    #[cfg(feature = "rocksdb")]
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self, LiteSVMError> {
+        let store = RocksDBStore::open_read_only(path.as_ref())?;
+        Self::open_store(OverlayStore::new(Arc::new(store)))
+    }
+
+    /// Like [`open_read_only`](LiteSVM::open_read_only), but opens the database as a RocksDB
+    /// secondary instance with [`RocksDBStore::open_as_secondary`], so it can be opened while
+    /// another process is writing to it.
+    #[cfg(feature = "rocksdb")]
+    pub fn open_secondary<P: AsRef<Path>, S: AsRef<Path>>(
+        primary_path: P,
+        secondary_path: S,
+    ) -> Result<Self, LiteSVMError> {
+        let store =
+            RocksDBStore::open_as_secondary(primary_path.as_ref(), secondary_path.as_ref())?;
+        Self::open_store(OverlayStore::new(Arc::new(store)))
+    }
+
+    /// Restores a LiteSVM instance from a store that a previous instance wrote to.
+    ///
+    /// The program and sysvar caches are rebuilt from the stored accounts, and the latest blockhash,
+    /// feature set, fee structure, config, inflation and the stake rewards still to be paid are
+    /// restored from the metadata last written to the store, see
+    /// [`save_metadata`](LiteSVM::save_metadata) for when that happens.
+    /// If the store holds no metadata yet, a fresh instance is set up on top of it as in
+    /// [`LiteSVM::new`], and its metadata is written right away.
+    ///
+    /// Transaction history, the compute budget override and builtins added with
+    /// [`add_builtin`](LiteSVM::add_builtin) are not persisted.
+    pub fn open_store(store: impl AccountStore + 'static) -> Result<Self, LiteSVMError> {
+        let store: Arc<dyn AccountStore> = Arc::new(store);
+        let Some(metadata) = store.get_metadata()? else {
+            let svm = Self::from_store(store).try_with_defaults()?;
+            svm.save_metadata()?;
+            return Ok(svm);
+        };
+        let metadata = SvmMetadata::from_bytes(&metadata)?;
+        let mut svm = Self::from_store(store);
+        svm.apply_metadata(metadata)?;
+        svm.accounts.load_caches_from_store()?;
+        Ok(svm)
+    }
+
+    /// Writes a snapshot of this instance to the directory at `dir`, to be loaded again with
+    /// [`load_snapshot`](LiteSVM::load_snapshot), for example on another machine.
+    ///
+    /// The snapshot holds the accounts as a RocksDB directory, plus a manifest with the
+    /// latest blockhash, feature set, fee structure, config and transaction history.
+    /// If the VM writes to a [`RocksDBStore`], the accounts are saved with a RocksDB checkpoint.
+    /// Otherwise they are copied into a new RocksDB directory.
+    ///
+    /// `dir` is created if needed, but must not already hold a snapshot.
+    #[cfg(feature = "rocksdb")]
+    pub fn save_snapshot<P: AsRef<Path>>(&self, dir: P) -> Result<(), LiteSVMError> {
+        let dir = dir.as_ref();
+        let accounts_dir = dir.join(ACCOUNTS_DIR);
+        if accounts_dir.exists() {
+            return Err(StoreError::from(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} already holds a snapshot", dir.display()),
+            ))
+            .into());
+        }
+        std::fs::create_dir_all(dir).map_err(StoreError::from)?;
+        if !self.accounts.store.save_checkpoint(&accounts_dir)? {
+            self.accounts
+                .copy_accounts_to(&RocksDBStore::open(&accounts_dir)?)?;
+        }
+        SnapshotManifest {
+            metadata: self.metadata().to_bytes()?,
+            history_capacity: self.history.capacity(),
+            history: self
+                .history
+                .iter()
+                .map(|(signature, result)| (*signature, result.clone()))
+                .collect(),
+        }
+        .write(dir)?;
+        Ok(())
+    }
+
+    /// Loads a snapshot written by [`save_snapshot`](LiteSVM::save_snapshot).
+    ///
+    /// The loaded instance writes to an [`OverlayStore`] on top of the snapshot's accounts,
+    /// so the snapshot itself is never modified and can be loaded again.
+    /// As with [`open_store`](LiteSVM::open_store), the compute budget override and builtins
+    /// added with [`add_builtin`](LiteSVM::add_builtin) are not restored.
+    #[cfg(feature = "rocksdb")]
+    pub fn load_snapshot<P: AsRef<Path>>(dir: P) -> Result<Self, LiteSVMError> {
+        let dir = dir.as_ref();
+        let manifest = SnapshotManifest::read(dir)?;
+        let store = OverlayStore::new(Arc::new(RocksDBStore::open(&dir.join(ACCOUNTS_DIR))?));
+        store.put_metadata(&manifest.metadata)?;
+        let mut svm = Self::open_store(store)?;
+        svm.history.set_capacity(manifest.history_capacity);
+        for (signature, result) in manifest.history {
+            svm.history.add_new_transaction(signature, result);
+        }
+        Ok(svm)
+    }
+
+    fn from_store(store: Arc<dyn AccountStore>) -> Self {
+        Self::from_accounts_db(AccountsDb::new(store))
+    }
 
+    fn from_accounts_db(accounts: AccountsDb) -> Self {
         Self {
-            accounts: accounts_db,
+            accounts,
             airdrop_kp: Keypair::new().to_bytes(),
             feature_set: FeatureSet::default(),
-            latest_blockhash: create_blockhash(b"genesis"),
+            blockhash_queue: BlockhashQueue::new(
+                create_blockhash(b"genesis"),
+                FeeStructure::default().lamports_per_signature,
+            ),
             history: TransactionHistory::new(),
             compute_budget: None,
             sigverify: false,
             blockhash_check: false,
             fee_structure: FeeStructure::default(),
+            fee_collector: None,
+            fee_burn_percent: DEFAULT_BURN_PERCENT,
             log_bytes_limit: Some(10_000),
+            checkpoints: Vec::new(),
+            next_checkpoint_id: 0,
+            slot_duration: Duration::from_millis(DEFAULT_MS_PER_SLOT),
+            auto_produce_blocks: false,
+            unix_timestamp_remainder: Duration::ZERO,
+            inflation: None,
+            epoch_rewards: None,
+            epoch_stakes: EpochStakes::default(),
+            #[cfg(feature = "serde")]
+            recorder: Default::default(),
+        }
+    }
+
+    fn metadata(&self) -> SvmMetadata {
+        SvmMetadata {
+            blockhash_queue: self.blockhash_queue.clone(),
+            feature_set: self.feature_set.clone(),
+            fee_structure: self.fee_structure.clone(),
+            fee_collector: self.fee_collector,
+            fee_burn_percent: self.fee_burn_percent,
+            inflation: self.inflation,
+            epoch_rewards: self.epoch_rewards.clone(),
+            airdrop_kp: self.airdrop_kp,
+            sigverify: self.sigverify,
+            blockhash_check: self.blockhash_check,
+            log_bytes_limit: self.log_bytes_limit,
+        }
+    }
+
+    /// Restores the state saved by [`metadata`](LiteSVM::metadata), setting up the builtins
+    /// again for the restored feature set.
+    fn apply_metadata(&mut self, metadata: SvmMetadata) -> Result<(), LiteSVMError> {
+        self.blockhash_queue = metadata.blockhash_queue;
+        self.feature_set = metadata.feature_set;
+        self.fee_structure = metadata.fee_structure;
+        self.fee_collector = metadata.fee_collector;
+        self.fee_burn_percent = metadata.fee_burn_percent;
+        self.inflation = metadata.inflation;
+        self.epoch_rewards = metadata.epoch_rewards;
+        self.airdrop_kp = metadata.airdrop_kp;
+        self.sigverify = metadata.sigverify;
+        self.blockhash_check = metadata.blockhash_check;
+        self.log_bytes_limit = metadata.log_bytes_limit;
+        self.set_builtins()
+    }
+
+    /// Writes the state that doesn't live in accounts to the store, for
+    /// [`open_store`](LiteSVM::open_store) to restore: the blockhash queue, feature set, fee
+    /// structure, config, inflation and the stake rewards still to be paid.
+    ///
+    /// Accounts are written through to the store as they change. The metadata is written along
+    /// with the sysvars in the same store write by [`produce_block`](LiteSVM::produce_block),
+    /// [`warp_to_epoch`](LiteSVM::warp_to_epoch), [`warp_to_slot`](LiteSVM::warp_to_slot),
+    /// [`expire_blockhash`](LiteSVM::expire_blockhash) and [`set_sysvar`](LiteSVM::set_sysvar),
+    /// so the blockhash queue and the stake rewards never lag behind the accounts. Changes to the
+    /// feature set, fee structure, config and inflation are only written when the store is set up
+    /// and when this is called, so call it after changing them on an instance that will be
+    /// reopened.
+    pub fn save_metadata(&self) -> Result<(), LiteSVMError> {
+        let metadata = self.metadata().to_bytes()?;
+        self.accounts.store.put_metadata(&metadata)?;
+        Ok(())
+    }
+
+    /// Runs `f` as a single batch of account writes, which reaches the store in one
+    /// [`write_accounts_with_metadata`](AccountStore::write_accounts_with_metadata) call along
+    /// with the metadata, so a reopened instance never sees one without the other.
+    ///
+    /// If `f` or the store fails, the accounts, caches, blockhash queue, epoch stakes and stake
+    /// rewards are left as they were.
+    fn write_with_metadata(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<(), LiteSVMError>,
+    ) -> Result<(), LiteSVMError> {
+        let blockhash_queue = self.blockhash_queue.clone();
+        let epoch_rewards = self.epoch_rewards.clone();
+        let epoch_stakes = self.epoch_stakes.clone();
+        let unix_timestamp_remainder = self.unix_timestamp_remainder;
+        self.accounts.start_batch();
+        let result = f(self).and_then(|()| {
+            let metadata = self.metadata().to_bytes()?;
+            self.accounts.commit_batch(&metadata)
+        });
+        if result.is_err() {
+            self.accounts.abort_batch();
+            self.blockhash_queue = blockhash_queue;
+            self.epoch_rewards = epoch_rewards;
+            self.epoch_stakes = epoch_stakes;
+            self.unix_timestamp_remainder = unix_timestamp_remainder;
         }
+        result
+    }
+
+    #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
+    fn set_store(&mut self, store: impl AccountStore + 'static) -> Result<(), LiteSVMError> {
+        self.accounts.set_store(Arc::new(store))?;
+        self.save_metadata()
+    }
+
+    /// Sets the [`AccountStore`] that accounts are written through to.
+    ///
+    /// Every account the VM already holds is copied into the new store.
+    /// The default is a [`NullStore`](storage::NullStore), which keeps nothing besides the
+    /// accounts held in memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the accounts or the metadata can't be written to the new store.
+    /// Use [`try_with_store`](LiteSVM::try_with_store) to handle the error instead.
+    pub fn with_store(self, store: impl AccountStore + 'static) -> Self {
+        self.try_with_store(store)
+            .expect("Failed to copy accounts into the new store")
+    }
+
+    /// Like [`with_store`](LiteSVM::with_store), but returns the store error instead of panicking.
+    pub fn try_with_store(
+        mut self,
+        store: impl AccountStore + 'static,
+    ) -> Result<Self, LiteSVMError> {
+        self.set_store(store)?;
+        Ok(self)
+    }
+
+    /// Creates an independent copy of this instance. This is what [`Clone`] does as well.
+    ///
+    /// The fork starts with a copy of the accounts held in memory, the program and sysvar caches,
+    /// the latest blockhash, the transaction history, the feature set and the config.
+    /// Changing any of these on one side never affects the other.
+    ///
+    /// The accounts the original only holds in its [`AccountStore`], for example after
+    /// [`open_store`](LiteSVM::open_store) or once they're evicted from a bounded account cache,
+    /// are read from that same store rather than copied. The fork writes to a copy-on-write
+    /// [`OverlayStore`](storage::OverlayStore) on top of it, and the original keeps the previous
+    /// state of what it overwrites in that overlay, so neither instance sees the accounts the
+    /// other writes afterwards.
+    pub fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    /// Records the current state so it can be restored later with [`revert`](LiteSVM::revert).
+    ///
+    /// Rather than copying the accounts and the transaction history, LiteSVM keeps an undo log
+    /// of every change made to them while a checkpoint exists, and the checkpoint shares the
+    /// program and sysvar caches until they're next written. This makes checkpoints much
+    /// cheaper than [`fork`](LiteSVM::fork) when you want to try several scenarios from the
+    /// same starting point.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use litesvm::LiteSVM;
+    /// use solana_pubkey::Pubkey;
+    ///
+    /// let mut svm = LiteSVM::new();
+    /// let user = Pubkey::new_unique();
+    /// svm.airdrop(&user, 1_000).unwrap();
+    ///
+    /// let checkpoint = svm.checkpoint();
+    /// svm.airdrop(&user, 2_000).unwrap();
+    /// assert_eq!(svm.get_balance(&user), Some(2_000));
+    ///
+    /// svm.revert(checkpoint).unwrap();
+    /// assert_eq!(svm.get_balance(&user), Some(1_000));
+    /// ```
+    pub fn checkpoint(&mut self) -> CheckpointId {
+        let id = CheckpointId(self.next_checkpoint_id);
+        self.next_checkpoint_id += 1;
+        self.checkpoints.push(Checkpoint {
+            id,
+            undo_log_position: self.accounts.undo_log_position(),
+            programs_cache: self.accounts.programs_cache.clone(),
+            sysvar_cache: self.accounts.sysvar_cache.clone(),
+            blockhash_queue: self.blockhash_queue.clone(),
+            epoch_rewards: self.epoch_rewards.clone(),
+            epoch_stakes: self.epoch_stakes.clone(),
+            history_position: self.history.undo_log_position(),
+        });
+        id
+    }
+
+    /// Restores the accounts, the program and sysvar caches, the recent blockhashes, the epoch
+    /// stakes, the epoch rewards still to be paid and the transaction history to what they were
+    /// when the checkpoint was taken.
+    ///
+    /// The checkpoint stays valid, so you can revert to it again. Checkpoints taken after it
+    /// are discarded. The feature set and the config are not affected.
+    ///
+    /// The restored accounts are written to the store along with the metadata in a single
+    /// write. If that fails, nothing changes and the store error is returned.
+    pub fn revert(&mut self, id: CheckpointId) -> Result<(), LiteSVMError> {
+        let index = self
+            .checkpoints
+            .iter()
+            .position(|checkpoint| checkpoint.id == id)
+            .ok_or(LiteSVMError::CheckpointNotFound(id))?;
+        let checkpoint = self.checkpoints[index].clone();
+        self.write_with_metadata(|svm| {
+            svm.accounts.rollback(checkpoint.undo_log_position)?;
+            svm.accounts.programs_cache = checkpoint.programs_cache;
+            svm.accounts.sysvar_cache = checkpoint.sysvar_cache;
+            svm.blockhash_queue = checkpoint.blockhash_queue;
+            svm.epoch_rewards = checkpoint.epoch_rewards;
+            svm.epoch_stakes = checkpoint.epoch_stakes;
+            Ok(())
+        })?;
+        self.checkpoints.truncate(index + 1);
+        self.history.rollback(checkpoint.history_position);
+        Ok(())
+    }
+
+    /// Discards every checkpoint and stops recording account writes for them.
+    pub fn clear_checkpoints(&mut self) {
+        self.checkpoints.clear();
+        self.accounts.clear_undo_log();
+        self.history.clear_undo_log();
     }
 
     pub fn new() -> Self {
-        LiteSVM::default()
-            .with_feature_set(FeatureSet::all_enabled())
-            .with_builtins()
-            .with_lamports(1_000_000u64.wrapping_mul(LAMPORTS_PER_SOL))
-            .with_sysvars()
-            .with_precompiles()
-            .with_spl_programs()
-            .with_sigverify(true)
-            .with_blockhash_check(true)
+        LiteSVM::default().with_defaults()
+    }
+
+    fn with_defaults(self) -> Self {
+        self.try_with_defaults()
+            .expect("Failed to write the default accounts to the store")
+    }
+
+    fn try_with_defaults(mut self) -> Result<Self, LiteSVMError> {
+        self.set_feature_set(FeatureSet::all_enabled());
+        self.set_builtins()?;
+        self.set_lamports(1_000_000u64.wrapping_mul(LAMPORTS_PER_SOL))?;
+        self.set_sysvars()?;
+        self.set_precompiles()?;
+        self.set_spl_programs()?;
+        Ok(self.with_sigverify(true).with_blockhash_check(true))
     }
 
     #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
@@ -448,33 +839,121 @@ impl LiteSVM {
     }
 
     #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
-    fn set_sysvars(&mut self) {
-        self.set_sysvar(&Clock::default());
-        self.set_sysvar(&EpochRewards::default());
-        self.set_sysvar(&EpochSchedule::default());
+    fn set_fee_structure(&mut self, fee_structure: FeeStructure) {
+        self.fee_structure = fee_structure;
+    }
+
+    /// Sets the fee structure. Its `lamports_per_signature` is what each signature costs,
+    /// and is reported with the blockhashes from the next one on.
+    pub fn with_fee_structure(mut self, fee_structure: FeeStructure) -> Self {
+        self.set_fee_structure(fee_structure);
+        self
+    }
+
+    #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
+    fn set_fee_collector(&mut self, fee_collector: Pubkey) {
+        self.fee_collector = Some(fee_collector);
+    }
+
+    /// Credits `fee_collector` with the fees of each transaction that aren't burned,
+    /// like the leader of a block on a cluster: the whole prioritization fee and what the
+    /// [burn percentage](LiteSVM::with_fee_burn_percent) leaves of the transaction fee.
+    ///
+    /// As on a cluster, the collector only gets paid if it's a system account that stays rent
+    /// exempt, so fund it first. Otherwise, and if no collector is set, the whole fee is burned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use litesvm::LiteSVM;
+    /// use solana_keypair::Keypair;
+    /// use solana_message::Message;
+    /// use solana_pubkey::Pubkey;
+    /// use solana_signer::Signer;
+    /// use solana_system_interface::instruction::transfer;
+    /// use solana_transaction::Transaction;
+    ///
+    /// let leader = Pubkey::new_unique();
+    /// let mut svm = LiteSVM::new().with_fee_collector(leader);
+    /// svm.airdrop(&leader, 1_000_000_000).unwrap();
+    ///
+    /// let payer = Keypair::new();
+    /// svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    /// let tx = Transaction::new(
+    ///     &[&payer],
+    ///     Message::new(
+    ///         &[transfer(&payer.pubkey(), &Pubkey::new_unique(), 1_000_000)],
+    ///         Some(&payer.pubkey()),
+    ///     ),
+    ///     svm.latest_blockhash(),
+    /// );
+    /// let fees = svm.send_transaction(tx).unwrap().fees;
+    /// assert_eq!((fees.burned, fees.collected), (2_500, 2_500));
+    /// assert_eq!(svm.get_balance(&leader), Some(1_000_002_500));
+    /// ```
+    pub fn with_fee_collector(mut self, fee_collector: Pubkey) -> Self {
+        self.set_fee_collector(fee_collector);
+        self
+    }
+
+    #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
+    fn set_fee_burn_percent(&mut self, burn_percent: u8) -> Result<(), LiteSVMError> {
+        if burn_percent > 100 {
+            return Err(LiteSVMError::InvalidBurnPercent(burn_percent));
+        }
+        self.fee_burn_percent = burn_percent;
+        Ok(())
+    }
+
+    /// Sets the percentage of each transaction fee that is burned rather than paid to the
+    /// [fee collector](LiteSVM::with_fee_collector). Prioritization fees are never burned.
+    ///
+    /// The default is 50, as on mainnet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `burn_percent` is more than 100.
+    /// Use [`try_with_fee_burn_percent`](LiteSVM::try_with_fee_burn_percent) to handle the
+    /// error instead.
+    pub fn with_fee_burn_percent(self, burn_percent: u8) -> Self {
+        self.try_with_fee_burn_percent(burn_percent)
+            .expect("Invalid burn percentage")
+    }
+
+    /// Like [`with_fee_burn_percent`](LiteSVM::with_fee_burn_percent), but returns
+    /// [`LiteSVMError::InvalidBurnPercent`] instead of panicking.
+    pub fn try_with_fee_burn_percent(mut self, burn_percent: u8) -> Result<Self, LiteSVMError> {
+        self.set_fee_burn_percent(burn_percent)?;
+        Ok(self)
+    }
+
+    #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
+    fn set_sysvars(&mut self) -> Result<(), LiteSVMError> {
+        self.try_set_sysvar(&Clock::default())?;
+        self.try_set_sysvar(&EpochRewards::default())?;
+        self.try_set_sysvar(&EpochSchedule::default())?;
         #[allow(deprecated)]
         let fees = Fees::default();
-        self.set_sysvar(&fees);
-        self.set_sysvar(&LastRestartSlot::default());
-        let latest_blockhash = self.latest_blockhash;
-        #[allow(deprecated)]
-        self.set_sysvar(&RecentBlockhashes::from_iter([IterItem(
-            0,
-            &latest_blockhash,
-            fees.fee_calculator.lamports_per_signature,
-        )]));
-        self.set_sysvar(&Rent::default());
-        self.set_sysvar(&SlotHashes::new(&[(
+        self.try_set_sysvar(&fees)?;
+        self.try_set_sysvar(&LastRestartSlot::default())?;
+        self.try_set_sysvar(&self.recent_blockhashes())?;
+        self.try_set_sysvar(&Rent::default())?;
+        self.try_set_sysvar(&SlotHashes::new(&[(
             self.accounts.sysvar_cache.get_clock().unwrap().slot,
-            latest_blockhash,
-        )]));
-        self.set_sysvar(&SlotHistory::default());
-        self.set_sysvar(&StakeHistory::default());
+            self.latest_blockhash(),
+        )]))?;
+        self.try_set_sysvar(&SlotHistory::default())?;
+        self.try_set_sysvar(&StakeHistory::default())
     }
 
     /// Includes the default sysvars.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sysvar accounts can't be written to the store.
     pub fn with_sysvars(mut self) -> Self {
-        self.set_sysvars();
+        self.set_sysvars()
+            .expect("Failed to write the sysvar accounts to the store");
         self
     }
 
@@ -490,23 +969,22 @@ impl LiteSVM {
     }
 
     #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
-    fn set_builtins(&mut self) {
-        BUILTINS.iter().for_each(|builtint| {
+    fn set_builtins(&mut self) -> Result<(), LiteSVMError> {
+        for builtint in BUILTINS.iter() {
             if builtint
                 .enable_feature_id
                 .map_or(true, |x| self.feature_set.is_active(&x))
             {
                 let loaded_program =
                     ProgramCacheEntry::new_builtin(0, builtint.name.len(), builtint.entrypoint);
-                self.accounts
-                    .programs_cache
+                Arc::make_mut(&mut self.accounts.programs_cache)
                     .replenish(builtint.program_id, Arc::new(loaded_program));
                 self.accounts.add_builtin_account(
                     builtint.program_id,
                     crate::utils::create_loadable_account_for_test(builtint.name),
-                );
+                )?;
             }
-        });
+        }
 
         let program_runtime_v1 = create_program_runtime_environment_v1(
             &self.get_svm_feature_set(),
@@ -519,15 +997,22 @@ impl LiteSVM {
         let program_runtime_v2 =
             create_program_runtime_environment_v2(&SVMTransactionExecutionBudget::default(), true);
 
-        self.accounts.programs_cache.environments.program_runtime_v1 = Arc::new(program_runtime_v1);
-        self.accounts.programs_cache.environments.program_runtime_v2 = Arc::new(program_runtime_v2);
+        let programs_cache = Arc::make_mut(&mut self.accounts.programs_cache);
+        programs_cache.environments.program_runtime_v1 = Arc::new(program_runtime_v1);
+        programs_cache.environments.program_runtime_v2 = Arc::new(program_runtime_v2);
+        Ok(())
     }
 
     /// Changes the default builtins.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the builtin program accounts can't be written to the store.
     //
     // Use `with_feature_set` beforehand to change change what builtins are added.
     pub fn with_builtins(mut self) -> Self {
-        self.set_builtins();
+        self.set_builtins()
+            .expect("Failed to write builtin account to the store");
         self
     }
 
@@ -638,27 +1123,37 @@ impl LiteSVM {
 
     #[allow(deprecated)]
     #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
-    fn set_lamports(&mut self, lamports: u64) {
+    fn set_lamports(&mut self, lamports: u64) -> Result<(), LiteSVMError> {
         self.accounts.add_account_no_checks(
             Keypair::from_bytes(&self.airdrop_kp).unwrap().pubkey(),
             AccountSharedData::new(lamports, 0, &system_program::id()),
-        );
+        )
     }
 
     /// Changes the initial lamports in LiteSVM's airdrop account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the airdrop account can't be written to the store.
     pub fn with_lamports(mut self, lamports: u64) -> Self {
-        self.set_lamports(lamports);
+        self.set_lamports(lamports)
+            .expect("Failed to write the airdrop account to the store");
         self
     }
 
     #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
-    fn set_spl_programs(&mut self) {
-        load_spl_programs(self);
+    fn set_spl_programs(&mut self) -> Result<(), LiteSVMError> {
+        load_spl_programs(self)
     }
 
     /// Includes the standard SPL programs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the program accounts can't be written to the store.
     pub fn with_spl_programs(mut self) -> Self {
-        self.set_spl_programs();
+        self.set_spl_programs()
+            .expect("Failed to write the SPL programs to the store");
         self
     }
 
@@ -685,15 +1180,284 @@ impl LiteSVM {
     }
 
     #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
-    fn set_precompiles(&mut self) {
-        load_precompiles(self);
+    fn set_account_cache_size(&mut self, size: Option<usize>) {
+        self.accounts.set_cache_size(size);
+    }
+
+    /// Bounds how many accounts are kept in memory, for states too large to hold entirely.
+    ///
+    /// Every account is still written through to the [`AccountStore`]. Past `size`, the least
+    /// recently used accounts are dropped from memory and read back from the store when needed.
+    /// Sysvar accounts are always kept and don't count towards `size`.
+    /// The default, `None`, keeps every account in memory.
+    ///
+    /// Evicted accounts are only as durable as the store, so don't combine a bounded cache
+    /// with a [`NullStore`](storage::NullStore) set explicitly. The default one is replaced
+    /// with a [`MemoryStore`](storage::MemoryStore).
+    pub fn with_account_cache_size(mut self, size: Option<usize>) -> Self {
+        self.set_account_cache_size(size);
+        self
+    }
+
+    #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
+    fn set_account_history(&mut self, enabled: bool) {
+        self.accounts.set_record_account_history(enabled);
+    }
+
+    /// Records the state of every account written by each transaction sent with
+    /// [`send_transaction`](LiteSVM::send_transaction), keyed by slot and transaction index,
+    /// so it can be looked up with [`account_history`](LiteSVM::account_history) and
+    /// [`get_account_at`](LiteSVM::get_account_at).
+    ///
+    /// The versions are kept in the [`AccountStore`], so they're only recorded by stores that
+    /// support it, which all the stores in [`storage`] do apart from [`NullStore`](storage::NullStore).
+    /// The default `NullStore` is replaced with a [`MemoryStore`](storage::MemoryStore).
+    /// Accounts changed outside of transactions, for example with
+    /// [`set_account`](LiteSVM::set_account), aren't recorded.
+    pub fn with_account_history(mut self, enabled: bool) -> Self {
+        self.set_account_history(enabled);
+        self
+    }
+
+    #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
+    fn set_account_source(&mut self, source: impl AccountSource + 'static) {
+        self.accounts.set_account_source(Arc::new(source));
+    }
+
+    /// Fetches the accounts that are neither in memory nor in the store from `source`,
+    /// such as an [`RpcAccountSource`](account_source::RpcAccountSource) pointed at a cluster
+    /// to fork from.
+    ///
+    /// Fetched accounts are written to the [`AccountStore`], so with a
+    /// [`RocksDBStore`](storage::RocksDBStore) a later run finds them without the source.
+    /// [`send_transaction`](LiteSVM::send_transaction) fetches all the missing accounts of a
+    /// transaction in one request, and upgradeable programs come with their program data.
+    pub fn with_account_source(mut self, source: impl AccountSource + 'static) -> Self {
+        self.set_account_source(source);
+        self
+    }
+
+    #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
+    fn set_slot_duration(&mut self, slot_duration: Duration) {
+        self.slot_duration = slot_duration;
+        #[cfg(feature = "serde")]
+        self.recorder
+            .record(|| session::SessionEntry::SetSlotDuration { slot_duration });
+    }
+
+    /// Changes how far [`produce_block`](LiteSVM::produce_block) moves the clock's timestamp
+    /// forward. The default is 400ms, the target slot time of a cluster.
+    pub fn with_slot_duration(mut self, slot_duration: Duration) -> Self {
+        self.set_slot_duration(slot_duration);
+        self
+    }
+
+    #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
+    fn set_auto_produce_blocks(&mut self, enabled: bool) {
+        self.auto_produce_blocks = enabled;
+    }
+
+    /// Produces a block with [`produce_block`](LiteSVM::produce_block) after each transaction
+    /// committed by [`send_transaction`](LiteSVM::send_transaction), so every transaction lands
+    /// in its own slot with a fresh blockhash.
+    pub fn with_auto_produce_blocks(mut self, enabled: bool) -> Self {
+        self.set_auto_produce_blocks(enabled);
+        self
+    }
+
+    #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
+    fn set_max_blockhash_age(&mut self, max_age: u64) -> Result<(), LiteSVMError> {
+        self.write_with_metadata(|svm| {
+            svm.blockhash_queue.set_max_age(max_age);
+            svm.write_blockhashes()
+        })
+    }
+
+    /// Changes how many blocks a blockhash stays valid for after the block it was the latest
+    /// blockhash of. The default is 150, as on a cluster.
+    ///
+    /// Only blockhashes registered by [`produce_block`](LiteSVM::produce_block) age this way,
+    /// since [`expire_blockhash`](LiteSVM::expire_blockhash) expires every previous blockhash.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `RecentBlockhashes` sysvar can't be written to the store.
+    pub fn with_max_blockhash_age(mut self, max_age: u64) -> Self {
+        self.set_max_blockhash_age(max_age)
+            .expect("Failed to write the blockhashes to the store");
+        self
+    }
+
+    #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
+    fn set_inflation(&mut self, inflation: Inflation) {
+        self.inflation = Some(inflation);
+        #[cfg(feature = "serde")]
+        self.recorder
+            .record(|| session::SessionEntry::SetInflation { inflation });
+    }
+
+    /// Pays inflation rewards to delegated stake at each epoch boundary, as a cluster with
+    /// this inflation schedule would. Without it, crossing an epoch pays nothing.
+    ///
+    /// The rewards of an epoch are a share of the total lamports of every account, taken from
+    /// the yearly validator inflation rate. They're split between stake accounts by the vote
+    /// credits their vote accounts earned, and with the vote accounts by their commission.
+    /// Vote accounts are paid right away, while stake accounts are paid over the following blocks
+    /// produced with [`produce_block`](LiteSVM::produce_block), during which the
+    /// [`EpochRewards`] sysvar is active and the stake program rejects most instructions.
+    pub fn with_inflation(mut self, inflation: Inflation) -> Self {
+        self.set_inflation(inflation);
+        self
+    }
+
+    #[cfg(feature = "serde")]
+    #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
+    fn set_session_recording(&mut self, path: impl AsRef<Path>) -> Result<(), LiteSVMError> {
+        let metadata = self.metadata().to_bytes()?;
+        self.recorder
+            .start(path.as_ref(), metadata, self.slot_duration)
+    }
+
+    /// Records every call that changes the state to the file at `path`, replacing it,
+    /// so the session can be rebuilt with [`LiteSVM::replay`], for example to reproduce
+    /// a failing test elsewhere.
+    ///
+    /// The recorded calls are [`set_account`](LiteSVM::set_account),
+    /// [`remove_account`](LiteSVM::remove_account), [`airdrop`](LiteSVM::airdrop),
+    /// [`add_program`](LiteSVM::add_program), [`set_sysvar`](LiteSVM::set_sysvar),
+    /// [`warp_to_slot`](LiteSVM::warp_to_slot), [`warp_to_epoch`](LiteSVM::warp_to_epoch),
+    /// [`set_epoch_stake`](LiteSVM::set_epoch_stake),
+    /// [`expire_blockhash`](LiteSVM::expire_blockhash), [`produce_block`](LiteSVM::produce_block),
+    /// [`with_slot_duration`](LiteSVM::with_slot_duration),
+    /// [`with_inflation`](LiteSVM::with_inflation)
+    /// and [`send_transaction`](LiteSVM::send_transaction), along with its result.
+    /// Each one is appended as a JSON line as soon as it returns.
+    ///
+    /// Replays start from [`LiteSVM::new`] with the latest blockhash, feature set, fee structure
+    /// and config this instance has now, so start recording before changing anything else.
+    /// Clones don't record.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the file can't be created. Use
+    /// [`try_with_session_recording`](LiteSVM::try_with_session_recording) to handle the error
+    /// instead.
+    #[cfg(feature = "serde")]
+    pub fn with_session_recording(self, path: impl AsRef<Path>) -> Self {
+        self.try_with_session_recording(path)
+            .expect("Failed to create the session recording")
+    }
+
+    /// Like [`with_session_recording`](LiteSVM::with_session_recording), but returns the error
+    /// instead of panicking.
+    #[cfg(feature = "serde")]
+    pub fn try_with_session_recording(
+        mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, LiteSVMError> {
+        self.set_session_recording(path)?;
+        Ok(self)
+    }
+
+    /// Rebuilds the state of a session recorded with
+    /// [`with_session_recording`](LiteSVM::with_session_recording) by making the same calls
+    /// in the same order.
+    ///
+    /// Fails with [`LiteSVMError::SessionDiverged`] if a transaction doesn't give the result it
+    /// gave when it was recorded.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use litesvm::LiteSVM;
+    ///
+    /// let svm = LiteSVM::replay("flaky-test.jsonl").unwrap();
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self, LiteSVMError> {
+        use session::SessionEntry;
+
+        let path = path.as_ref();
+        let invalid = |line: usize, reason: String| LiteSVMError::InvalidSession {
+            path: path.to_path_buf(),
+            line,
+            reason,
+        };
+        let mut svm = Self::new();
+        for (line, entry) in session::read_session(path)? {
+            match entry {
+                SessionEntry::Start {
+                    metadata,
+                    slot_duration,
+                } => {
+                    let metadata = SvmMetadata::from_bytes(&metadata)
+                        .map_err(|e| invalid(line, e.to_string()))?;
+                    svm.apply_metadata(metadata)?;
+                    svm.slot_duration = slot_duration;
+                }
+                SessionEntry::SetAccount(keyed) => {
+                    svm.set_account(keyed.pubkey, keyed.account.into())?
+                }
+                SessionEntry::RemoveAccount { pubkey } => svm.remove_account(&pubkey)?,
+                SessionEntry::Airdrop { pubkey, lamports } => svm.airdrop(&pubkey, lamports)?,
+                SessionEntry::AddProgram {
+                    program_id,
+                    program,
+                } => svm.try_add_program(program_id, &program)?,
+                SessionEntry::SetSysvar { id, data } => {
+                    let account = Account {
+                        lamports: 1,
+                        data,
+                        owner: solana_sdk_ids::sysvar::id(),
+                        executable: false,
+                        rent_epoch: 0,
+                    };
+                    svm.accounts.add_account(id, account.into())?;
+                }
+                SessionEntry::WarpToSlot { slot } => svm.try_warp_to_slot(slot)?,
+                SessionEntry::ExpireBlockhash => svm.try_expire_blockhash()?,
+                SessionEntry::ProduceBlock => svm.produce_block()?,
+                SessionEntry::SetEpochStake { vote_pubkey, stake } => {
+                    svm.set_epoch_stake(vote_pubkey, stake)
+                }
+                SessionEntry::WarpToEpoch { epoch } => svm.warp_to_epoch(epoch)?,
+                SessionEntry::SetSlotDuration { slot_duration } => {
+                    svm.set_slot_duration(slot_duration)
+                }
+                SessionEntry::SetInflation { inflation } => svm.set_inflation(inflation),
+                SessionEntry::SendTransaction {
+                    transaction,
+                    result,
+                } => {
+                    let tx: VersionedTransaction = bincode::deserialize(&transaction)
+                        .map_err(|e| invalid(line, format!("invalid transaction: {e}")))?;
+                    if svm.try_send_transaction(tx)? != result {
+                        return Err(LiteSVMError::SessionDiverged {
+                            path: path.to_path_buf(),
+                            line,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(svm)
+    }
+
+    #[cfg_attr(feature = "nodejs-internal", qualifiers(pub))]
+    fn set_precompiles(&mut self) -> Result<(), LiteSVMError> {
+        load_precompiles(self)
     }
 
     /// Adds the standard precompiles to the VM.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the precompile accounts can't be written to the store.
     //
     // Use `with_feature_set` beforehand to change change what precompiles are added.
     pub fn with_precompiles(mut self) -> Self {
-        self.set_precompiles();
+        self.set_precompiles()
+            .expect("Failed to write the precompiles to the store");
         self
     }
 
@@ -708,14 +1472,172 @@ impl LiteSVM {
         )
     }
 
+    /// Returns the hit, miss and eviction counters of the in-memory account cache.
+    ///
+    /// See [`with_account_cache_size`](LiteSVM::with_account_cache_size).
+    pub fn account_cache_stats(&self) -> AccountCacheStats {
+        self.accounts.cache_stats()
+    }
+
     /// Returns all information associated with the account of the provided pubkey.
     pub fn get_account(&self, pubkey: &Pubkey) -> Option<Account> {
         self.accounts.get_account(pubkey).map(Into::into)
     }
 
+    /// Returns every recorded state of the account at `pubkey`, oldest first.
+    ///
+    /// This is empty unless account history is enabled with
+    /// [`with_account_history`](LiteSVM::with_account_history).
+    pub fn account_history(&self, pubkey: &Pubkey) -> Result<Vec<AccountVersion>, LiteSVMError> {
+        Ok(self.accounts.store.get_account_versions(pubkey)?)
+    }
+
+    /// Returns the account at `pubkey` as it was at the end of `slot`, from the account history.
+    ///
+    /// Returns `None` if the account didn't exist then, or if no transaction had written it yet.
+    pub fn get_account_at(
+        &self,
+        pubkey: &Pubkey,
+        slot: u64,
+    ) -> Result<Option<Account>, LiteSVMError> {
+        Ok(self
+            .account_history(pubkey)?
+            .into_iter()
+            .take_while(|version| version.slot <= slot)
+            .last()
+            .and_then(|version| version.account)
+            .map(Into::into))
+    }
+
+    /// Returns every account, whether it's held in memory or only in the store, in no particular order.
+    ///
+    /// The accounts only held in the store are read from it as the iteration goes,
+    /// so each item can fail with the store's error.
+    pub fn accounts(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<(Pubkey, Account), LiteSVMError>> + '_, LiteSVMError>
+    {
+        Ok(self.accounts.iter_accounts()?.map(|item| {
+            item.map(|(pubkey, account)| (pubkey, account.into()))
+                .map_err(Into::into)
+        }))
+    }
+
+    /// Writes the accounts for which `filter` returns `true` to `writer` as a JSON array,
+    /// sorted by address.
+    ///
+    /// Each account has the shape printed by `solana account --output json`, with its data
+    /// encoded as base64.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use litesvm::LiteSVM;
+    /// use solana_pubkey::Pubkey;
+    ///
+    /// let mut svm = LiteSVM::new();
+    /// let wallet = Pubkey::new_unique();
+    /// svm.airdrop(&wallet, 1_000).unwrap();
+    ///
+    /// let mut json = Vec::new();
+    /// svm.export_accounts_json(&mut json, |pubkey, _| *pubkey == wallet)
+    ///     .unwrap();
+    /// assert!(String::from_utf8(json).unwrap().contains(r#""lamports": 1000"#));
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn export_accounts_json<W: std::io::Write>(
+        &self,
+        writer: W,
+        filter: impl Fn(&Pubkey, &Account) -> bool,
+    ) -> Result<(), LiteSVMError> {
+        let mut accounts = Vec::new();
+        for item in self.accounts()? {
+            let (pubkey, account) = item?;
+            if filter(&pubkey, &account) {
+                accounts.push((pubkey, account));
+            }
+        }
+        accounts.sort_unstable_by_key(|(pubkey, _)| *pubkey);
+        let accounts = accounts
+            .iter()
+            .map(|(pubkey, account)| account_json::KeyedAccountJson::new(pubkey, account))
+            .collect::<Vec<_>>();
+        serde_json::to_writer_pretty(writer, &accounts)?;
+        Ok(())
+    }
+
     /// Sets all information associated with the account of the provided pubkey.
     pub fn set_account(&mut self, pubkey: Pubkey, data: Account) -> Result<(), LiteSVMError> {
-        self.accounts.add_account(pubkey, data.into())
+        #[cfg(feature = "serde")]
+        let recorded = self.recorder.is_recording().then(|| data.clone());
+        self.accounts.add_account(pubkey, data.into())?;
+        #[cfg(feature = "serde")]
+        if let Some(account) = recorded {
+            self.recorder.record(|| {
+                session::SessionEntry::SetAccount(account_json::KeyedAccountJson::new(
+                    &pubkey, &account,
+                ))
+            });
+        }
+        Ok(())
+    }
+
+    /// Removes the account from the test environment and from the store.
+    ///
+    /// Removing an account that doesn't exist does nothing. If the account was a program,
+    /// later transactions invoking it fail as they would for a closed program.
+    /// The [account source](LiteSVM::with_account_source) isn't asked for the account,
+    /// and won't be asked for it afterwards either.
+    pub fn remove_account(&mut self, pubkey: &Pubkey) -> Result<(), LiteSVMError> {
+        self.accounts.remove_account(pubkey)?;
+        #[cfg(feature = "serde")]
+        self.recorder
+            .record(|| session::SessionEntry::RemoveAccount { pubkey: *pubkey });
+        Ok(())
+    }
+
+    /// Returns the accounts owned by `owner` that pass every filter, sorted by address,
+    /// like the `getProgramAccounts` RPC method.
+    ///
+    /// If `data_slice` is set, only that part of each account's data is returned.
+    /// Filters are always applied to the full data.
+    pub fn get_program_accounts(
+        &self,
+        owner: &Pubkey,
+        filters: &[AccountFilter],
+        data_slice: Option<DataSlice>,
+    ) -> Result<Vec<(Pubkey, Account)>, LiteSVMError> {
+        Ok(self
+            .accounts
+            .get_program_accounts(owner)?
+            .into_iter()
+            .filter(|(_, account)| filters.iter().all(|filter| filter.matches(account.data())))
+            .map(|(pubkey, account)| {
+                let mut account = Account::from(account);
+                if let Some(data_slice) = data_slice {
+                    account.data = data_slice.apply(&account.data);
+                }
+                (pubkey, account)
+            })
+            .collect())
+    }
+
+    /// Returns the SPL Token and Token-2022 accounts owned by `owner`, sorted by address.
+    pub fn get_token_accounts_by_owner(&self, owner: &Pubkey) -> Vec<(Pubkey, Account)> {
+        self.accounts
+            .get_token_accounts_by_owner(owner)
+            .into_iter()
+            .map(|(pubkey, account)| (pubkey, account.into()))
+            .collect()
+    }
+
+    /// Returns the SPL Token and Token-2022 accounts holding `mint`, sorted by address.
+    pub fn get_token_accounts_by_mint(&self, mint: &Pubkey) -> Vec<(Pubkey, Account)> {
+        self.accounts
+            .get_token_accounts_by_mint(mint)
+            .into_iter()
+            .map(|(pubkey, account)| (pubkey, account.into()))
+            .collect()
     }
 
     /// Gets the balance of the provided account pubkey.
@@ -725,17 +1647,66 @@ impl LiteSVM {
 
     /// Gets the latest blockhash.
     pub fn latest_blockhash(&self) -> Hash {
-        self.latest_blockhash
+        self.blockhash_queue.latest().blockhash
+    }
+
+    /// Gets the latest blockhash along with the last block height at which transactions
+    /// signed with it are still accepted, like the `getLatestBlockhash` RPC method.
+    pub fn get_latest_blockhash_with_last_valid_block_height(&self) -> (Hash, u64) {
+        (
+            self.latest_blockhash(),
+            self.blockhash_queue.last_valid_block_height(),
+        )
+    }
+
+    /// Gets the block height, which every new blockhash increments.
+    pub fn get_block_height(&self) -> u64 {
+        self.blockhash_queue.block_height()
+    }
+
+    /// Returns whether transactions signed with `blockhash` are still accepted.
+    pub fn is_blockhash_valid(&self, blockhash: &Hash) -> bool {
+        self.blockhash_queue.get(blockhash).is_some()
     }
 
     /// Sets the sysvar to the test environment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sysvar account can't be written to the store.
+    /// Use [`try_set_sysvar`](LiteSVM::try_set_sysvar) to handle the error instead.
     pub fn set_sysvar<T>(&mut self, sysvar: &T)
+    where
+        T: Sysvar + SysvarId,
+    {
+        self.try_set_sysvar(sysvar)
+            .expect("Failed to write the sysvar to the store");
+    }
+
+    /// Like [`set_sysvar`](LiteSVM::set_sysvar), but returns the store error instead of
+    /// panicking.
+    pub fn try_set_sysvar<T>(&mut self, sysvar: &T) -> Result<(), LiteSVMError>
+    where
+        T: Sysvar + SysvarId,
+    {
+        self.write_with_metadata(|svm| svm.write_sysvar(sysvar))?;
+        #[cfg(feature = "serde")]
+        self.recorder.record(|| session::SessionEntry::SetSysvar {
+            id: T::id(),
+            data: bincode::serialize(sysvar).unwrap(),
+        });
+        Ok(())
+    }
+
+    /// Like [`try_set_sysvar`](LiteSVM::try_set_sysvar), but isn't recorded by itself, for the
+    /// calls that are recorded as a whole.
+    fn write_sysvar<T>(&mut self, sysvar: &T) -> Result<(), LiteSVMError>
     where
         T: Sysvar + SysvarId,
     {
         let account =
             AccountSharedData::new_data(1, &sysvar, &solana_sdk_ids::sysvar::id()).unwrap();
-        self.accounts.add_account(T::id(), account).unwrap();
+        self.accounts.add_account(T::id(), account)
     }
 
     /// Gets a sysvar from the test environment.
@@ -763,12 +1734,33 @@ impl LiteSVM {
         });
 
         acc.set_lamports(lamports);
-        self.accounts.add_account_no_checks(*pubkey, acc);
+        self.accounts.add_account_no_checks(*pubkey, acc)?;
+        #[cfg(feature = "serde")]
+        self.recorder.record(|| session::SessionEntry::Airdrop {
+            pubkey: *pubkey,
+            lamports,
+        });
         Ok(())
     }
 
     /// Adds a builtin program to the test environment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the program account can't be written to the store.
+    /// Use [`try_add_builtin`](LiteSVM::try_add_builtin) to handle the error instead.
     pub fn add_builtin(&mut self, program_id: Pubkey, entrypoint: BuiltinFunctionWithContext) {
+        self.try_add_builtin(program_id, entrypoint)
+            .expect("Failed to write the builtin account to the store");
+    }
+
+    /// Like [`add_builtin`](LiteSVM::add_builtin), but returns the store error instead of
+    /// panicking.
+    pub fn try_add_builtin(
+        &mut self,
+        program_id: Pubkey,
+        entrypoint: BuiltinFunctionWithContext,
+    ) -> Result<(), LiteSVMError> {
         let builtin = ProgramCacheEntry::new_builtin(
             self.accounts
                 .sysvar_cache
@@ -779,13 +1771,12 @@ impl LiteSVM {
             entrypoint,
         );
 
-        self.accounts
-            .programs_cache
-            .replenish(program_id, Arc::new(builtin));
+        Arc::make_mut(&mut self.accounts.programs_cache).replenish(program_id, Arc::new(builtin));
 
-        let mut account = AccountSharedData::new(0, 1, &bpf_loader::id());
-        account.set_executable(true);
-        self.accounts.add_account(program_id, account).unwrap();
+        self.accounts.add_builtin_account(
+            program_id,
+            crate::utils::create_loadable_account_for_test(&program_id.to_string()),
+        )
     }
 
     /// Adds an SBF program to the test environment from the file specified.
@@ -800,39 +1791,247 @@ impl LiteSVM {
     }
 
     /// Adds am SBF program to the test environment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the program fails verification or can't be written to the store.
+    /// Use [`try_add_program`](LiteSVM::try_add_program) to handle the error instead.
     pub fn add_program(&mut self, program_id: impl Into<Pubkey>, program_bytes: &[u8]) {
+        self.try_add_program(program_id, program_bytes)
+            .expect("Failed to add the program");
+    }
+
+    /// Like [`add_program`](LiteSVM::add_program), but returns the error instead of panicking.
+    pub fn try_add_program(
+        &mut self,
+        program_id: impl Into<Pubkey>,
+        program_bytes: &[u8],
+    ) -> Result<(), LiteSVMError> {
         let program_id = program_id.into();
         let program_len = program_bytes.len();
         let lamports = self.minimum_balance_for_rent_exemption(program_len);
         let mut account = AccountSharedData::new(lamports, program_len, &bpf_loader::id());
         account.set_executable(true);
         account.set_data_from_slice(program_bytes);
-        let current_slot = self
-            .accounts
-            .sysvar_cache
-            .get_clock()
-            .unwrap_or_default()
-            .slot;
-        let mut loaded_program = solana_bpf_loader_program::load_program_from_bytes(
-            None,
-            &mut LoadProgramMetrics::default(),
-            account.data(),
-            account.owner(),
-            account.data().len(),
-            current_slot,
-            self.accounts
-                .programs_cache
-                .environments
-                .program_runtime_v1
-                .clone(),
-            false,
-        )
-        .unwrap_or_default();
-        loaded_program.effective_slot = current_slot;
-        self.accounts.add_account(program_id, account).unwrap();
+        // loading the account also verifies the program and adds it to the program cache
+        self.accounts.add_account(program_id, account)?;
+        #[cfg(feature = "serde")]
+        self.recorder.record(|| session::SessionEntry::AddProgram {
+            program_id,
+            program: program_bytes.to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Deploys each `(program_id, path)` pair from an ELF file, like the `--bpf-program`
+    /// option of `solana-test-validator`.
+    ///
+    /// The programs are owned by the upgradeable loader with upgrades disabled, so each one
+    /// also gets its program data account at the usual derived address.
+    pub fn load_programs<P: AsRef<Path>>(
+        &mut self,
+        programs: &[(Pubkey, P)],
+    ) -> Result<(), LiteSVMError> {
+        for (program_id, path) in programs {
+            let bytes = std::fs::read(path)?;
+            self.add_upgradeable_program(*program_id, &bytes, None)?;
+        }
+        Ok(())
+    }
+
+    /// Deploys an upgradeable program from an ELF file, like the `--upgradeable-program`
+    /// option of `solana-test-validator`.
+    pub fn load_upgradeable_program(
+        &mut self,
+        program_id: Pubkey,
+        path: impl AsRef<Path>,
+        upgrade_authority: Pubkey,
+    ) -> Result<(), LiteSVMError> {
+        let bytes = std::fs::read(path)?;
+        self.add_upgradeable_program(program_id, &bytes, Some(upgrade_authority))
+    }
+
+    fn add_upgradeable_program(
+        &mut self,
+        program_id: Pubkey,
+        program_bytes: &[u8],
+        upgrade_authority_address: Option<Pubkey>,
+    ) -> Result<(), LiteSVMError> {
+        let programdata_address = get_program_data_address(&program_id);
+        let metadata_len = UpgradeableLoaderState::size_of_programdata_metadata();
+        let programdata_len = metadata_len + program_bytes.len();
+        let mut programdata = AccountSharedData::new(
+            self.minimum_balance_for_rent_exemption(programdata_len),
+            programdata_len,
+            &bpf_loader_upgradeable::id(),
+        );
+        programdata.set_state(&UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address,
+        })?;
+        programdata.data_as_mut_slice()[metadata_len..].copy_from_slice(program_bytes);
+
+        let program_len = UpgradeableLoaderState::size_of_program();
+        let mut program = AccountSharedData::new(
+            self.minimum_balance_for_rent_exemption(program_len),
+            program_len,
+            &bpf_loader_upgradeable::id(),
+        );
+        program.set_state(&UpgradeableLoaderState::Program {
+            programdata_address,
+        })?;
+        program.set_executable(true);
+
+        // the program data has to be there before loading the program reads it
         self.accounts
-            .programs_cache
-            .replenish(program_id, Arc::new(loaded_program));
+            .add_account(programdata_address, programdata)?;
+        self.accounts.add_account(program_id, program)
+    }
+
+    /// Loads an account from a file written by `solana account --output json`, like the
+    /// `--account` option of `solana-test-validator`, returning its address.
+    ///
+    /// The account is stored at `address` if set, and at the address in the file otherwise.
+    #[cfg(feature = "serde")]
+    pub fn load_account_file(
+        &mut self,
+        address: Option<Pubkey>,
+        path: impl AsRef<Path>,
+    ) -> Result<Pubkey, LiteSVMError> {
+        let (pubkey, account) = account_json::read_account_file(path.as_ref())?;
+        let pubkey = address.unwrap_or(pubkey);
+        self.accounts.add_account(pubkey, account.into())?;
+        Ok(pubkey)
+    }
+
+    /// Loads every `.json` file in the directory as an account written by
+    /// `solana account --output json`, like the `--account-dir` option of `solana-test-validator`.
+    ///
+    /// Every file is read before any account is written, and executable accounts are written
+    /// last so the program data of upgradeable programs is found when loading them.
+    #[cfg(feature = "serde")]
+    pub fn load_accounts_dir(&mut self, path: impl AsRef<Path>) -> Result<(), LiteSVMError> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                paths.push(path);
+            }
+        }
+        paths.sort_unstable();
+        let mut accounts = paths
+            .iter()
+            .map(|path| account_json::read_account_file(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        accounts.sort_by_key(|(_, account)| account.executable);
+        for (pubkey, account) in accounts {
+            self.accounts.add_account(pubkey, account.into())?;
+        }
+        Ok(())
+    }
+
+    /// Replays a mainnet transaction offline and reports how it differs from what was recorded.
+    ///
+    /// The file is a JSON object with the `getTransaction` response for the transaction,
+    /// fetched with the base64 encoding, under `transaction`, and the accounts it touched as they
+    /// were before it ran, in the format written by `solana account --output json`, under
+    /// `accounts`. Lookup table accounts must be included for versioned transactions.
+    ///
+    /// The accounts are loaded and the clock is set to the slot and block time of the
+    /// transaction, which is then sent without checking its blockhash. The report compares
+    /// its result, logs, compute units and post balances with the recorded ones.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use litesvm::LiteSVM;
+    ///
+    /// let mut svm = LiteSVM::new();
+    /// let report = svm.replay_transaction("failed-swap.json").unwrap();
+    /// for mismatch in &report.mismatches {
+    ///     println!("{mismatch:?}");
+    /// }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn replay_transaction(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<types::ReplayReport, LiteSVMError> {
+        let mut replay = replay::Replay::read(path.as_ref())?;
+        // the program data of upgradeable programs has to be there before their programs
+        replay
+            .accounts
+            .sort_by_key(|(_, account)| account.executable);
+        for (pubkey, account) in std::mem::take(&mut replay.accounts) {
+            self.accounts.add_account(pubkey, account.into())?;
+        }
+        let mut clock = self.get_sysvar::<Clock>();
+        clock.slot = replay.slot;
+        if let Some(block_time) = replay.block_time {
+            clock.unix_timestamp = block_time;
+        }
+        self.try_set_sysvar(&clock)?;
+
+        // the recorded blockhash is long expired
+        let blockhash_check = std::mem::replace(&mut self.blockhash_check, false);
+        let result = self.try_send_transaction(std::mem::take(&mut replay.transaction));
+        self.blockhash_check = blockhash_check;
+        let result = result?;
+        Ok(replay.compare(result, |pubkey| {
+            self.get_balance(pubkey).unwrap_or_default()
+        }))
+    }
+
+    /// Imports the accounts selected by `filter` from Agave snapshot archives (`.tar.zst`),
+    /// writing them to the store in a single write.
+    ///
+    /// Pass a full snapshot followed by its incremental snapshot to import the newest state.
+    /// Accounts the snapshot shows as closed are removed.
+    ///
+    /// Unless the filter keeps the current one, the feature set is replaced by the features
+    /// activated in the snapshot and the builtins are set up again for it. The sysvars are only
+    /// imported if selected, for example with `SnapshotFilter::with_owners([sysvar::id()])`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use litesvm::{types::SnapshotFilter, LiteSVM};
+    /// use solana_pubkey::pubkey;
+    ///
+    /// let mut svm = LiteSVM::new();
+    /// let info = svm
+    ///     .import_snapshot(
+    ///         &["snapshot-100-11111111111111111111111111111111.tar.zst"],
+    ///         &SnapshotFilter::default()
+    ///             .with_owners([pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]),
+    ///     )
+    ///     .unwrap();
+    /// println!("imported {} accounts at slot {}", info.accounts, info.slot);
+    /// ```
+    #[cfg(feature = "agave-snapshot")]
+    pub fn import_snapshot<P: AsRef<Path>>(
+        &mut self,
+        archives: &[P],
+        filter: &types::SnapshotFilter,
+    ) -> Result<types::SnapshotInfo, LiteSVMError> {
+        let snapshot = agave_snapshot::SnapshotAccounts::read(archives, filter)?;
+        let slot = snapshot.slot;
+        let feature_set = snapshot.feature_set();
+        if !filter.keep_feature_set {
+            self.set_feature_set(feature_set.clone());
+            self.set_builtins()?;
+        }
+        let writes = snapshot.into_writes();
+        let accounts = writes
+            .iter()
+            .filter(|(_, account)| account.is_some())
+            .count();
+        self.accounts.import_accounts(writes)?;
+        Ok(types::SnapshotInfo {
+            slot,
+            accounts,
+            feature_set,
+        })
     }
 
     fn create_transaction_context(
@@ -923,7 +2122,7 @@ impl LiteSVM {
         Result<(), TransactionError>,
         u64,
         Option<TransactionContext>,
-        u64,
+        FeeDetails,
         Option<Pubkey>,
     ) {
         let compute_budget = self.compute_budget.unwrap_or_else(|| ComputeBudget {
@@ -934,7 +2133,7 @@ impl LiteSVM {
 
         let blockhash = tx.message().recent_blockhash();
         //reload program cache
-        let mut program_cache_for_tx_batch = self.accounts.programs_cache.clone();
+        let mut program_cache_for_tx_batch = (*self.accounts.programs_cache).clone();
         let mut accumulated_consume_units = 0;
         let message = tx.message();
         let account_keys = message.account_keys();
@@ -944,13 +2143,14 @@ impl LiteSVM {
             .flat_map(|instruction| &instruction.accounts)
             .unique()
             .collect::<Vec<&u8>>();
-        let fee = solana_fee::calculate_fee(
+        let fee_details = solana_fee::calculate_fee_details(
             message,
             false,
             self.fee_structure.lamports_per_signature,
-            0,
+            compute_budget_limits.get_prioritization_fee(),
             solana_fee::FeeFeatures::from(&self.feature_set),
         );
+        let fee = fee_details.total_fee();
         let mut validated_fee_payer = false;
         let mut payer_key = None;
         let maybe_accounts = account_keys
@@ -966,7 +2166,11 @@ impl LiteSVM {
                         .unwrap_or(false);
                     let mut account = if !instruction_account
                         && !message.is_writable(i)
-                        && self.accounts.programs_cache.find(key).is_some()
+                        && self
+                            .accounts
+                            .programs_cache
+                            .find(key)
+                            .is_some_and(|entry| !entry.is_tombstone())
                     {
                         // Optimization to skip loading of accounts which are only used as
                         // programs in top-level instructions and not passed as instruction accounts.
@@ -1001,16 +2205,24 @@ impl LiteSVM {
         let mut accounts = match maybe_accounts {
             Ok(accs) => accs,
             Err(e) => {
-                return (Err(e), accumulated_consume_units, None, fee, payer_key);
+                return (
+                    Err(e),
+                    accumulated_consume_units,
+                    None,
+                    fee_details,
+                    payer_key,
+                );
             }
         };
+        self.accounts
+            .load_fetched_programs(&accounts, &mut program_cache_for_tx_batch);
         if !validated_fee_payer {
             error!("Failed to validate fee payer");
             return (
                 Err(TransactionError::AccountNotFound),
                 accumulated_consume_units,
                 None,
-                fee,
+                fee_details,
                 payer_key,
             );
         }
@@ -1071,7 +2283,9 @@ impl LiteSVM {
                         EnvironmentConfig::new(
                             *blockhash,
                             self.fee_structure.lamports_per_signature,
-                            &LiteSVMCallback {},
+                            &LiteSVMCallback {
+                                epoch_stakes: &self.epoch_stakes,
+                            },
                             &self.get_svm_feature_set(),
                             &self.accounts.sysvar_cache,
                         ),
@@ -1092,11 +2306,17 @@ impl LiteSVM {
                     tx_result,
                     accumulated_consume_units,
                     Some(context),
-                    fee,
+                    fee_details,
                     payer_key,
                 )
             }
-            Err(e) => (Err(e), accumulated_consume_units, None, fee, payer_key),
+            Err(e) => (
+                Err(e),
+                accumulated_consume_units,
+                None,
+                fee_details,
+                payer_key,
+            ),
         }
     }
 
@@ -1233,15 +2453,36 @@ impl LiteSVM {
                     compute_units_consumed,
                     context,
                 },
-            fee,
+            fee_details,
             payer_key,
         } = match self.check_and_process_transaction(&sanitized_tx, log_collector) {
             Ok(value) => value,
             Err(value) => return value,
         };
         if let Some(ctx) = context {
-            let tx_result = self.check_tx_result(result, payer_key, fee);
-            execution_result_if_context(sanitized_tx, ctx, tx_result, compute_units_consumed)
+            let mut execution_result =
+                execution_result_if_context(sanitized_tx, ctx, result, compute_units_consumed);
+            let mut fee_charged = true;
+            if execution_result.tx_result.is_err() {
+                // a failed transaction only commits the fee withdrawal
+                execution_result.post_accounts = Vec::new();
+                fee_charged = false;
+                if let Some(payer) = payer_key {
+                    match self.accounts.withdraw(&payer, fee_details.total_fee()) {
+                        Ok(account) => {
+                            execution_result.post_accounts.push((payer, account));
+                            fee_charged = true;
+                        }
+                        Err(e) => execution_result.tx_result = Err(e),
+                    }
+                }
+            }
+            // a fee that couldn't be withdrawn isn't burned or collected either
+            if fee_charged {
+                execution_result.fees =
+                    self.distribute_fees(&fee_details, &mut execution_result.post_accounts);
+            }
+            execution_result
         } else {
             ExecutionResult::result_and_compute_units(result, compute_units_consumed)
         }
@@ -1259,33 +2500,24 @@ impl LiteSVM {
                     compute_units_consumed,
                     context,
                 },
+            fee_details,
             ..
         } = match self.check_and_process_transaction(&sanitized_tx, log_collector) {
             Ok(value) => value,
             Err(value) => return value,
         };
         if let Some(ctx) = context {
-            execution_result_if_context(sanitized_tx, ctx, result, compute_units_consumed)
+            let mut execution_result =
+                execution_result_if_context(sanitized_tx, ctx, result, compute_units_consumed);
+            // nothing is committed, so the collector isn't credited
+            (execution_result.fees, _) =
+                self.split_fees(&fee_details, &execution_result.post_accounts);
+            execution_result
         } else {
             ExecutionResult::result_and_compute_units(result, compute_units_consumed)
         }
     }
 
-    fn check_tx_result(
-        &mut self,
-        result: Result<(), TransactionError>,
-        payer_key: Option<Pubkey>,
-        fee: u64,
-    ) -> Result<(), TransactionError> {
-        if result.is_ok() {
-            result
-        } else if let Some(payer) = payer_key {
-            self.accounts.withdraw(&payer, fee).and(result)
-        } else {
-            result
-        }
-    }
-
     fn check_and_process_transaction(
         &self,
         sanitized_tx: &SanitizedTransaction,
@@ -1294,7 +2526,7 @@ impl LiteSVM {
         self.maybe_blockhash_check(sanitized_tx)?;
         let compute_budget_limits = get_compute_budget_limits(sanitized_tx, &self.feature_set)?;
         self.maybe_history_check(sanitized_tx)?;
-        let (result, compute_units_consumed, context, fee, payer_key) =
+        let (result, compute_units_consumed, context, fee_details, payer_key) =
             self.process_transaction(sanitized_tx, compute_budget_limits, log_collector);
         Ok(CheckAndProcessTransactionSuccess {
             core: {
@@ -1304,11 +2536,73 @@ impl LiteSVM {
                     context,
                 }
             },
-            fee,
+            fee_details,
             payer_key,
         })
     }
 
+    /// Credits the fee collector with its part of the fee a transaction paid, adding the
+    /// collector's new state to `post_accounts`.
+    fn distribute_fees(
+        &self,
+        fee_details: &FeeDetails,
+        post_accounts: &mut Vec<(Pubkey, AccountSharedData)>,
+    ) -> FeeBreakdown {
+        let (fees, collector_account) = self.split_fees(fee_details, post_accounts);
+        if let (Some(collector), Some(account)) = (self.fee_collector, collector_account) {
+            match post_accounts
+                .iter_mut()
+                .find(|(pubkey, _)| pubkey == &collector)
+            {
+                Some((_, post_account)) => *post_account = account,
+                None => post_accounts.push((collector, account)),
+            }
+        }
+        fees
+    }
+
+    /// Splits the fee a transaction paid into the part that's burned and the part credited
+    /// to the fee collector, returning the collector's state once credited if it can be.
+    fn split_fees(
+        &self,
+        fee_details: &FeeDetails,
+        post_accounts: &[(Pubkey, AccountSharedData)],
+    ) -> (FeeBreakdown, Option<AccountSharedData>) {
+        let transaction_fee = fee_details.transaction_fee();
+        let mut fees = FeeBreakdown {
+            transaction_fee,
+            prioritization_fee: fee_details.prioritization_fee(),
+            burned: fee_details.total_fee(),
+            collected: 0,
+        };
+        let Some(collector) = self.fee_collector else {
+            return (fees, None);
+        };
+        let burned = transaction_fee * u64::from(self.fee_burn_percent) / 100;
+        let reward = fee_details.total_fee() - burned;
+        if reward == 0 {
+            return (fees, None);
+        }
+        // the collector may have been written by the transaction itself
+        let mut account = match post_accounts
+            .iter()
+            .find(|(pubkey, _)| pubkey == &collector)
+        {
+            Some((_, account)) => account.clone(),
+            None => self.accounts.get_account(&collector).unwrap_or_default(),
+        };
+        let rent = self.accounts.sysvar_cache.get_rent().unwrap_or_default();
+        if !system_program::check_id(account.owner())
+            || account.checked_add_lamports(reward).is_err()
+            || !rent.is_exempt(account.lamports(), account.data().len())
+        {
+            return (fees, None);
+        }
+        fees.burned = burned;
+        fees.collected = reward;
+        (fees, Some(account))
+    }
+
     fn maybe_history_check(
         &self,
         sanitized_tx: &SanitizedTransaction,
@@ -1353,13 +2647,76 @@ impl LiteSVM {
     }
 
     /// Submits a signed transaction.
+    ///
+    /// If the store fails to commit the transaction, it isn't applied and fails with
+    /// [`TransactionError::CommitCancelled`], and the store error is logged.
+    /// Use [`try_send_transaction`](LiteSVM::try_send_transaction) to get the store error instead.
     pub fn send_transaction(&mut self, tx: impl Into<VersionedTransaction>) -> TransactionResult {
+        let (result, outcome) = self.send_and_record(tx.into());
+        if let Err(e) = outcome {
+            let signature = match &result {
+                Ok(meta) => meta.signature,
+                Err(failed) => failed.meta.signature,
+            };
+            error!("Failed to send transaction {signature}: {e}");
+        }
+        result
+    }
+
+    /// Like [`send_transaction`](LiteSVM::send_transaction), but returns the store error if the
+    /// store fails to commit the transaction, or to produce the block after it when
+    /// [`with_auto_produce_blocks`](LiteSVM::with_auto_produce_blocks) is set.
+    pub fn try_send_transaction(
+        &mut self,
+        tx: impl Into<VersionedTransaction>,
+    ) -> Result<TransactionResult, LiteSVMError> {
+        let (result, outcome) = self.send_and_record(tx.into());
+        outcome?;
+        Ok(result)
+    }
+
+    /// Sends and records the transaction, producing a block after it if enabled.
+    ///
+    /// Returns the transaction's result along with the store error that kept the transaction
+    /// from being committed or the block from being produced, if any.
+    fn send_and_record(
+        &mut self,
+        vtx: VersionedTransaction,
+    ) -> (TransactionResult, Result<(), LiteSVMError>) {
+        #[cfg(feature = "serde")]
+        let recorded = self.recorder.is_recording().then(|| vtx.clone());
+        let (result, committed) = self.send_versioned_transaction(vtx);
+        let committed = match committed {
+            Ok(committed) => committed,
+            Err(e) => return (result, Err(e)),
+        };
+        #[cfg(feature = "serde")]
+        if let Some(tx) = recorded {
+            self.recorder
+                .record(|| session::SessionEntry::send_transaction(&tx, &result));
+        }
+        if committed && self.auto_produce_blocks {
+            let produced = self.produce_block();
+            return (result, produced);
+        }
+        (result, Ok(()))
+    }
+
+    /// Sends the transaction, returning its result and whether it was committed.
+    ///
+    /// If the store fails to commit it, the result is [`TransactionError::CommitCancelled`]
+    /// and the store error is returned alongside it.
+    fn send_versioned_transaction(
+        &mut self,
+        vtx: VersionedTransaction,
+    ) -> (TransactionResult, Result<bool, LiteSVMError>) {
         let log_collector = LogCollector {
             bytes_limit: self.log_bytes_limit,
             ..Default::default()
         };
         let log_collector = Rc::new(RefCell::new(log_collector));
-        let vtx: VersionedTransaction = tx.into();
+        self.accounts
+            .prefetch_accounts(vtx.message.static_account_keys());
         let ExecutionResult {
             post_accounts,
             tx_result,
@@ -1367,6 +2724,7 @@ impl LiteSVM {
             compute_units_consumed,
             inner_instructions,
             return_data,
+            fees,
             included,
         } = if self.sigverify {
             self.execute_transaction(vtx, log_collector.clone())
@@ -1382,23 +2740,29 @@ impl LiteSVM {
             compute_units_consumed,
             return_data,
             signature,
+            fees,
         };
 
-        if let Err(tx_err) = tx_result {
-            let err = TransactionResult::Err(FailedTransactionMetadata { err: tx_err, meta });
-            if included {
-                self.history.add_new_transaction(signature, err.clone());
-            }
-            err
-        } else {
-            self.history
-                .add_new_transaction(signature, Ok(meta.clone()));
-            self.accounts
-                .sync_accounts(post_accounts)
-                .expect("It shouldn't be possible to write invalid sysvars in send_transaction.");
-
-            TransactionResult::Ok(meta)
+        if let (Err(err), false) = (&tx_result, included) {
+            let result = TransactionResult::Err(FailedTransactionMetadata {
+                err: err.clone(),
+                meta,
+            });
+            return (result, Ok(false));
         }
+        if let Err(e) = self.accounts.sync_accounts(post_accounts) {
+            let result = TransactionResult::Err(FailedTransactionMetadata {
+                err: TransactionError::CommitCancelled,
+                meta,
+            });
+            return (result, Err(e));
+        }
+        let result = match tx_result {
+            Ok(()) => TransactionResult::Ok(meta),
+            Err(err) => TransactionResult::Err(FailedTransactionMetadata { err, meta }),
+        };
+        self.history.add_new_transaction(signature, result.clone());
+        (result, Ok(true))
     }
 
     /// Simulates a transaction.
@@ -1418,6 +2782,7 @@ impl LiteSVM {
             compute_units_consumed,
             inner_instructions,
             return_data,
+            fees,
             ..
         } = if self.sigverify {
             self.execute_transaction_readonly(tx.into(), log_collector.clone())
@@ -1433,6 +2798,7 @@ impl LiteSVM {
             inner_instructions,
             compute_units_consumed,
             return_data,
+            fees,
         };
 
         if let Err(tx_err) = tx_result {
@@ -1445,22 +2811,390 @@ impl LiteSVM {
         }
     }
 
-    /// Expires the current blockhash.
+    /// Replaces the current blockhash with a new one, expiring it along with
+    /// every other recent blockhash.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `RecentBlockhashes` sysvar can't be written to the store.
+    /// Use [`try_expire_blockhash`](LiteSVM::try_expire_blockhash) to handle the error instead.
     pub fn expire_blockhash(&mut self) {
-        self.latest_blockhash = create_blockhash(&self.latest_blockhash.to_bytes());
-        #[allow(deprecated)]
-        self.set_sysvar(&RecentBlockhashes::from_iter([IterItem(
-            0,
-            &self.latest_blockhash,
-            self.fee_structure.lamports_per_signature,
-        )]));
+        self.try_expire_blockhash()
+            .expect("Failed to write the blockhashes to the store");
+    }
+
+    /// Like [`expire_blockhash`](LiteSVM::expire_blockhash), but returns the store error instead
+    /// of panicking.
+    pub fn try_expire_blockhash(&mut self) -> Result<(), LiteSVMError> {
+        self.write_with_metadata(|svm| {
+            let blockhash = create_blockhash(&svm.latest_blockhash().to_bytes());
+            svm.blockhash_queue
+                .replace(blockhash, svm.fee_structure.lamports_per_signature);
+            svm.write_blockhashes()
+        })?;
+        #[cfg(feature = "serde")]
+        self.recorder
+            .record(|| session::SessionEntry::ExpireBlockhash);
+        Ok(())
+    }
+
+    /// Registers a new blockhash, keeping the previous ones valid until they're too old.
+    fn advance_blockhash(&mut self) -> Result<(), LiteSVMError> {
+        let blockhash = create_blockhash(&self.latest_blockhash().to_bytes());
+        self.blockhash_queue
+            .register(blockhash, self.fee_structure.lamports_per_signature);
+        self.write_blockhashes()
+    }
+
+    fn write_blockhashes(&mut self) -> Result<(), LiteSVMError> {
+        self.write_sysvar(&self.recent_blockhashes())
+    }
+
+    #[allow(deprecated)]
+    fn recent_blockhashes(&self) -> RecentBlockhashes {
+        self.blockhash_queue
+            .iter()
+            .take(solana_sysvar::recent_blockhashes::MAX_ENTRIES)
+            .map(|entry| {
+                IterItem(
+                    entry.block_height,
+                    &entry.blockhash,
+                    entry.lamports_per_signature,
+                )
+            })
+            .collect()
+    }
+
+    /// Ends the current block and starts the next one, moving the sysvars along together
+    /// as a validator would.
+    ///
+    /// The current slot and blockhash are added to [`SlotHashes`] and [`SlotHistory`], and a new
+    /// blockhash is derived from the last one. The [`Clock`] moves to the next slot, with its
+    /// epoch taken from the [`EpochSchedule`] sysvar and its timestamp advanced by the slot
+    /// duration set with [`with_slot_duration`](LiteSVM::with_slot_duration).
+    ///
+    /// Starting a new epoch updates [`StakeHistory`] and begins paying the epoch's rewards,
+    /// as [`warp_to_epoch`](LiteSVM::warp_to_epoch) does, and each block after that pays the
+    /// next part of the stake rewards.
+    ///
+    /// The sysvars, the rewards paid and the metadata are written to the store in a single write.
+    /// If the store fails to read the accounts or to write them, nothing changes and the store
+    /// error is returned.
+    pub fn produce_block(&mut self) -> Result<(), LiteSVMError> {
+        self.write_with_metadata(Self::end_block)?;
+        #[cfg(feature = "serde")]
+        self.recorder.record(|| session::SessionEntry::ProduceBlock);
+        Ok(())
+    }
+
+    /// Does the work of [`produce_block`](LiteSVM::produce_block).
+    fn end_block(&mut self) -> Result<(), LiteSVMError> {
+        let mut clock = self.get_sysvar::<Clock>();
+        let mut slot_hashes = self.get_sysvar::<SlotHashes>();
+        slot_hashes.add(clock.slot, self.latest_blockhash());
+        self.write_sysvar(&slot_hashes)?;
+        let mut slot_history = self.get_sysvar::<SlotHistory>();
+        slot_history.add(clock.slot);
+        self.write_sysvar(&slot_history)?;
+        self.advance_blockhash()?;
+
+        // timestamps are in whole seconds, so carry the rest over to the next block
+        let elapsed = self.unix_timestamp_remainder + self.slot_duration;
+        self.unix_timestamp_remainder = Duration::from_nanos(elapsed.subsec_nanos().into());
+        clock.unix_timestamp = clock
+            .unix_timestamp
+            .saturating_add(elapsed.as_secs() as i64);
+        clock.slot += 1;
+        let epoch_schedule = self.get_sysvar::<EpochSchedule>();
+        let parent_epoch = clock.epoch;
+        let epoch = epoch_schedule.get_epoch(clock.slot);
+        if epoch != clock.epoch {
+            clock.epoch = epoch;
+            clock.epoch_start_timestamp = clock.unix_timestamp;
+        }
+        clock.leader_schedule_epoch = epoch_schedule.get_leader_schedule_epoch(clock.slot);
+        self.write_sysvar(&clock)?;
+        self.process_new_epochs(parent_epoch, epoch)?;
+        if self
+            .epoch_rewards
+            .as_ref()
+            .is_some_and(|rewards| rewards.next_block_height <= self.get_block_height())
+        {
+            self.distribute_stake_rewards()?;
+        }
+        Ok(())
+    }
+
+    /// Warps the clock to the first slot of `epoch`, crossing each epoch boundary on the way
+    /// as a cluster would.
+    ///
+    /// At each boundary, the effective, activating and deactivating stake of every delegated
+    /// stake account is added to [`StakeHistory`] for the epoch that ended, so stakes warm up
+    /// and cool down from one epoch to the next. If [`with_inflation`](LiteSVM::with_inflation)
+    /// is set, the rewards for that epoch are calculated too, and the [`EpochRewards`] sysvar
+    /// stays active until the blocks produced after the warp have paid them all. Rewards still
+    /// being paid when another boundary is crossed are paid right away.
+    ///
+    /// As on a cluster, a stake warms up by a share of the stake that was already effective,
+    /// so a stake delegated alone never becomes fully effective. To give it a cluster to warm
+    /// up in, add a stake account whose delegation has `activation_epoch` set to `u64::MAX`,
+    /// which counts as effective from the start like the stake of a genesis validator.
+    ///
+    /// Warping to the current epoch or an earlier one only moves the clock.
+    ///
+    /// As with [`produce_block`](LiteSVM::produce_block), everything is written to the store in
+    /// a single write, and nothing changes if that fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use litesvm::LiteSVM;
+    /// use solana_clock::Clock;
+    /// use solana_stake_interface::stake_history::StakeHistory;
+    ///
+    /// let mut svm = LiteSVM::new();
+    /// svm.warp_to_epoch(3).unwrap();
+    ///
+    /// assert_eq!(svm.get_sysvar::<Clock>().epoch, 3);
+    /// assert!(svm.get_sysvar::<StakeHistory>().get(2).is_some());
+    /// ```
+    pub fn warp_to_epoch(&mut self, epoch: Epoch) -> Result<(), LiteSVMError> {
+        self.write_with_metadata(|svm| {
+            let epoch_schedule = svm.get_sysvar::<EpochSchedule>();
+            let mut clock = svm.get_sysvar::<Clock>();
+            let parent_epoch = clock.epoch;
+            clock.slot = epoch_schedule.get_first_slot_in_epoch(epoch);
+            if epoch != clock.epoch {
+                clock.epoch = epoch;
+                clock.epoch_start_timestamp = clock.unix_timestamp;
+            }
+            clock.leader_schedule_epoch = epoch_schedule.get_leader_schedule_epoch(clock.slot);
+            svm.write_sysvar(&clock)?;
+            svm.process_new_epochs(parent_epoch, epoch)
+        })?;
+        #[cfg(feature = "serde")]
+        self.recorder
+            .record(|| session::SessionEntry::WarpToEpoch { epoch });
+        Ok(())
+    }
+
+    /// Returns the stake delegated to `vote_pubkey` in the current epoch, as programs see it
+    /// through the `sol_get_epoch_stake` syscall.
+    ///
+    /// The stakes are taken from the delegated stake accounts at each epoch boundary crossed
+    /// by [`warp_to_epoch`](LiteSVM::warp_to_epoch) or [`produce_block`](LiteSVM::produce_block),
+    /// and can be changed with [`set_epoch_stake`](LiteSVM::set_epoch_stake).
+    pub fn get_epoch_stake(&self, vote_pubkey: &Pubkey) -> u64 {
+        self.epoch_stakes.get(vote_pubkey)
+    }
+
+    /// Returns the total stake of every vote account in the current epoch.
+    pub fn get_total_epoch_stake(&self) -> u64 {
+        self.epoch_stakes.total()
+    }
+
+    /// Sets the stake delegated to `vote_pubkey` in the current epoch, without the stake
+    /// accounts to back it. The total epoch stake changes with it.
+    ///
+    /// The next epoch boundary takes the stakes from the stake accounts again, replacing it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use litesvm::LiteSVM;
+    /// use solana_pubkey::Pubkey;
+    ///
+    /// let mut svm = LiteSVM::new();
+    /// let vote_pubkey = Pubkey::new_unique();
+    /// svm.set_epoch_stake(vote_pubkey, 1_000_000);
+    ///
+    /// assert_eq!(svm.get_epoch_stake(&vote_pubkey), 1_000_000);
+    /// assert_eq!(svm.get_total_epoch_stake(), 1_000_000);
+    /// ```
+    pub fn set_epoch_stake(&mut self, vote_pubkey: Pubkey, stake: u64) {
+        self.epoch_stakes.set(vote_pubkey, stake);
+        #[cfg(feature = "serde")]
+        self.recorder
+            .record(|| session::SessionEntry::SetEpochStake { vote_pubkey, stake });
+    }
+
+    /// Crosses each epoch boundary from `parent_epoch` to `epoch`.
+    fn process_new_epochs(
+        &mut self,
+        parent_epoch: Epoch,
+        epoch: Epoch,
+    ) -> Result<(), LiteSVMError> {
+        for ended_epoch in parent_epoch..epoch {
+            while self.epoch_rewards.is_some() {
+                self.distribute_stake_rewards()?;
+            }
+            self.process_new_epoch(ended_epoch)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the stake of `ended_epoch` to [`StakeHistory`], takes the stake of each vote account
+    /// for the next epoch and calculates the rewards of `ended_epoch`, as the bank does at the
+    /// first block of the next epoch.
+    ///
+    /// The rewards depend on the capitalization, summed over every account, so an account the
+    /// store fails to read fails the whole boundary rather than leaving the rewards short.
+    fn process_new_epoch(&mut self, ended_epoch: Epoch) -> Result<(), LiteSVMError> {
+        let epoch_schedule = self.get_sysvar::<EpochSchedule>();
+        let new_rate_activation_epoch = self
+            .feature_set
+            .new_warmup_cooldown_rate_epoch(&epoch_schedule);
+        let stake_accounts = self
+            .accounts
+            .get_program_accounts(&solana_sdk_ids::stake::id())?;
+        let stakes = stakes::stake_accounts(stake_accounts);
+        let mut stake_history = self.get_sysvar::<StakeHistory>();
+        let entry = stakes::stake_history_entry(
+            &stakes,
+            ended_epoch,
+            &stake_history,
+            new_rate_activation_epoch,
+        );
+        stake_history.add(ended_epoch, entry);
+        self.write_sysvar(&stake_history)?;
+
+        let vote_accounts: HashMap<_, _> = stakes
+            .iter()
+            .map(|account| account.stake.delegation.voter_pubkey)
+            .unique()
+            .filter_map(|pubkey| {
+                let account = self.accounts.get_account(&pubkey)?;
+                if account.owner() != &solana_sdk_ids::vote::id() {
+                    return None;
+                }
+                let vote_state = VoteState::deserialize(account.data()).ok()?;
+                Some((pubkey, vote_state))
+            })
+            .collect();
+        self.epoch_stakes = EpochStakes::new(
+            &stakes,
+            &vote_accounts,
+            ended_epoch + 1,
+            &stake_history,
+            new_rate_activation_epoch,
+        );
+
+        let Some(inflation) = self.inflation else {
+            return Ok(());
+        };
+        let clock = self.get_sysvar::<Clock>();
+        let capitalization = self
+            .accounts
+            .iter_accounts()?
+            .map(|item| item.map(|(_, account)| account.lamports()))
+            .sum::<Result<u64, _>>()?;
+        let validator_rate = inflation.validator(clock.slot as f64 / stakes::SLOTS_PER_YEAR);
+        let epoch_duration_in_years =
+            epoch_schedule.get_slots_in_epoch(ended_epoch) as f64 / stakes::SLOTS_PER_YEAR;
+        let validator_rewards =
+            (validator_rate * capitalization as f64 * epoch_duration_in_years) as u64;
+
+        let minimum_delegation = if self
+            .feature_set
+            .is_active(&agave_feature_set::stake_minimum_delegation_for_rewards::id())
+        {
+            LAMPORTS_PER_SOL
+        } else {
+            0
+        };
+        let calculation = EpochRewardsCalculation::new(
+            ended_epoch,
+            validator_rewards,
+            &stakes,
+            &vote_accounts,
+            &stake_history,
+            new_rate_activation_epoch,
+            minimum_delegation,
+        );
+
+        for (pubkey, lamports) in &calculation.vote_rewards {
+            let Some(mut account) = self.accounts.get_account(pubkey) else {
+                continue;
+            };
+            account.set_lamports(account.lamports().saturating_add(*lamports));
+            self.accounts.add_account_no_checks(*pubkey, account)?;
+        }
+        let num_partitions = stakes::num_partitions(
+            calculation.stake_rewards.len(),
+            clock.epoch,
+            &epoch_schedule,
+        );
+        let parent_blockhash = self.latest_blockhash();
+        let distribution_starting_block_height = self.get_block_height() + 1;
+        self.write_sysvar(&EpochRewards {
+            distribution_starting_block_height,
+            num_partitions,
+            parent_blockhash,
+            total_points: calculation.total_points,
+            total_rewards: calculation.total_rewards,
+            distributed_rewards: calculation.total_vote_rewards(),
+            active: true,
+        })?;
+        self.epoch_rewards = Some(RewardsDistribution::new(
+            calculation.stake_rewards,
+            num_partitions,
+            &parent_blockhash,
+            distribution_starting_block_height,
+        ));
+        Ok(())
+    }
+
+    /// Pays the next partition of the stake rewards, ending the rewards period after the last.
+    fn distribute_stake_rewards(&mut self) -> Result<(), LiteSVMError> {
+        let Some(distribution) = &mut self.epoch_rewards else {
+            return Ok(());
+        };
+        let partition = distribution.partitions.pop_front().unwrap_or_default();
+        distribution.next_block_height += 1;
+        let done = distribution.partitions.is_empty();
+
+        let mut distributed = 0;
+        for reward in &partition {
+            distributed += reward.lamports;
+            let Some(mut account) = self.accounts.get_account(&reward.pubkey) else {
+                continue;
+            };
+            if !stakes::apply_stake_reward(&mut account, reward) {
+                continue;
+            }
+            self.accounts
+                .add_account_no_checks(reward.pubkey, account)?;
+        }
+        let mut epoch_rewards = self.get_sysvar::<EpochRewards>();
+        epoch_rewards.distribute(distributed);
+        if done {
+            epoch_rewards.active = false;
+            self.epoch_rewards = None;
+        }
+        self.write_sysvar(&epoch_rewards)
     }
 
     /// Warps the clock to the specified slot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Clock` sysvar can't be written to the store.
+    /// Use [`try_warp_to_slot`](LiteSVM::try_warp_to_slot) to handle the error instead.
     pub fn warp_to_slot(&mut self, slot: u64) {
+        self.try_warp_to_slot(slot)
+            .expect("Failed to write the clock to the store");
+    }
+
+    /// Like [`warp_to_slot`](LiteSVM::warp_to_slot), but returns the store error instead of
+    /// panicking.
+    pub fn try_warp_to_slot(&mut self, slot: u64) -> Result<(), LiteSVMError> {
         let mut clock = self.get_sysvar::<Clock>();
         clock.slot = slot;
-        self.set_sysvar(&clock);
+        self.write_with_metadata(|svm| svm.write_sysvar(&clock))?;
+        #[cfg(feature = "serde")]
+        self.recorder
+            .record(|| session::SessionEntry::WarpToSlot { slot });
+        Ok(())
     }
 
     /// Gets the current compute budget.
@@ -1490,18 +3224,18 @@ impl LiteSVM {
         tx: &SanitizedTransaction,
     ) -> solana_transaction_error::TransactionResult<()> {
         let recent_blockhash = tx.message().recent_blockhash();
-        if recent_blockhash == &self.latest_blockhash
+        if self.is_blockhash_valid(recent_blockhash)
             || self.check_transaction_for_nonce(
                 tx,
-                &DurableNonce::from_blockhash(&self.latest_blockhash),
+                &DurableNonce::from_blockhash(&self.latest_blockhash()),
             )
         {
             Ok(())
         } else {
             log::error!(
-                "Blockhash {} not found. Expected blockhash {}",
+                "Blockhash {} not found or expired. Latest blockhash is {}",
                 recent_blockhash,
-                self.latest_blockhash
+                self.latest_blockhash()
             );
             Err(TransactionError::BlockhashNotFound)
         }
@@ -1542,7 +3276,7 @@ struct CheckAndProcessTransactionSuccessCore {
 
 struct CheckAndProcessTransactionSuccess {
     core: CheckAndProcessTransactionSuccessCore,
-    fee: u64,
+    fee_details: FeeDetails,
     payer_key: Option<Pubkey>,
 }
 
@@ -1561,6 +3295,7 @@ fn execution_result_if_context(
         inner_instructions,
         compute_units_consumed,
         return_data,
+        fees: FeeBreakdown::default(),
         included: true,
     }
 }
@@ -1692,6 +3427,16 @@ where
     }
 }
 
-struct LiteSVMCallback {}
+struct LiteSVMCallback<'a> {
+    epoch_stakes: &'a EpochStakes,
+}
+
+impl InvokeContextCallback for LiteSVMCallback<'_> {
+    fn get_epoch_stake(&self) -> u64 {
+        self.epoch_stakes.total()
+    }
 
-impl InvokeContextCallback for LiteSVMCallback {}
+    fn get_epoch_stake_for_vote_account(&self, vote_address: &Pubkey) -> u64 {
+        self.epoch_stakes.get(vote_address)
+    }
+}
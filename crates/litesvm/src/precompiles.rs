@@ -1,11 +1,11 @@
 use {
-    crate::LiteSVM,
+    crate::{error::LiteSVMError, LiteSVM},
     agave_precompiles::get_precompiles,
     solana_account::{AccountSharedData, WritableAccount},
     solana_sdk_ids::native_loader,
 };
 
-pub(crate) fn load_precompiles(svm: &mut LiteSVM) {
+pub(crate) fn load_precompiles(svm: &mut LiteSVM) -> Result<(), LiteSVMError> {
     let mut account = AccountSharedData::default();
     account.set_owner(native_loader::id());
     account.set_lamports(1);
@@ -16,8 +16,8 @@ pub(crate) fn load_precompiles(svm: &mut LiteSVM) {
             .feature
             .map_or(true, |feature_id| svm.feature_set.is_active(&feature_id))
         {
-            svm.set_account(precompile.program_id, account.clone().into())
-                .unwrap();
+            svm.set_account(precompile.program_id, account.clone().into())?;
         }
     }
+    Ok(())
 }
@@ -0,0 +1,176 @@
+use {
+    crate::{
+        account_json::KeyedAccountJson,
+        error::LiteSVMError,
+        types::{ReplayMismatch, ReplayReport, TransactionResult},
+    },
+    base64::{prelude::BASE64_STANDARD, Engine},
+    serde::Deserialize,
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+    solana_transaction::versioned::VersionedTransaction,
+    solana_transaction_error::TransactionError,
+    std::path::Path,
+};
+
+/// A transaction as returned by `getTransaction`, along with the accounts it touched
+/// as they were before it ran.
+#[derive(Deserialize)]
+struct ReplayFile {
+    transaction: RpcResponse,
+    accounts: Vec<KeyedAccountJson>,
+}
+
+/// Either the whole JSON-RPC response or just its `result`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RpcResponse {
+    Response { result: RecordedTransaction },
+    Result(RecordedTransaction),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordedTransaction {
+    slot: u64,
+    #[serde(default)]
+    block_time: Option<i64>,
+    /// A `[transaction, encoding]` pair. Only the base64 encoding is supported.
+    transaction: (String, String),
+    meta: RecordedMeta,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordedMeta {
+    err: Option<TransactionError>,
+    post_balances: Vec<u64>,
+    #[serde(default)]
+    log_messages: Option<Vec<String>>,
+    #[serde(default)]
+    compute_units_consumed: Option<u64>,
+    #[serde(default)]
+    loaded_addresses: Option<RecordedLoadedAddresses>,
+}
+
+#[derive(Deserialize)]
+struct RecordedLoadedAddresses {
+    writable: Vec<String>,
+    readonly: Vec<String>,
+}
+
+/// A recorded transaction ready to run.
+pub(crate) struct Replay {
+    pub(crate) slot: u64,
+    pub(crate) block_time: Option<i64>,
+    pub(crate) transaction: VersionedTransaction,
+    pub(crate) accounts: Vec<(Pubkey, Account)>,
+    /// The account keys in the order of the recorded balances, the loaded addresses included.
+    account_keys: Vec<Pubkey>,
+    err: Option<TransactionError>,
+    post_balances: Vec<u64>,
+    logs: Option<Vec<String>>,
+    compute_units_consumed: Option<u64>,
+}
+
+impl Replay {
+    pub(crate) fn read(path: &Path) -> Result<Self, LiteSVMError> {
+        let invalid = |reason: String| LiteSVMError::InvalidReplayFile {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let ReplayFile {
+            transaction,
+            accounts,
+        } = serde_json::from_reader(file).map_err(|e| invalid(e.to_string()))?;
+        let (RpcResponse::Response { result: recorded } | RpcResponse::Result(recorded)) =
+            transaction;
+
+        let (encoded, encoding) = &recorded.transaction;
+        if encoding != "base64" {
+            return Err(invalid(format!(
+                "unsupported transaction encoding: {encoding}"
+            )));
+        }
+        let bytes = BASE64_STANDARD
+            .decode(encoded)
+            .map_err(|e| invalid(format!("invalid base64 transaction: {e}")))?;
+        let transaction: VersionedTransaction = bincode::deserialize(&bytes)
+            .map_err(|e| invalid(format!("invalid transaction: {e}")))?;
+
+        let mut account_keys = transaction.message.static_account_keys().to_vec();
+        if let Some(loaded) = &recorded.meta.loaded_addresses {
+            for address in loaded.writable.iter().chain(&loaded.readonly) {
+                let pubkey = address
+                    .parse()
+                    .map_err(|e| invalid(format!("invalid loaded address {address}: {e}")))?;
+                account_keys.push(pubkey);
+            }
+        }
+
+        Ok(Self {
+            slot: recorded.slot,
+            block_time: recorded.block_time,
+            transaction,
+            accounts: accounts
+                .into_iter()
+                .map(|keyed| (keyed.pubkey, keyed.account.into()))
+                .collect(),
+            account_keys,
+            err: recorded.meta.err,
+            post_balances: recorded.meta.post_balances,
+            logs: recorded.meta.log_messages,
+            compute_units_consumed: recorded.meta.compute_units_consumed,
+        })
+    }
+
+    /// Compares the result of running the transaction, and the balances after it,
+    /// with what was recorded.
+    pub(crate) fn compare(
+        self,
+        result: TransactionResult,
+        get_balance: impl Fn(&Pubkey) -> u64,
+    ) -> ReplayReport {
+        let mut mismatches = Vec::new();
+        let (err, meta) = match &result {
+            Ok(meta) => (None, meta),
+            Err(failed) => (Some(failed.err.clone()), &failed.meta),
+        };
+        if err != self.err {
+            mismatches.push(ReplayMismatch::Result {
+                expected: self.err,
+                actual: err,
+            });
+        }
+        if let Some(logs) = self.logs {
+            let len = logs.len().max(meta.logs.len());
+            if let Some(index) = (0..len).find(|&i| logs.get(i) != meta.logs.get(i)) {
+                mismatches.push(ReplayMismatch::Log {
+                    index,
+                    expected: logs.get(index).cloned(),
+                    actual: meta.logs.get(index).cloned(),
+                });
+            }
+        }
+        if let Some(expected) = self.compute_units_consumed {
+            if expected != meta.compute_units_consumed {
+                mismatches.push(ReplayMismatch::ComputeUnits {
+                    expected,
+                    actual: meta.compute_units_consumed,
+                });
+            }
+        }
+        for (pubkey, expected) in self.account_keys.into_iter().zip(self.post_balances) {
+            let actual = get_balance(&pubkey);
+            if expected != actual {
+                mismatches.push(ReplayMismatch::PostBalance {
+                    pubkey,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        ReplayReport { result, mismatches }
+    }
+}
@@ -0,0 +1,184 @@
+use {
+    crate::{account_json::KeyedAccountJson, error::LiteSVMError, types::TransactionResult},
+    log::error,
+    serde::{Deserialize, Serialize},
+    solana_inflation::Inflation,
+    solana_pubkey::Pubkey,
+    solana_transaction::versioned::VersionedTransaction,
+    std::{
+        fs::File,
+        io::{BufRead, BufReader, Write},
+        path::Path,
+        time::Duration,
+    },
+};
+
+/// A line of a session recording: one mutating call, in the order they were made.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "call", rename_all = "snake_case")]
+pub(crate) enum SessionEntry {
+    /// Always the first line, with the encoded [`SvmMetadata`](crate::storage::SvmMetadata)
+    /// and the slot duration of the instance when recording started.
+    Start {
+        #[serde(with = "base64_bytes")]
+        metadata: Vec<u8>,
+        slot_duration: Duration,
+    },
+    SetAccount(KeyedAccountJson),
+    RemoveAccount {
+        #[serde(with = "crate::utils::serde_with_str")]
+        pubkey: Pubkey,
+    },
+    Airdrop {
+        #[serde(with = "crate::utils::serde_with_str")]
+        pubkey: Pubkey,
+        lamports: u64,
+    },
+    AddProgram {
+        #[serde(with = "crate::utils::serde_with_str")]
+        program_id: Pubkey,
+        #[serde(with = "base64_bytes")]
+        program: Vec<u8>,
+    },
+    SetSysvar {
+        #[serde(with = "crate::utils::serde_with_str")]
+        id: Pubkey,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+    },
+    WarpToSlot {
+        slot: u64,
+    },
+    SetEpochStake {
+        #[serde(with = "crate::utils::serde_with_str")]
+        vote_pubkey: Pubkey,
+        stake: u64,
+    },
+    ExpireBlockhash,
+    ProduceBlock,
+    WarpToEpoch {
+        epoch: u64,
+    },
+    SetSlotDuration {
+        slot_duration: Duration,
+    },
+    SetInflation {
+        inflation: Inflation,
+    },
+    SendTransaction {
+        /// The bincode encoded transaction.
+        #[serde(with = "base64_bytes")]
+        transaction: Vec<u8>,
+        result: TransactionResult,
+    },
+}
+
+impl SessionEntry {
+    pub(crate) fn send_transaction(tx: &VersionedTransaction, result: &TransactionResult) -> Self {
+        Self::SendTransaction {
+            transaction: bincode::serialize(tx).expect("transactions are always serializable"),
+            result: result.clone(),
+        }
+    }
+}
+
+/// Appends every mutating call of a `LiteSVM` instance to a file, one JSON line each,
+/// written as soon as the call returns so a panicking test still leaves a usable recording.
+///
+/// Clones don't inherit the recording.
+#[derive(Default)]
+pub(crate) struct SessionRecorder {
+    file: Option<File>,
+}
+
+impl Clone for SessionRecorder {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl SessionRecorder {
+    /// Starts a new recording at `path`, replacing any file already there.
+    pub(crate) fn start(
+        &mut self,
+        path: &Path,
+        metadata: Vec<u8>,
+        slot_duration: Duration,
+    ) -> Result<(), LiteSVMError> {
+        self.file = Some(File::create(path)?);
+        self.record(|| SessionEntry::Start {
+            metadata,
+            slot_duration,
+        });
+        Ok(())
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Records the entry built by `entry`, which is only called while recording.
+    pub(crate) fn record(&mut self, entry: impl FnOnce() -> SessionEntry) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        let res = serde_json::to_string(&entry())
+            .map_err(std::io::Error::from)
+            .and_then(|mut line| {
+                line.push('\n');
+                file.write_all(line.as_bytes())
+            });
+        if let Err(e) = res {
+            error!("Failed to record LiteSVM session: {e}");
+        }
+    }
+}
+
+/// Reads a session recording, returning each entry with its line number.
+pub(crate) fn read_session(path: &Path) -> Result<Vec<(usize, SessionEntry)>, LiteSVMError> {
+    let invalid = |line: usize, reason: String| LiteSVMError::InvalidSession {
+        path: path.to_path_buf(),
+        line,
+        reason,
+    };
+    let mut entries = Vec::new();
+    for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line_number = index + 1;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: SessionEntry =
+            serde_json::from_str(&line).map_err(|e| invalid(line_number, e.to_string()))?;
+        let is_start = matches!(entry, SessionEntry::Start { .. });
+        if is_start != entries.is_empty() {
+            return Err(invalid(
+                line_number,
+                "a recording starts with one `start` line".to_string(),
+            ));
+        }
+        entries.push((line_number, entry));
+    }
+    if entries.is_empty() {
+        return Err(invalid(1, "the recording is empty".to_string()));
+    }
+    Ok(entries)
+}
+
+mod base64_bytes {
+    use {
+        base64::{prelude::BASE64_STANDARD, Engine},
+        serde::{de, Deserialize, Deserializer, Serializer},
+    };
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64_STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        BASE64_STANDARD
+            .decode(encoded)
+            .map_err(|e| de::Error::custom(format!("invalid base64: {e}")))
+    }
+}
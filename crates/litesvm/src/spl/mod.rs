@@ -1,24 +1,62 @@
-use {crate::LiteSVM, solana_pubkey::pubkey};
+use {
+    crate::{error::LiteSVMError, LiteSVM},
+    solana_account::{AccountSharedData, ReadableAccount},
+    solana_pubkey::{pubkey, Pubkey},
+};
 
-pub fn load_spl_programs(svm: &mut LiteSVM) {
-    svm.add_program(
-        pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+pub(crate) const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub(crate) const TOKEN_2022_PROGRAM_ID: Pubkey =
+    pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Size of a token account without extensions.
+const TOKEN_ACCOUNT_LEN: usize = 165;
+/// Size of a multisig, which Token-2022 never extends.
+const MULTISIG_LEN: usize = 355;
+/// `AccountType::Account`, stored right after the base account by Token-2022 accounts with extensions.
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+pub fn load_spl_programs(svm: &mut LiteSVM) -> Result<(), LiteSVMError> {
+    svm.try_add_program(
+        TOKEN_PROGRAM_ID,
         include_bytes!("programs/spl_token-3.5.0.so"),
-    );
-    svm.add_program(
-        pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb"),
+    )?;
+    svm.try_add_program(
+        TOKEN_2022_PROGRAM_ID,
         include_bytes!("programs/spl_token_2022-5.0.2.so"),
-    );
-    svm.add_program(
+    )?;
+    svm.try_add_program(
         pubkey!("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo"),
         include_bytes!("programs/spl_memo-1.0.0.so"),
-    );
-    svm.add_program(
+    )?;
+    svm.try_add_program(
         pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr"),
         include_bytes!("programs/spl_memo-3.0.0.so"),
-    );
-    svm.add_program(
+    )?;
+    svm.try_add_program(
         pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL"),
         include_bytes!("programs/spl_associated_token_account-1.1.1.so"),
-    );
+    )?;
+    Ok(())
+}
+
+/// Returns the mint and the owner of an SPL Token or Token-2022 account,
+/// or `None` if the account isn't a token account.
+pub(crate) fn token_account_keys(account: &AccountSharedData) -> Option<(Pubkey, Pubkey)> {
+    let data = account.data();
+    let is_token_account = if account.owner() == &TOKEN_PROGRAM_ID {
+        data.len() == TOKEN_ACCOUNT_LEN
+    } else if account.owner() == &TOKEN_2022_PROGRAM_ID {
+        data.len() == TOKEN_ACCOUNT_LEN
+            || (data.len() > TOKEN_ACCOUNT_LEN
+                && data.len() != MULTISIG_LEN
+                && data[TOKEN_ACCOUNT_LEN] == ACCOUNT_TYPE_ACCOUNT)
+    } else {
+        false
+    };
+    if !is_token_account {
+        return None;
+    }
+    let mint = Pubkey::try_from(&data[..32]).ok()?;
+    let owner = Pubkey::try_from(&data[32..64]).ok()?;
+    Some((mint, owner))
 }
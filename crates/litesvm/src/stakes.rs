@@ -0,0 +1,351 @@
+use {
+    solana_account::{AccountSharedData, ReadableAccount, WritableAccount},
+    solana_clock::{Epoch, DEFAULT_TICKS_PER_SECOND, DEFAULT_TICKS_PER_SLOT},
+    solana_epoch_rewards_hasher::EpochRewardsHasher,
+    solana_epoch_schedule::EpochSchedule,
+    solana_hash::Hash,
+    solana_pubkey::Pubkey,
+    solana_stake_interface::{
+        stake_history::{StakeHistory, StakeHistoryEntry},
+        state::{Stake, StakeStateV2},
+    },
+    solana_vote_program::vote_state::VoteState,
+    std::collections::{HashMap, VecDeque},
+};
+
+/// Slots in a year at the default slot time, as in the genesis config of a cluster.
+pub(crate) const SLOTS_PER_YEAR: f64 =
+    365.242_199 * 24.0 * 60.0 * 60.0 * DEFAULT_TICKS_PER_SECOND as f64
+        / DEFAULT_TICKS_PER_SLOT as f64;
+
+/// How many stake accounts get their rewards in each block of the distribution.
+const STAKE_ACCOUNT_STORES_PER_BLOCK: usize = 4096;
+
+/// A delegated stake account.
+pub(crate) struct StakeAccount {
+    pub(crate) pubkey: Pubkey,
+    pub(crate) stake: Stake,
+}
+
+/// Returns the delegated stake accounts among `accounts`, skipping the others.
+pub(crate) fn stake_accounts(accounts: Vec<(Pubkey, AccountSharedData)>) -> Vec<StakeAccount> {
+    accounts
+        .into_iter()
+        .filter_map(|(pubkey, account)| match account.deserialize_data() {
+            Ok(StakeStateV2::Stake(_, stake, _)) => Some(StakeAccount { pubkey, stake }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Sums the effective, activating and deactivating stake of every delegation at `epoch`.
+pub(crate) fn stake_history_entry(
+    stakes: &[StakeAccount],
+    epoch: Epoch,
+    stake_history: &StakeHistory,
+    new_rate_activation_epoch: Option<Epoch>,
+) -> StakeHistoryEntry {
+    stakes
+        .iter()
+        .map(|account| {
+            account.stake.delegation.stake_activating_and_deactivating(
+                epoch,
+                stake_history,
+                new_rate_activation_epoch,
+            )
+        })
+        .fold(StakeHistoryEntry::default(), |sum, entry| sum + entry)
+}
+
+/// The stake delegated to each vote account in the current epoch, as read by the
+/// `sol_get_epoch_stake` syscall.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EpochStakes(HashMap<Pubkey, u64>);
+
+impl EpochStakes {
+    /// Sums the effective stake at `epoch` delegated to each of `vote_accounts`.
+    pub(crate) fn new(
+        stakes: &[StakeAccount],
+        vote_accounts: &HashMap<Pubkey, VoteState>,
+        epoch: Epoch,
+        stake_history: &StakeHistory,
+        new_rate_activation_epoch: Option<Epoch>,
+    ) -> Self {
+        let mut epoch_stakes = HashMap::new();
+        for account in stakes {
+            let delegation = &account.stake.delegation;
+            if !vote_accounts.contains_key(&delegation.voter_pubkey) {
+                continue;
+            }
+            let stake = delegation.stake(epoch, stake_history, new_rate_activation_epoch);
+            if stake > 0 {
+                *epoch_stakes.entry(delegation.voter_pubkey).or_default() += stake;
+            }
+        }
+        Self(epoch_stakes)
+    }
+
+    pub(crate) fn get(&self, vote_pubkey: &Pubkey) -> u64 {
+        self.0.get(vote_pubkey).copied().unwrap_or_default()
+    }
+
+    pub(crate) fn set(&mut self, vote_pubkey: Pubkey, stake: u64) {
+        if stake == 0 {
+            self.0.remove(&vote_pubkey);
+        } else {
+            self.0.insert(vote_pubkey, stake);
+        }
+    }
+
+    pub(crate) fn total(&self) -> u64 {
+        self.0.values().sum()
+    }
+}
+
+/// The updated stake of a stake account, and the lamports it earned.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StakeReward {
+    pub(crate) pubkey: Pubkey,
+    pub(crate) stake: Stake,
+    pub(crate) lamports: u64,
+}
+
+/// The rewards of an epoch, computed as the bank does at the first block of the next one.
+pub(crate) struct EpochRewardsCalculation {
+    pub(crate) total_points: u128,
+    pub(crate) total_rewards: u64,
+    /// Paid right away, unlike the stake rewards.
+    pub(crate) vote_rewards: HashMap<Pubkey, u64>,
+    pub(crate) stake_rewards: Vec<StakeReward>,
+}
+
+impl EpochRewardsCalculation {
+    /// Splits `validator_rewards` between the stakes of `rewarded_epoch` by the vote credits
+    /// they earned, and between each stake and its vote account by the vote account's commission.
+    ///
+    /// Stakes smaller than `minimum_delegation`, or delegated to an account that isn't
+    /// a vote account, earn nothing.
+    pub(crate) fn new(
+        rewarded_epoch: Epoch,
+        validator_rewards: u64,
+        stakes: &[StakeAccount],
+        vote_accounts: &HashMap<Pubkey, VoteState>,
+        stake_history: &StakeHistory,
+        new_rate_activation_epoch: Option<Epoch>,
+        minimum_delegation: u64,
+    ) -> Self {
+        let rewarded: Vec<_> = stakes
+            .iter()
+            .filter(|account| account.stake.delegation.stake >= minimum_delegation)
+            .filter_map(|account| {
+                let vote_state = vote_accounts.get(&account.stake.delegation.voter_pubkey)?;
+                let points = stake_points(
+                    &account.stake,
+                    vote_state,
+                    stake_history,
+                    new_rate_activation_epoch,
+                );
+                Some((account, vote_state, points))
+            })
+            .collect();
+        let total_points: u128 = rewarded.iter().map(|(_, _, points)| points.points).sum();
+        // without points there's no one to pay
+        let total_rewards = if total_points == 0 {
+            0
+        } else {
+            validator_rewards
+        };
+
+        let mut calculation = Self {
+            total_points,
+            total_rewards,
+            vote_rewards: HashMap::new(),
+            stake_rewards: Vec::new(),
+        };
+        for (account, vote_state, points) in rewarded {
+            let Some((voter_rewards, staker_rewards)) = calculation.stake_rewards(
+                rewarded_epoch,
+                &account.stake,
+                vote_state.commission,
+                &points,
+            ) else {
+                continue;
+            };
+            let mut stake = account.stake;
+            stake.credits_observed = points.new_credits_observed;
+            stake.delegation.stake += staker_rewards;
+            if voter_rewards > 0 {
+                *calculation
+                    .vote_rewards
+                    .entry(stake.delegation.voter_pubkey)
+                    .or_default() += voter_rewards;
+            }
+            calculation.stake_rewards.push(StakeReward {
+                pubkey: account.pubkey,
+                stake,
+                lamports: staker_rewards,
+            });
+        }
+        calculation
+    }
+
+    /// Returns the voter's and the staker's share of the rewards of `stake`, or `None` if it
+    /// isn't rewarded and keeps its credits for next time.
+    fn stake_rewards(
+        &self,
+        rewarded_epoch: Epoch,
+        stake: &Stake,
+        commission: u8,
+        points: &StakePoints,
+    ) -> Option<(u64, u64)> {
+        // stakes activated in the rewarded epoch only catch up on their credits
+        if points.force_credits_update
+            || self.total_rewards == 0
+            || stake.delegation.activation_epoch == rewarded_epoch
+        {
+            return Some((0, 0));
+        }
+        if points.points == 0 {
+            return None;
+        }
+        let rewards = (points.points * u128::from(self.total_rewards) / self.total_points) as u64;
+        if rewards == 0 {
+            return None;
+        }
+        let (voter_rewards, staker_rewards, is_split) = commission_split(commission, rewards);
+        if (voter_rewards == 0 || staker_rewards == 0) && is_split {
+            return None;
+        }
+        Some((voter_rewards, staker_rewards))
+    }
+
+    pub(crate) fn total_vote_rewards(&self) -> u64 {
+        self.vote_rewards.values().sum()
+    }
+}
+
+struct StakePoints {
+    points: u128,
+    new_credits_observed: u64,
+    /// Set when the vote account has fewer credits than the stake observed, which happens when
+    /// it was closed and recreated, so the stake starts over from the new credits.
+    force_credits_update: bool,
+}
+
+/// Returns the points `stake` earned from the credits of its vote account since it was last
+/// rewarded: its effective stake times the credits earned, summed over each epoch.
+fn stake_points(
+    stake: &Stake,
+    vote_state: &VoteState,
+    stake_history: &StakeHistory,
+    new_rate_activation_epoch: Option<Epoch>,
+) -> StakePoints {
+    let credits_in_stake = stake.credits_observed;
+    let credits_in_vote = vote_state.credits();
+    if credits_in_vote <= credits_in_stake {
+        return StakePoints {
+            points: 0,
+            new_credits_observed: credits_in_vote,
+            force_credits_update: credits_in_vote < credits_in_stake,
+        };
+    }
+
+    let mut points = 0;
+    let mut new_credits_observed = credits_in_stake;
+    for &(epoch, final_epoch_credits, initial_epoch_credits) in &vote_state.epoch_credits {
+        let stake_amount = u128::from(stake.delegation.stake(
+            epoch,
+            stake_history,
+            new_rate_activation_epoch,
+        ));
+        let earned_credits = if credits_in_stake < initial_epoch_credits {
+            final_epoch_credits - initial_epoch_credits
+        } else if credits_in_stake < final_epoch_credits {
+            final_epoch_credits - new_credits_observed
+        } else {
+            0
+        };
+        new_credits_observed = new_credits_observed.max(final_epoch_credits);
+        points += stake_amount * u128::from(earned_credits);
+    }
+    StakePoints {
+        points,
+        new_credits_observed,
+        force_credits_update: false,
+    }
+}
+
+/// Splits `rewards` into the voter's and the staker's share, and whether it was split at all.
+fn commission_split(commission: u8, rewards: u64) -> (u64, u64, bool) {
+    match commission.min(100) {
+        0 => (0, rewards, false),
+        100 => (rewards, 0, false),
+        split => {
+            let rewards = u128::from(rewards);
+            let voter = rewards * u128::from(split) / 100;
+            let staker = rewards * u128::from(100 - split) / 100;
+            (voter as u64, staker as u64, true)
+        }
+    }
+}
+
+/// The stake rewards still to be paid, one partition per block.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RewardsDistribution {
+    /// The block height of the block that pays the first remaining partition.
+    pub(crate) next_block_height: u64,
+    pub(crate) partitions: VecDeque<Vec<StakeReward>>,
+}
+
+impl RewardsDistribution {
+    /// Partitions `rewards` with the hasher the stake program and the bank use, seeded
+    /// with the blockhash of the last block of the rewarded epoch.
+    pub(crate) fn new(
+        rewards: Vec<StakeReward>,
+        num_partitions: u64,
+        parent_blockhash: &Hash,
+        first_block_height: u64,
+    ) -> Self {
+        let mut partitions = vec![Vec::new(); num_partitions as usize];
+        let hasher = EpochRewardsHasher::new(partitions.len(), parent_blockhash);
+        for reward in rewards {
+            let partition = hasher.clone().hash_address_to_partition(&reward.pubkey);
+            partitions[partition].push(reward);
+        }
+        Self {
+            next_block_height: first_block_height,
+            partitions: partitions.into(),
+        }
+    }
+}
+
+/// How many blocks the stake rewards of the epoch before `epoch` are paid over: enough that
+/// each pays at most 4096 stake accounts, but never more than a tenth of an epoch.
+pub(crate) fn num_partitions(
+    num_stake_rewards: usize,
+    epoch: Epoch,
+    epoch_schedule: &EpochSchedule,
+) -> u64 {
+    if epoch_schedule.warmup && epoch < epoch_schedule.first_normal_epoch {
+        return 1;
+    }
+    let num_chunks = num_stake_rewards.div_ceil(STAKE_ACCOUNT_STORES_PER_BLOCK) as u64;
+    num_chunks.clamp(1, (epoch_schedule.slots_per_epoch / 10).max(1))
+}
+
+/// Adds `reward` to its stake account. Returns `false` if the account isn't delegated anymore,
+/// in which case the reward is burned.
+pub(crate) fn apply_stake_reward(account: &mut AccountSharedData, reward: &StakeReward) -> bool {
+    let Ok(StakeStateV2::Stake(meta, _, flags)) = account.deserialize_data() else {
+        return false;
+    };
+    let Some(lamports) = account.lamports().checked_add(reward.lamports) else {
+        return false;
+    };
+    let state = StakeStateV2::Stake(meta, reward.stake, flags);
+    if account.serialize_data(&state).is_err() {
+        return false;
+    }
+    account.set_lamports(lamports);
+    true
+}
@@ -0,0 +1,152 @@
+use {
+    super::{AccountStore, AccountsIter, OverlayStore, Result},
+    crate::types::AccountVersion,
+    solana_account::AccountSharedData,
+    solana_pubkey::Pubkey,
+    std::sync::{Arc, Mutex, Weak},
+};
+
+/// Wraps the store of a LiteSVM instance so it can be forked without copying it.
+///
+/// A fork reads through to the same store, from an [`OverlayStore`] of its own. Before this
+/// store overwrites anything a live fork hasn't written itself, the previous value is kept in
+/// the fork's overlay, so the fork keeps seeing the store as it was when forking.
+pub(crate) struct ForkableStore {
+    store: Arc<dyn AccountStore>,
+    forks: Mutex<Vec<Weak<OverlayStore>>>,
+}
+
+impl ForkableStore {
+    pub(crate) fn new(store: Arc<dyn AccountStore>) -> Self {
+        Self {
+            store,
+            forks: Mutex::default(),
+        }
+    }
+
+    /// Returns a new overlay on top of this store, which later writes to this store don't affect.
+    pub(crate) fn fork(&self) -> Arc<OverlayStore> {
+        let overlay = Arc::new(OverlayStore::new(self.store.clone()));
+        let mut forks = self.forks.lock().unwrap();
+        forks.retain(|fork| fork.strong_count() > 0);
+        forks.push(Arc::downgrade(&overlay));
+        overlay
+    }
+
+    fn live_forks(&self) -> Vec<Arc<OverlayStore>> {
+        let mut forks = self.forks.lock().unwrap();
+        forks.retain(|fork| fork.strong_count() > 0);
+        forks.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    /// Hands the current metadata to the forks that haven't written their own yet.
+    fn preserve_metadata(&self) -> Result<()> {
+        let forks = self.live_forks();
+        if !forks.is_empty() {
+            let previous = self.store.get_metadata()?;
+            for fork in &forks {
+                fork.preserve_metadata(previous.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Hands the current state of `pubkeys` to the forks that haven't written them yet.
+    fn preserve_accounts<'a>(&self, pubkeys: impl IntoIterator<Item = &'a Pubkey>) -> Result<()> {
+        let forks = self.live_forks();
+        if forks.is_empty() {
+            return Ok(());
+        }
+        for pubkey in pubkeys {
+            if forks.iter().all(|fork| fork.has_written(pubkey)) {
+                continue;
+            }
+            let previous = self.store.get_account(pubkey)?;
+            for fork in &forks {
+                fork.preserve_account(pubkey, previous.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AccountStore for ForkableStore {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<AccountSharedData>> {
+        self.store.get_account(pubkey)
+    }
+
+    fn put_account(&self, pubkey: &Pubkey, account: &AccountSharedData) -> Result<()> {
+        self.preserve_accounts([pubkey])?;
+        self.store.put_account(pubkey, account)
+    }
+
+    fn put_accounts(&self, accounts: &[(Pubkey, AccountSharedData)]) -> Result<()> {
+        self.preserve_accounts(accounts.iter().map(|(pubkey, _)| pubkey))?;
+        self.store.put_accounts(accounts)
+    }
+
+    fn delete_account(&self, pubkey: &Pubkey) -> Result<()> {
+        self.preserve_accounts([pubkey])?;
+        self.store.delete_account(pubkey)
+    }
+
+    fn write_accounts(&self, accounts: &[(Pubkey, Option<AccountSharedData>)]) -> Result<()> {
+        self.preserve_accounts(accounts.iter().map(|(pubkey, _)| pubkey))?;
+        self.store.write_accounts(accounts)
+    }
+
+    fn iter_accounts(&self) -> Result<AccountsIter<'_>> {
+        self.store.iter_accounts()
+    }
+
+    fn get_program_account_keys(&self, owner: &Pubkey) -> Result<Vec<Pubkey>> {
+        self.store.get_program_account_keys(owner)
+    }
+
+    fn put_account_versions(
+        &self,
+        slot: u64,
+        accounts: &[(Pubkey, Option<AccountSharedData>)],
+    ) -> Result<u64> {
+        let forks = self.live_forks();
+        if !forks.is_empty() {
+            let recorded = self.store.recorded_tx_count(slot)?;
+            for fork in &forks {
+                fork.preserve_tx_count(slot, recorded);
+            }
+        }
+        self.store.put_account_versions(slot, accounts)
+    }
+
+    fn get_account_versions(&self, pubkey: &Pubkey) -> Result<Vec<AccountVersion>> {
+        self.store.get_account_versions(pubkey)
+    }
+
+    fn recorded_tx_count(&self, slot: u64) -> Result<u64> {
+        self.store.recorded_tx_count(slot)
+    }
+
+    fn get_metadata(&self) -> Result<Option<Vec<u8>>> {
+        self.store.get_metadata()
+    }
+
+    fn put_metadata(&self, metadata: &[u8]) -> Result<()> {
+        self.preserve_metadata()?;
+        self.store.put_metadata(metadata)
+    }
+
+    fn write_accounts_with_metadata(
+        &self,
+        accounts: &[(Pubkey, Option<AccountSharedData>)],
+        metadata: &[u8],
+    ) -> Result<()> {
+        self.preserve_accounts(accounts.iter().map(|(pubkey, _)| pubkey))?;
+        self.preserve_metadata()?;
+        self.store.write_accounts_with_metadata(accounts, metadata)
+    }
+
+    #[cfg(feature = "rocksdb")]
+    fn save_checkpoint(&self, path: &std::path::Path) -> Result<bool> {
+        self.store.save_checkpoint(path)
+    }
+}
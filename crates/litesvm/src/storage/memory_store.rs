@@ -0,0 +1,192 @@
+use {
+    super::{AccountStore, AccountsIter, Result},
+    crate::{account_cache::AccountIndex, types::AccountVersion},
+    solana_account::{AccountSharedData, ReadableAccount},
+    solana_pubkey::Pubkey,
+    std::{
+        collections::{BTreeMap, HashMap},
+        sync::RwLock,
+    },
+};
+
+/// An [`AccountStore`] that keeps everything in a process-local map.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    accounts: RwLock<StoredAccounts>,
+    metadata: RwLock<Option<Vec<u8>>>,
+    history: RwLock<AccountHistory>,
+}
+
+/// The stored accounts, along with the addresses of the accounts owned by each program.
+#[derive(Debug, Default, Clone)]
+struct StoredAccounts {
+    accounts: HashMap<Pubkey, AccountSharedData>,
+    owner_index: AccountIndex,
+}
+
+impl StoredAccounts {
+    fn insert(&mut self, pubkey: Pubkey, account: AccountSharedData) {
+        self.remove(&pubkey);
+        self.owner_index.insert(*account.owner(), pubkey);
+        self.accounts.insert(pubkey, account);
+    }
+
+    fn remove(&mut self, pubkey: &Pubkey) {
+        if let Some(previous) = self.accounts.remove(pubkey) {
+            self.owner_index.remove(previous.owner(), pubkey);
+        }
+    }
+}
+
+/// Recorded account states, keyed by address, slot and transaction index.
+#[derive(Debug, Default, Clone)]
+pub(super) struct AccountHistory {
+    versions: BTreeMap<(Pubkey, u64, u64), Option<AccountSharedData>>,
+    /// The number of transactions recorded in each slot.
+    tx_counts: HashMap<u64, u64>,
+}
+
+impl AccountHistory {
+    /// Records the next transaction of `slot`, counting from `recorded` if it's the first one
+    /// this history holds for the slot.
+    pub(super) fn put(
+        &mut self,
+        slot: u64,
+        recorded: u64,
+        accounts: &[(Pubkey, Option<AccountSharedData>)],
+    ) -> u64 {
+        let tx_count = self.tx_counts.entry(slot).or_insert(recorded);
+        let tx_index = *tx_count;
+        *tx_count += 1;
+        for (pubkey, account) in accounts {
+            self.versions
+                .insert((*pubkey, slot, tx_index), account.clone());
+        }
+        tx_index
+    }
+
+    pub(super) fn tx_count(&self, slot: u64) -> Option<u64> {
+        self.tx_counts.get(&slot).copied()
+    }
+
+    /// Returns the versions of `pubkey`, oldest first.
+    pub(super) fn get(&self, pubkey: &Pubkey) -> Vec<AccountVersion> {
+        self.versions
+            .range((*pubkey, 0, 0)..=(*pubkey, u64::MAX, u64::MAX))
+            .map(|((_, slot, tx_index), account)| AccountVersion {
+                slot: *slot,
+                tx_index: *tx_index,
+                account: account.clone(),
+            })
+            .collect()
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a store holding `accounts`.
+    pub(crate) fn from_accounts(
+        accounts: impl IntoIterator<Item = (Pubkey, AccountSharedData)>,
+    ) -> Self {
+        let mut stored = StoredAccounts::default();
+        for (pubkey, account) in accounts {
+            stored.insert(pubkey, account);
+        }
+        Self {
+            accounts: RwLock::new(stored),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the number of stored accounts.
+    pub fn len(&self) -> usize {
+        self.accounts.read().unwrap().accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl AccountStore for MemoryStore {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<AccountSharedData>> {
+        Ok(self.accounts.read().unwrap().accounts.get(pubkey).cloned())
+    }
+
+    fn put_account(&self, pubkey: &Pubkey, account: &AccountSharedData) -> Result<()> {
+        self.accounts
+            .write()
+            .unwrap()
+            .insert(*pubkey, account.clone());
+        Ok(())
+    }
+
+    fn put_accounts(&self, accounts: &[(Pubkey, AccountSharedData)]) -> Result<()> {
+        let mut stored = self.accounts.write().unwrap();
+        for (pubkey, account) in accounts {
+            stored.insert(*pubkey, account.clone());
+        }
+        Ok(())
+    }
+
+    fn delete_account(&self, pubkey: &Pubkey) -> Result<()> {
+        self.accounts.write().unwrap().remove(pubkey);
+        Ok(())
+    }
+
+    fn write_accounts(&self, accounts: &[(Pubkey, Option<AccountSharedData>)]) -> Result<()> {
+        let mut stored = self.accounts.write().unwrap();
+        for (pubkey, account) in accounts {
+            match account {
+                Some(account) => stored.insert(*pubkey, account.clone()),
+                None => stored.remove(pubkey),
+            }
+        }
+        Ok(())
+    }
+
+    fn iter_accounts(&self) -> Result<AccountsIter<'_>> {
+        // snapshot the map so the lock isn't held while the caller iterates
+        let accounts = self
+            .accounts
+            .read()
+            .unwrap()
+            .accounts
+            .iter()
+            .map(|(pubkey, account)| Ok((*pubkey, account.clone())))
+            .collect::<Vec<_>>();
+        Ok(Box::new(accounts.into_iter()))
+    }
+
+    fn get_program_account_keys(&self, owner: &Pubkey) -> Result<Vec<Pubkey>> {
+        Ok(self.accounts.read().unwrap().owner_index.get(owner))
+    }
+
+    fn put_account_versions(
+        &self,
+        slot: u64,
+        accounts: &[(Pubkey, Option<AccountSharedData>)],
+    ) -> Result<u64> {
+        Ok(self.history.write().unwrap().put(slot, 0, accounts))
+    }
+
+    fn get_account_versions(&self, pubkey: &Pubkey) -> Result<Vec<AccountVersion>> {
+        Ok(self.history.read().unwrap().get(pubkey))
+    }
+
+    fn recorded_tx_count(&self, slot: u64) -> Result<u64> {
+        Ok(self.history.read().unwrap().tx_count(slot).unwrap_or(0))
+    }
+
+    fn get_metadata(&self) -> Result<Option<Vec<u8>>> {
+        Ok(self.metadata.read().unwrap().clone())
+    }
+
+    fn put_metadata(&self, metadata: &[u8]) -> Result<()> {
+        *self.metadata.write().unwrap() = Some(metadata.to_vec());
+        Ok(())
+    }
+}
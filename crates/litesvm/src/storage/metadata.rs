@@ -0,0 +1,255 @@
+use {
+    super::{Result, StoreError},
+    crate::{
+        blockhash_queue::{BlockhashEntry, BlockhashQueue},
+        stakes::{RewardsDistribution, StakeReward},
+    },
+    agave_feature_set::FeatureSet,
+    solana_fee_structure::{FeeBin, FeeStructure},
+    solana_hash::Hash,
+    solana_inflation::Inflation,
+    solana_pubkey::Pubkey,
+    solana_stake_interface::state::{Delegation, Stake},
+};
+
+/// The state of a `LiteSVM` instance that does not live in accounts,
+/// persisted through the [`AccountStore`](super::AccountStore) so the instance can be restored later.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SvmMetadata {
+    pub(crate) blockhash_queue: BlockhashQueue,
+    pub(crate) feature_set: FeatureSet,
+    pub(crate) fee_structure: FeeStructure,
+    pub(crate) fee_collector: Option<Pubkey>,
+    pub(crate) fee_burn_percent: u8,
+    pub(crate) inflation: Option<Inflation>,
+    pub(crate) epoch_rewards: Option<RewardsDistribution>,
+    pub(crate) airdrop_kp: [u8; 64],
+    pub(crate) sigverify: bool,
+    pub(crate) blockhash_check: bool,
+    pub(crate) log_bytes_limit: Option<usize>,
+}
+
+/// On-disk layout of [`SvmMetadata`]. None of the Solana types involved implement serde,
+/// so they are flattened into plain tuples before being bincode encoded.
+type EncodedMetadata = (
+    EncodedBlockhashQueue,
+    Vec<(Pubkey, u64)>,
+    (u64, u64, Vec<(u64, u64)>),
+    Option<[u8; 32]>,
+    u8,
+    EncodedEpochRewards,
+    Vec<u8>,
+    bool,
+    bool,
+    Option<u64>,
+);
+
+/// The max age and the entries of the blockhash queue, oldest first.
+type EncodedBlockhashQueue = (u64, Vec<([u8; 32], u64, u64)>);
+
+/// The inflation, and the stake rewards still to be paid as the block height of the next
+/// partition and the partitions.
+type EncodedEpochRewards = (
+    Option<(f64, f64, f64, f64, f64)>,
+    Option<(u64, Vec<Vec<EncodedStakeReward>>)>,
+);
+
+/// The stake account, its delegation (vote account, stake, activation and deactivation epochs,
+/// warmup cooldown rate), the credits observed and the lamports earned.
+type EncodedStakeReward = ([u8; 32], ([u8; 32], u64, u64, u64, f64), u64, u64);
+
+impl SvmMetadata {
+    pub(crate) fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut active_features = self
+            .feature_set
+            .active()
+            .iter()
+            .map(|(id, slot)| (*id, *slot))
+            .collect::<Vec<_>>();
+        // keep the encoding deterministic regardless of hash map ordering
+        active_features.sort_unstable();
+        let epoch_rewards: EncodedEpochRewards = (
+            self.inflation.map(|inflation| {
+                (
+                    inflation.initial,
+                    inflation.terminal,
+                    inflation.taper,
+                    inflation.foundation,
+                    inflation.foundation_term,
+                )
+            }),
+            self.epoch_rewards.as_ref().map(|rewards| {
+                (
+                    rewards.next_block_height,
+                    rewards
+                        .partitions
+                        .iter()
+                        .map(|partition| partition.iter().map(encode_stake_reward).collect())
+                        .collect(),
+                )
+            }),
+        );
+        let encoded: EncodedMetadata = (
+            encode_blockhash_queue(&self.blockhash_queue),
+            active_features,
+            (
+                self.fee_structure.lamports_per_signature,
+                self.fee_structure.lamports_per_write_lock,
+                self.fee_structure
+                    .compute_fee_bins
+                    .iter()
+                    .map(|bin| (bin.limit, bin.fee))
+                    .collect(),
+            ),
+            self.fee_collector.map(|collector| collector.to_bytes()),
+            self.fee_burn_percent,
+            epoch_rewards,
+            self.airdrop_kp.to_vec(),
+            self.sigverify,
+            self.blockhash_check,
+            self.log_bytes_limit.map(|limit| limit as u64),
+        );
+        Ok(bincode::serialize(&encoded)?)
+    }
+
+    pub(crate) fn from_bytes(mut bytes: &[u8]) -> Result<Self> {
+        let (
+            (max_age, entries),
+            active_features,
+            (lamports_per_signature, lamports_per_write_lock, compute_fee_bins),
+            fee_collector,
+            fee_burn_percent,
+            (inflation, epoch_rewards),
+            airdrop_kp,
+            sigverify,
+            blockhash_check,
+            log_bytes_limit,
+        ): EncodedMetadata = bincode::deserialize_from(&mut bytes)?;
+        if entries.is_empty() {
+            return Err(StoreError::InvalidMetadata("the blockhash queue is empty"));
+        }
+        let entries =
+            entries
+                .into_iter()
+                .map(
+                    |(blockhash, block_height, lamports_per_signature)| BlockhashEntry {
+                        blockhash: Hash::new_from_array(blockhash),
+                        block_height,
+                        lamports_per_signature,
+                    },
+                );
+        let blockhash_queue = BlockhashQueue::from_entries(entries, max_age);
+        if fee_burn_percent > 100 {
+            return Err(StoreError::InvalidMetadata(
+                "the burn percentage can't exceed 100",
+            ));
+        }
+        if !bytes.is_empty() {
+            return Err(StoreError::InvalidMetadata(
+                "trailing bytes after the metadata",
+            ));
+        }
+        let inflation = inflation.map(|(initial, terminal, taper, foundation, foundation_term)| {
+            let mut inflation = Inflation::default();
+            inflation.initial = initial;
+            inflation.terminal = terminal;
+            inflation.taper = taper;
+            inflation.foundation = foundation;
+            inflation.foundation_term = foundation_term;
+            inflation
+        });
+        let epoch_rewards =
+            epoch_rewards.map(|(next_block_height, partitions)| RewardsDistribution {
+                next_block_height,
+                partitions: partitions
+                    .into_iter()
+                    .map(|partition| partition.into_iter().map(decode_stake_reward).collect())
+                    .collect(),
+            });
+        let mut feature_set = FeatureSet::default();
+        for (id, slot) in active_features {
+            feature_set.activate(&id, slot);
+        }
+        let airdrop_kp = airdrop_kp
+            .try_into()
+            .map_err(|_| StoreError::InvalidMetadata("airdrop keypair must be 64 bytes"))?;
+        Ok(Self {
+            blockhash_queue,
+            feature_set,
+            fee_structure: FeeStructure {
+                lamports_per_signature,
+                lamports_per_write_lock,
+                compute_fee_bins: compute_fee_bins
+                    .into_iter()
+                    .map(|(limit, fee)| FeeBin { limit, fee })
+                    .collect(),
+            },
+            fee_collector: fee_collector.map(Pubkey::new_from_array),
+            fee_burn_percent,
+            inflation,
+            epoch_rewards,
+            airdrop_kp,
+            sigverify,
+            blockhash_check,
+            log_bytes_limit: log_bytes_limit.map(|limit| limit as usize),
+        })
+    }
+}
+
+fn encode_blockhash_queue(blockhash_queue: &BlockhashQueue) -> EncodedBlockhashQueue {
+    (
+        blockhash_queue.max_age(),
+        blockhash_queue
+            .iter()
+            .rev()
+            .map(|entry| {
+                (
+                    entry.blockhash.to_bytes(),
+                    entry.block_height,
+                    entry.lamports_per_signature,
+                )
+            })
+            .collect(),
+    )
+}
+
+fn encode_stake_reward(reward: &StakeReward) -> EncodedStakeReward {
+    let delegation = &reward.stake.delegation;
+    #[allow(deprecated)]
+    let warmup_cooldown_rate = delegation.warmup_cooldown_rate;
+    (
+        reward.pubkey.to_bytes(),
+        (
+            delegation.voter_pubkey.to_bytes(),
+            delegation.stake,
+            delegation.activation_epoch,
+            delegation.deactivation_epoch,
+            warmup_cooldown_rate,
+        ),
+        reward.stake.credits_observed,
+        reward.lamports,
+    )
+}
+
+fn decode_stake_reward(
+    (pubkey, delegation, credits_observed, lamports): EncodedStakeReward,
+) -> StakeReward {
+    let (voter_pubkey, stake, activation_epoch, deactivation_epoch, warmup_cooldown_rate) =
+        delegation;
+    #[allow(deprecated)]
+    let delegation = Delegation {
+        voter_pubkey: Pubkey::new_from_array(voter_pubkey),
+        stake,
+        activation_epoch,
+        deactivation_epoch,
+        warmup_cooldown_rate,
+    };
+    StakeReward {
+        pubkey: Pubkey::new_from_array(pubkey),
+        stake: Stake {
+            delegation,
+            credits_observed,
+        },
+        lamports,
+    }
+}
@@ -1,152 +1,221 @@
-// use solana_sdk::{
-//     account::AccountSharedData,
-//     pubkey::Pubkey,
-// };
-use solana_account::AccountSharedData;
-use std::sync::Arc; // 导入 Arc
 use {
-    bincode, num_cpus,
-    rocksdb::{ColumnFamilyDescriptor, Options, DB},
+    crate::types::AccountVersion,
+    solana_account::{AccountSharedData, ReadableAccount},
     solana_pubkey::Pubkey,
-    std::path::Path,
+    std::sync::Arc,
     thiserror::Error,
 };
 
+mod forkable_store;
+mod memory_store;
+mod metadata;
+mod null_store;
+mod overlay_store;
+#[cfg(feature = "rocksdb")]
+mod rocksdb_store;
+#[cfg(feature = "rocksdb")]
+mod snapshot;
+
+pub(crate) use forkable_store::ForkableStore;
+use memory_store::AccountHistory;
+pub(crate) use metadata::SvmMetadata;
+#[cfg(feature = "rocksdb")]
+pub use rocksdb_store::{DbKey, RocksDBStore, SCHEMA_VERSION};
+#[cfg(feature = "rocksdb")]
+pub(crate) use snapshot::{SnapshotManifest, ACCOUNTS_DIR};
+pub use {memory_store::MemoryStore, null_store::NullStore, overlay_store::OverlayStore};
+
 #[derive(Error, Debug)]
 pub enum StoreError {
+    #[cfg(feature = "rocksdb")]
     #[error("RocksDB operation failed: {0}")]
     RocksDB(#[from] rocksdb::Error),
     #[error("Serialization error: {0}")]
     Serialization(#[from] bincode::Error),
+    #[error("Invalid LiteSVM metadata: {0}")]
+    InvalidMetadata(&'static str),
+    #[error(
+        "Database schema version {found} is not supported by this version of LiteSVM, \
+         which reads schema version {supported}"
+    )]
+    IncompatibleSchema { found: u32, supported: u32 },
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
 }
 
-type Result<T> = std::result::Result<T, StoreError>;
+pub type Result<T> = std::result::Result<T, StoreError>;
 
-#[repr(u8)]
-enum KeyPrefix {
-    Account = 0x01, // 存储完整账户
-    ProgramData = 0x02, // 存储程序数据
-                    // 可以添加更多数据类型...
-}
+/// A boxed iterator over the accounts held by an [`AccountStore`].
+pub type AccountsIter<'a> = Box<dyn Iterator<Item = Result<(Pubkey, AccountSharedData)>> + 'a>;
 
-pub enum DbKey {
-    Account(Pubkey),     // 账户键
-    ProgramData(Pubkey), // 程序数据键
-}
+/// A backend that LiteSVM writes accounts through to.
+///
+/// LiteSVM keeps the accounts it writes in memory as well, and only reads from the store
+/// for accounts it doesn't already hold. With a bounded account cache
+/// (see [`LiteSVM::with_account_cache_size`](crate::LiteSVM::with_account_cache_size)),
+/// the store is the only copy of the accounts evicted from memory.
+pub trait AccountStore: Send + Sync {
+    /// Returns the account stored under `pubkey`, if any.
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<AccountSharedData>>;
 
-impl DbKey {
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(33);
-        match self {
-            Self::Account(pubkey) => {
-                bytes.push(KeyPrefix::Account as u8);
-                bytes.extend_from_slice(pubkey.as_ref());
-            }
-            Self::ProgramData(pubkey) => {
-                bytes.push(KeyPrefix::ProgramData as u8);
-                bytes.extend_from_slice(pubkey.as_ref());
+    /// Stores a single account.
+    fn put_account(&self, pubkey: &Pubkey, account: &AccountSharedData) -> Result<()>;
+
+    /// Stores several accounts at once.
+    fn put_accounts(&self, accounts: &[(Pubkey, AccountSharedData)]) -> Result<()>;
+
+    /// Removes the account stored under `pubkey`. Removing a missing account is not an error.
+    fn delete_account(&self, pubkey: &Pubkey) -> Result<()>;
+
+    /// Stores each account, or removes it if it's `None`.
+    ///
+    /// LiteSVM commits every transaction with a single call, so stores that can should apply
+    /// all of the writes atomically. The default implementation applies them one at a time.
+    fn write_accounts(&self, accounts: &[(Pubkey, Option<AccountSharedData>)]) -> Result<()> {
+        for (pubkey, account) in accounts {
+            match account {
+                Some(account) => self.put_account(pubkey, account)?,
+                None => self.delete_account(pubkey)?,
             }
         }
-        bytes
+        Ok(())
     }
-}
 
-pub struct RocksDBStore {
-    db: Arc<DB>, // 使用 Arc 共享数据库实例
-}
+    /// Iterates over every stored account.
+    fn iter_accounts(&self) -> Result<AccountsIter<'_>>;
+
+    /// Returns the addresses of the stored accounts owned by `owner`.
+    ///
+    /// The default implementation scans every account, so stores that keep an index should override it.
+    fn get_program_account_keys(&self, owner: &Pubkey) -> Result<Vec<Pubkey>> {
+        self.iter_accounts()?
+            .filter_map(|item| match item {
+                Ok((pubkey, account)) => (account.owner() == owner).then_some(Ok(pubkey)),
+                Err(e) => Some(Err(e)),
+            })
+            .collect()
+    }
 
-impl RocksDBStore {
-    pub fn open(path: &Path) -> Result<Self> {
-        let mut opts = Options::default();
-        opts.create_if_missing(true);
-        opts.create_missing_column_families(true);
-        opts.set_compression_type(rocksdb::DBCompressionType::Zstd);
-        opts.set_max_open_files(1024);
-        opts.increase_parallelism(num_cpus::get() as i32);
+    /// Records the state of `accounts` after the next transaction of `slot`, with `None` for
+    /// removed accounts, and returns the index of that transaction within the slot.
+    ///
+    /// Stores that don't keep account history can ignore it.
+    fn put_account_versions(
+        &self,
+        _slot: u64,
+        _accounts: &[(Pubkey, Option<AccountSharedData>)],
+    ) -> Result<u64> {
+        Ok(0)
+    }
 
-        // 定义列族
-        let cfs = vec!["accounts", "program_data"];
-        let cf_descriptors: Vec<_> = cfs
-            .iter()
-            .map(|name| ColumnFamilyDescriptor::new(*name, Options::default()))
-            .collect();
+    /// Returns every recorded state of the account at `pubkey`, oldest first.
+    fn get_account_versions(&self, _pubkey: &Pubkey) -> Result<Vec<AccountVersion>> {
+        Ok(Vec::new())
+    }
 
-        let db = DB::open_cf_descriptors(&opts, path, cf_descriptors)?;
+    /// Returns how many transactions of `slot` have had their account states recorded.
+    fn recorded_tx_count(&self, _slot: u64) -> Result<u64> {
+        Ok(0)
+    }
 
-        Ok(Self {
-            db: Arc::new(db), // 使用 Arc 包装数据库
-        })
+    /// Returns the serialized LiteSVM metadata, for stores that persist it.
+    fn get_metadata(&self) -> Result<Option<Vec<u8>>> {
+        Ok(None)
     }
 
-    // 获取列族句柄的辅助方法
-    fn accounts_cf(&self) -> &rocksdb::ColumnFamily {
-        self.db
-            .cf_handle("accounts")
-            .expect("Accounts column family not found")
+    /// Stores the serialized LiteSVM metadata. Stores that don't persist state can ignore it.
+    fn put_metadata(&self, _metadata: &[u8]) -> Result<()> {
+        Ok(())
     }
 
-    fn program_data_cf(&self) -> &rocksdb::ColumnFamily {
-        self.db
-            .cf_handle("program_data")
-            .expect("Program data column family not found")
+    /// Like [`write_accounts`](AccountStore::write_accounts), but stores the serialized LiteSVM
+    /// metadata along with the accounts.
+    ///
+    /// LiteSVM uses it for the writes that change the metadata as well, such as producing a block,
+    /// so stores that persist the metadata should apply it atomically with the accounts.
+    /// The default implementation writes the accounts, then the metadata.
+    fn write_accounts_with_metadata(
+        &self,
+        accounts: &[(Pubkey, Option<AccountSharedData>)],
+        metadata: &[u8],
+    ) -> Result<()> {
+        self.write_accounts(accounts)?;
+        self.put_metadata(metadata)
     }
 
-    /// 获取账户数据
-    pub fn get_account(&self, pubkey: &Pubkey) -> Result<Option<AccountSharedData>> {
-        let key = DbKey::Account(*pubkey).to_bytes();
-        match self.db.get_cf(self.accounts_cf(), &key)? {
-            Some(data) => bincode::deserialize(&data).map(Some).map_err(Into::into),
-            None => Ok(None),
-        }
+    /// Writes a consistent copy of the store to `path` as a RocksDB directory,
+    /// for stores that can do so natively.
+    ///
+    /// Returns `false` if the store can't, in which case the caller copies the accounts instead.
+    #[cfg(feature = "rocksdb")]
+    fn save_checkpoint(&self, _path: &std::path::Path) -> Result<bool> {
+        Ok(false)
     }
+}
 
-    /// 存储账户数据
-    pub fn put_account(&self, pubkey: &Pubkey, account: &AccountSharedData) -> Result<()> {
-        let key = DbKey::Account(*pubkey).to_bytes();
-        let value = bincode::serialize(account)?;
-        self.db.put_cf(self.accounts_cf(), &key, &value)?;
-        Ok(())
+impl<T: AccountStore + ?Sized> AccountStore for Arc<T> {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<AccountSharedData>> {
+        (**self).get_account(pubkey)
     }
 
-    /// 批量存储账户数据
-    pub fn put_accounts(&self, accounts: &[(Pubkey, AccountSharedData)]) -> Result<()> {
-        let mut batch = rocksdb::WriteBatch::default();
-        let accounts_cf = self.accounts_cf();
+    fn put_account(&self, pubkey: &Pubkey, account: &AccountSharedData) -> Result<()> {
+        (**self).put_account(pubkey, account)
+    }
 
-        for (pubkey, account) in accounts {
-            let key = DbKey::Account(*pubkey).to_bytes();
-            let value = bincode::serialize(account)?;
-            batch.put_cf(accounts_cf, &key, &value);
-        }
+    fn put_accounts(&self, accounts: &[(Pubkey, AccountSharedData)]) -> Result<()> {
+        (**self).put_accounts(accounts)
+    }
 
-        self.db.write(batch)?;
-        Ok(())
+    fn delete_account(&self, pubkey: &Pubkey) -> Result<()> {
+        (**self).delete_account(pubkey)
     }
 
-    /// 获取程序数据
-    pub fn get_program_data(&self, pubkey: &Pubkey) -> Result<Option<Vec<u8>>> {
-        let key = DbKey::ProgramData(*pubkey).to_bytes();
-        self.db
-            .get_cf(self.program_data_cf(), &key)
-            .map_err(Into::into)
+    fn write_accounts(&self, accounts: &[(Pubkey, Option<AccountSharedData>)]) -> Result<()> {
+        (**self).write_accounts(accounts)
     }
 
-    /// 存储程序数据
-    pub fn put_program_data(&self, pubkey: &Pubkey, data: &[u8]) -> Result<()> {
-        let key = DbKey::ProgramData(*pubkey).to_bytes();
-        self.db.put_cf(self.program_data_cf(), &key, data)?;
-        Ok(())
+    fn iter_accounts(&self) -> Result<AccountsIter<'_>> {
+        (**self).iter_accounts()
     }
 
-    // 创建检查点
-    pub fn create_checkpoint<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        let checkpoint = rocksdb::checkpoint::Checkpoint::new(&self.db)?;
-        checkpoint.create_checkpoint(path)?;
-        Ok(())
+    fn get_program_account_keys(&self, owner: &Pubkey) -> Result<Vec<Pubkey>> {
+        (**self).get_program_account_keys(owner)
+    }
+
+    fn put_account_versions(
+        &self,
+        slot: u64,
+        accounts: &[(Pubkey, Option<AccountSharedData>)],
+    ) -> Result<u64> {
+        (**self).put_account_versions(slot, accounts)
+    }
+
+    fn get_account_versions(&self, pubkey: &Pubkey) -> Result<Vec<AccountVersion>> {
+        (**self).get_account_versions(pubkey)
+    }
+
+    fn recorded_tx_count(&self, slot: u64) -> Result<u64> {
+        (**self).recorded_tx_count(slot)
+    }
+
+    fn get_metadata(&self) -> Result<Option<Vec<u8>>> {
+        (**self).get_metadata()
+    }
+
+    fn put_metadata(&self, metadata: &[u8]) -> Result<()> {
+        (**self).put_metadata(metadata)
+    }
+
+    fn write_accounts_with_metadata(
+        &self,
+        accounts: &[(Pubkey, Option<AccountSharedData>)],
+        metadata: &[u8],
+    ) -> Result<()> {
+        (**self).write_accounts_with_metadata(accounts, metadata)
     }
 
-    // 克隆数据库引用
-    pub fn clone_db(&self) -> Arc<DB> {
-        self.db.clone()
+    #[cfg(feature = "rocksdb")]
+    fn save_checkpoint(&self, path: &std::path::Path) -> Result<bool> {
+        (**self).save_checkpoint(path)
     }
 }
@@ -0,0 +1,40 @@
+use {
+    super::{AccountStore, AccountsIter, Result},
+    solana_account::AccountSharedData,
+    solana_pubkey::Pubkey,
+};
+
+/// An [`AccountStore`] that discards every write. This is the default store.
+///
+/// LiteSVM still holds all the accounts it writes in memory, so this is the cheapest option
+/// when nothing needs to outlive the VM. Unless it was set explicitly, it's replaced with a
+/// [`MemoryStore`](super::MemoryStore) when enabling a feature that reads accounts back from
+/// the store: a bounded account cache, account history or an account source.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullStore;
+
+impl AccountStore for NullStore {
+    fn get_account(&self, _pubkey: &Pubkey) -> Result<Option<AccountSharedData>> {
+        Ok(None)
+    }
+
+    fn put_account(&self, _pubkey: &Pubkey, _account: &AccountSharedData) -> Result<()> {
+        Ok(())
+    }
+
+    fn put_accounts(&self, _accounts: &[(Pubkey, AccountSharedData)]) -> Result<()> {
+        Ok(())
+    }
+
+    fn delete_account(&self, _pubkey: &Pubkey) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_accounts(&self, _accounts: &[(Pubkey, Option<AccountSharedData>)]) -> Result<()> {
+        Ok(())
+    }
+
+    fn iter_accounts(&self) -> Result<AccountsIter<'_>> {
+        Ok(Box::new(std::iter::empty()))
+    }
+}
@@ -0,0 +1,198 @@
+use {
+    super::{AccountHistory, AccountStore, AccountsIter, Result},
+    crate::types::AccountVersion,
+    solana_account::{AccountSharedData, ReadableAccount},
+    solana_pubkey::Pubkey,
+    std::{
+        collections::{HashMap, HashSet},
+        sync::{Arc, RwLock},
+    },
+};
+
+/// A copy-on-write [`AccountStore`] layered over another store.
+///
+/// Reads fall through to the base store for accounts the overlay hasn't written,
+/// while writes and deletions only ever land in the overlay. This is what forked
+/// LiteSVM instances write to.
+pub struct OverlayStore {
+    base: Arc<dyn AccountStore>,
+    /// `None` marks an account deleted in the overlay.
+    accounts: RwLock<HashMap<Pubkey, Option<AccountSharedData>>>,
+    /// `Some(None)` hides the base's metadata.
+    metadata: RwLock<Option<Option<Vec<u8>>>>,
+    history: RwLock<AccountHistory>,
+    /// The number of transactions of each slot the base had recorded when it recorded another
+    /// one after forking. The versions it recorded past that are hidden.
+    base_tx_counts: RwLock<HashMap<u64, u64>>,
+}
+
+impl OverlayStore {
+    pub fn new(base: Arc<dyn AccountStore>) -> Self {
+        Self {
+            base,
+            accounts: RwLock::default(),
+            metadata: RwLock::default(),
+            history: RwLock::default(),
+            base_tx_counts: RwLock::default(),
+        }
+    }
+
+    /// Returns the store this overlay reads through to.
+    pub fn base(&self) -> &Arc<dyn AccountStore> {
+        &self.base
+    }
+
+    pub(crate) fn has_written(&self, pubkey: &Pubkey) -> bool {
+        self.accounts.read().unwrap().contains_key(pubkey)
+    }
+
+    /// Keeps `account` as the state of `pubkey`, unless the overlay already wrote it.
+    /// Called before the base overwrites it, so the overlay keeps seeing the old state.
+    pub(crate) fn preserve_account(&self, pubkey: &Pubkey, account: Option<AccountSharedData>) {
+        self.accounts
+            .write()
+            .unwrap()
+            .entry(*pubkey)
+            .or_insert(account);
+    }
+
+    /// Keeps `metadata` as the overlay's metadata, unless it already wrote its own.
+    pub(crate) fn preserve_metadata(&self, metadata: Option<Vec<u8>>) {
+        self.metadata.write().unwrap().get_or_insert(metadata);
+    }
+
+    /// Hides the versions of `slot` the base records from now on, the first `recorded` being
+    /// the ones it held before.
+    pub(crate) fn preserve_tx_count(&self, slot: u64, recorded: u64) {
+        self.base_tx_counts
+            .write()
+            .unwrap()
+            .entry(slot)
+            .or_insert(recorded);
+    }
+
+    fn base_tx_count(&self, slot: u64) -> Result<u64> {
+        match self.base_tx_counts.read().unwrap().get(&slot) {
+            Some(recorded) => Ok(*recorded),
+            None => self.base.recorded_tx_count(slot),
+        }
+    }
+}
+
+impl AccountStore for OverlayStore {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<AccountSharedData>> {
+        match self.accounts.read().unwrap().get(pubkey) {
+            Some(account) => Ok(account.clone()),
+            None => self.base.get_account(pubkey),
+        }
+    }
+
+    fn put_account(&self, pubkey: &Pubkey, account: &AccountSharedData) -> Result<()> {
+        self.accounts
+            .write()
+            .unwrap()
+            .insert(*pubkey, Some(account.clone()));
+        Ok(())
+    }
+
+    fn put_accounts(&self, accounts: &[(Pubkey, AccountSharedData)]) -> Result<()> {
+        self.accounts.write().unwrap().extend(
+            accounts
+                .iter()
+                .map(|(pubkey, account)| (*pubkey, Some(account.clone()))),
+        );
+        Ok(())
+    }
+
+    fn delete_account(&self, pubkey: &Pubkey) -> Result<()> {
+        self.accounts.write().unwrap().insert(*pubkey, None);
+        Ok(())
+    }
+
+    fn write_accounts(&self, accounts: &[(Pubkey, Option<AccountSharedData>)]) -> Result<()> {
+        self.accounts
+            .write()
+            .unwrap()
+            .extend(accounts.iter().cloned());
+        Ok(())
+    }
+
+    fn iter_accounts(&self) -> Result<AccountsIter<'_>> {
+        // copy the overlay so the lock isn't held while the caller iterates,
+        // and read the base as the iteration goes
+        let overlay = self.accounts.read().unwrap();
+        let written = overlay
+            .iter()
+            .filter_map(|(pubkey, account)| Some(Ok((*pubkey, account.clone()?))))
+            .collect::<Vec<_>>();
+        let overlay_keys = overlay.keys().copied().collect::<HashSet<_>>();
+        drop(overlay);
+        let base = self.base.iter_accounts()?.filter(move |item| {
+            item.as_ref()
+                .map_or(true, |(pubkey, _)| !overlay_keys.contains(pubkey))
+        });
+        Ok(Box::new(written.into_iter().chain(base)))
+    }
+
+    fn get_program_account_keys(&self, owner: &Pubkey) -> Result<Vec<Pubkey>> {
+        let base = self.base.get_program_account_keys(owner)?;
+        let overlay = self.accounts.read().unwrap();
+        let mut keys = base
+            .into_iter()
+            .filter(|pubkey| !overlay.contains_key(pubkey))
+            .collect::<Vec<_>>();
+        keys.extend(overlay.iter().filter_map(|(pubkey, account)| {
+            account
+                .as_ref()
+                .filter(|account| account.owner() == owner)
+                .map(|_| *pubkey)
+        }));
+        Ok(keys)
+    }
+
+    fn put_account_versions(
+        &self,
+        slot: u64,
+        accounts: &[(Pubkey, Option<AccountSharedData>)],
+    ) -> Result<u64> {
+        let recorded = self.base_tx_count(slot)?;
+        Ok(self.history.write().unwrap().put(slot, recorded, accounts))
+    }
+
+    fn get_account_versions(&self, pubkey: &Pubkey) -> Result<Vec<AccountVersion>> {
+        // the overlay numbers its transactions after the base's, so the keys never collide
+        let base_tx_counts = self.base_tx_counts.read().unwrap();
+        let mut versions = self
+            .base
+            .get_account_versions(pubkey)?
+            .into_iter()
+            .filter(|version| {
+                base_tx_counts
+                    .get(&version.slot)
+                    .map_or(true, |recorded| version.tx_index < *recorded)
+            })
+            .collect::<Vec<_>>();
+        versions.extend(self.history.read().unwrap().get(pubkey));
+        versions.sort_by_key(|version| (version.slot, version.tx_index));
+        Ok(versions)
+    }
+
+    fn recorded_tx_count(&self, slot: u64) -> Result<u64> {
+        match self.history.read().unwrap().tx_count(slot) {
+            Some(tx_count) => Ok(tx_count),
+            None => self.base_tx_count(slot),
+        }
+    }
+
+    fn get_metadata(&self) -> Result<Option<Vec<u8>>> {
+        match self.metadata.read().unwrap().clone() {
+            Some(metadata) => Ok(metadata),
+            None => self.base.get_metadata(),
+        }
+    }
+
+    fn put_metadata(&self, metadata: &[u8]) -> Result<()> {
+        *self.metadata.write().unwrap() = Some(Some(metadata.to_vec()));
+        Ok(())
+    }
+}
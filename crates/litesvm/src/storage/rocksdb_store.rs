@@ -0,0 +1,574 @@
+use {
+    super::{AccountStore, AccountsIter, Result, StoreError},
+    crate::types::AccountVersion,
+    bincode, num_cpus,
+    rocksdb::{ColumnFamilyDescriptor, Direction, IteratorMode, Options, DB},
+    solana_account::{AccountSharedData, ReadableAccount},
+    solana_pubkey::Pubkey,
+    std::{collections::HashMap, path::Path, sync::Arc},
+};
+
+mod migrations;
+
+pub use migrations::SCHEMA_VERSION;
+
+/// The column family LiteSVM 0.6 kept program data in, which nothing reads anymore.
+const LEGACY_PROGRAM_DATA_CF: &str = "program_data";
+
+/// The first byte of every key, telling apart the kinds of values stored.
+#[repr(u8)]
+enum KeyPrefix {
+    /// The accounts, keyed by address.
+    Account = 0x01,
+    // 0x02 keyed the program data of LiteSVM 0.6, which isn't stored anymore
+    /// The LiteSVM metadata.
+    Metadata = 0x03,
+    /// The accounts indexed by owner.
+    OwnerIndex = 0x04,
+    /// The version of the database layout.
+    SchemaVersion = 0x05,
+    /// The historical versions of accounts.
+    AccountVersion = 0x06,
+    /// The number of transactions recorded in each slot.
+    SlotTxCount = 0x07,
+}
+
+/// A key in the database, encoded with [`to_bytes`](Self::to_bytes).
+pub enum DbKey {
+    /// The key of the account at an address.
+    Account(Pubkey),
+    /// The key of the LiteSVM metadata.
+    Metadata,
+    /// An account in the index of its owner's accounts.
+    OwnerIndex { owner: Pubkey, pubkey: Pubkey },
+    /// The key of the database layout version.
+    SchemaVersion,
+    /// The state of an account after a transaction: the account, the slot and the index
+    /// of the transaction within the slot.
+    AccountVersion(Pubkey, u64, u64),
+    /// The number of transactions recorded in a slot.
+    SlotTxCount(u64),
+}
+
+impl DbKey {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(33);
+        match self {
+            Self::Account(pubkey) => {
+                bytes.push(KeyPrefix::Account as u8);
+                bytes.extend_from_slice(pubkey.as_ref());
+            }
+            Self::Metadata => {
+                bytes.push(KeyPrefix::Metadata as u8);
+            }
+            Self::OwnerIndex { owner, pubkey } => {
+                bytes.push(KeyPrefix::OwnerIndex as u8);
+                bytes.extend_from_slice(owner.as_ref());
+                bytes.extend_from_slice(pubkey.as_ref());
+            }
+            Self::SchemaVersion => {
+                bytes.push(KeyPrefix::SchemaVersion as u8);
+            }
+            // big-endian so the versions of an account sort by slot, then transaction
+            Self::AccountVersion(pubkey, slot, tx_index) => {
+                bytes.push(KeyPrefix::AccountVersion as u8);
+                bytes.extend_from_slice(pubkey.as_ref());
+                bytes.extend_from_slice(&slot.to_be_bytes());
+                bytes.extend_from_slice(&tx_index.to_be_bytes());
+            }
+            Self::SlotTxCount(slot) => {
+                bytes.push(KeyPrefix::SlotTxCount as u8);
+                bytes.extend_from_slice(&slot.to_be_bytes());
+            }
+        }
+        bytes
+    }
+}
+
+pub struct RocksDBStore {
+    db: Arc<DB>, // 使用 Arc 共享数据库实例
+}
+
+impl RocksDBStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut opts = Self::options();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+
+        let mut db = DB::open_cf_descriptors(&opts, path, Self::column_families(path))?;
+        if db.cf_handle(LEGACY_PROGRAM_DATA_CF).is_some() {
+            db.drop_cf(LEGACY_PROGRAM_DATA_CF)?;
+        }
+
+        let store = Self {
+            db: Arc::new(db), // 使用 Arc 包装数据库
+        };
+        migrations::migrate(&store)?;
+        Ok(store)
+    }
+
+    /// Opens the database at `path` without write access.
+    ///
+    /// Any number of processes can open the same database this way at once, but every write
+    /// fails, so wrap the store in an [`OverlayStore`](super::OverlayStore) to use it from LiteSVM.
+    /// The database must already be at [`SCHEMA_VERSION`], since it can't be migrated;
+    /// opening it once with [`open`](Self::open) upgrades it.
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        let db = DB::open_cf_descriptors_read_only(
+            &Self::options(),
+            path,
+            Self::column_families(path),
+            false,
+        )?;
+        let store = Self { db: Arc::new(db) };
+        migrations::check(&store)?;
+        Ok(store)
+    }
+
+    /// Opens the database at `primary_path` as a RocksDB secondary instance, keeping its own
+    /// logs in `secondary_path`.
+    ///
+    /// Unlike [`open_read_only`](Self::open_read_only), this works while another process has
+    /// the database open for writing, and [`try_catch_up_with_primary`](Self::try_catch_up_with_primary)
+    /// picks up what it wrote since. Writes fail in the same way.
+    pub fn open_as_secondary(primary_path: &Path, secondary_path: &Path) -> Result<Self> {
+        let mut opts = Self::options();
+        // secondary instances need to keep every file open to follow the primary
+        opts.set_max_open_files(-1);
+        let db = DB::open_cf_descriptors_as_secondary(
+            &opts,
+            primary_path,
+            secondary_path,
+            Self::column_families(primary_path),
+        )?;
+        let store = Self { db: Arc::new(db) };
+        migrations::check(&store)?;
+        Ok(store)
+    }
+
+    /// Catches a secondary instance up with the writes of the primary.
+    pub fn try_catch_up_with_primary(&self) -> Result<()> {
+        self.db.try_catch_up_with_primary()?;
+        Ok(())
+    }
+
+    fn options() -> Options {
+        let mut opts = Options::default();
+        opts.set_compression_type(rocksdb::DBCompressionType::Zstd);
+        opts.set_max_open_files(1024);
+        opts.increase_parallelism(num_cpus::get() as i32);
+        opts
+    }
+
+    /// Returns the column families to open the database at `path` with. Every existing one
+    /// has to be opened, including the program data of databases written by LiteSVM 0.6,
+    /// which [`open`](Self::open) drops.
+    fn column_families(path: &Path) -> Vec<ColumnFamilyDescriptor> {
+        let mut names = vec!["accounts", "metadata", "owner_index", "account_history"];
+        if DB::list_cf(&Options::default(), path)
+            .is_ok_and(|existing| existing.iter().any(|name| name == LEGACY_PROGRAM_DATA_CF))
+        {
+            names.push(LEGACY_PROGRAM_DATA_CF);
+        }
+        names
+            .into_iter()
+            .map(|name| ColumnFamilyDescriptor::new(name, Options::default()))
+            .collect()
+    }
+
+    /// Returns the schema version the database was written with,
+    /// or `None` for databases written before schema versions were recorded.
+    pub fn schema_version(&self) -> Result<Option<u32>> {
+        let key = DbKey::SchemaVersion.to_bytes();
+        let Some(bytes) = self.db.get_cf(self.metadata_cf(), key)? else {
+            return Ok(None);
+        };
+        let bytes = bytes
+            .try_into()
+            .map_err(|_| StoreError::InvalidMetadata("schema version must be 4 bytes"))?;
+        Ok(Some(u32::from_le_bytes(bytes)))
+    }
+
+    fn put_schema_version(&self, version: u32) -> Result<()> {
+        let key = DbKey::SchemaVersion.to_bytes();
+        self.db
+            .put_cf(self.metadata_cf(), key, version.to_le_bytes())?;
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        [self.accounts_cf(), self.metadata_cf()]
+            .into_iter()
+            .all(|cf| {
+                self.db
+                    .iterator_cf(cf, IteratorMode::Start)
+                    .next()
+                    .is_none()
+            })
+    }
+
+    fn accounts_cf(&self) -> &rocksdb::ColumnFamily {
+        self.db
+            .cf_handle("accounts")
+            .expect("Accounts column family not found")
+    }
+
+    fn metadata_cf(&self) -> &rocksdb::ColumnFamily {
+        self.db
+            .cf_handle("metadata")
+            .expect("Metadata column family not found")
+    }
+
+    fn owner_index_cf(&self) -> &rocksdb::ColumnFamily {
+        self.db
+            .cf_handle("owner_index")
+            .expect("Owner index column family not found")
+    }
+
+    fn account_history_cf(&self) -> &rocksdb::ColumnFamily {
+        self.db
+            .cf_handle("account_history")
+            .expect("Account history column family not found")
+    }
+
+    /// Adds the owner index updates for writing `account`, or deleting it if `None`, to `batch`.
+    ///
+    /// `owners` holds the owners of the accounts written earlier in the same batch,
+    /// which the database doesn't reflect until the batch is written.
+    fn index_owner(
+        &self,
+        batch: &mut rocksdb::WriteBatch,
+        owners: &mut HashMap<Pubkey, Option<Pubkey>>,
+        pubkey: &Pubkey,
+        account: Option<&AccountSharedData>,
+    ) -> Result<()> {
+        let previous_owner = match owners.get(pubkey) {
+            Some(owner) => *owner,
+            None => self.get_owner(pubkey)?,
+        };
+        let owner = account.map(|account| *account.owner());
+        owners.insert(*pubkey, owner);
+        if previous_owner == owner {
+            return Ok(());
+        }
+        if let Some(owner) = previous_owner {
+            let key = DbKey::OwnerIndex {
+                owner,
+                pubkey: *pubkey,
+            };
+            batch.delete_cf(self.owner_index_cf(), key.to_bytes());
+        }
+        if let Some(owner) = owner {
+            let key = DbKey::OwnerIndex {
+                owner,
+                pubkey: *pubkey,
+            };
+            batch.put_cf(self.owner_index_cf(), key.to_bytes(), b"");
+        }
+        Ok(())
+    }
+
+    /// Returns the owner of the account stored under `pubkey` without copying its data.
+    fn get_owner(&self, pubkey: &Pubkey) -> Result<Option<Pubkey>> {
+        let key = DbKey::Account(*pubkey).to_bytes();
+        let Some(value) = self.db.get_pinned_cf(self.accounts_cf(), key)? else {
+            return Ok(None);
+        };
+        // accounts are serialized as their lamports, data and owner, followed by the rest
+        let (_, _, owner): (u64, &[u8], Pubkey) = bincode::deserialize(&value)?;
+        Ok(Some(owner))
+    }
+
+    /// Returns the account stored under `pubkey`, if any.
+    pub fn get_account(&self, pubkey: &Pubkey) -> Result<Option<AccountSharedData>> {
+        let key = DbKey::Account(*pubkey).to_bytes();
+        match self.db.get_cf(self.accounts_cf(), &key)? {
+            Some(data) => bincode::deserialize(&data).map(Some).map_err(Into::into),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores a single account.
+    pub fn put_account(&self, pubkey: &Pubkey, account: &AccountSharedData) -> Result<()> {
+        let key = DbKey::Account(*pubkey).to_bytes();
+        let value = bincode::serialize(account)?;
+        let mut batch = rocksdb::WriteBatch::default();
+        self.index_owner(&mut batch, &mut HashMap::new(), pubkey, Some(account))?;
+        batch.put_cf(self.accounts_cf(), &key, &value);
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    /// Stores several accounts in a single `WriteBatch`.
+    pub fn put_accounts(&self, accounts: &[(Pubkey, AccountSharedData)]) -> Result<()> {
+        let mut batch = rocksdb::WriteBatch::default();
+        let mut owners = HashMap::new();
+        let accounts_cf = self.accounts_cf();
+
+        for (pubkey, account) in accounts {
+            let key = DbKey::Account(*pubkey).to_bytes();
+            let value = bincode::serialize(account)?;
+            self.index_owner(&mut batch, &mut owners, pubkey, Some(account))?;
+            batch.put_cf(accounts_cf, &key, &value);
+        }
+
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    /// Stores each account, or deletes it if it's `None`, in a single `WriteBatch`.
+    pub fn write_accounts(&self, accounts: &[(Pubkey, Option<AccountSharedData>)]) -> Result<()> {
+        let mut batch = rocksdb::WriteBatch::default();
+        self.batch_accounts(&mut batch, accounts)?;
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    /// Like [`write_accounts`](RocksDBStore::write_accounts), but stores the serialized LiteSVM
+    /// metadata in the same `WriteBatch`.
+    pub fn write_accounts_with_metadata(
+        &self,
+        accounts: &[(Pubkey, Option<AccountSharedData>)],
+        metadata: &[u8],
+    ) -> Result<()> {
+        let mut batch = rocksdb::WriteBatch::default();
+        self.batch_accounts(&mut batch, accounts)?;
+        batch.put_cf(self.metadata_cf(), DbKey::Metadata.to_bytes(), metadata);
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    /// Adds the writes of each account, or its removal if it's `None`, to `batch`.
+    fn batch_accounts(
+        &self,
+        batch: &mut rocksdb::WriteBatch,
+        accounts: &[(Pubkey, Option<AccountSharedData>)],
+    ) -> Result<()> {
+        let mut owners = HashMap::new();
+        let accounts_cf = self.accounts_cf();
+
+        for (pubkey, account) in accounts {
+            let key = DbKey::Account(*pubkey).to_bytes();
+            self.index_owner(batch, &mut owners, pubkey, account.as_ref())?;
+            match account {
+                Some(account) => batch.put_cf(accounts_cf, &key, bincode::serialize(account)?),
+                None => batch.delete_cf(accounts_cf, &key),
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the account stored under `pubkey`, along with its owner index entry.
+    pub fn delete_account(&self, pubkey: &Pubkey) -> Result<()> {
+        let key = DbKey::Account(*pubkey).to_bytes();
+        let mut batch = rocksdb::WriteBatch::default();
+        self.index_owner(&mut batch, &mut HashMap::new(), pubkey, None)?;
+        batch.delete_cf(self.accounts_cf(), &key);
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    /// Returns the addresses of the accounts owned by `owner`, from the `owner_index` column family.
+    pub fn get_program_account_keys(&self, owner: &Pubkey) -> Result<Vec<Pubkey>> {
+        let mut prefix = vec![KeyPrefix::OwnerIndex as u8];
+        prefix.extend_from_slice(owner.as_ref());
+        let mut keys = Vec::new();
+        let iter = self.db.iterator_cf(
+            self.owner_index_cf(),
+            IteratorMode::From(&prefix, Direction::Forward),
+        );
+        for item in iter {
+            let (key, _) = item?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            if let Ok(pubkey) = Pubkey::try_from(&key[prefix.len()..]) {
+                keys.push(pubkey);
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Records the state of `accounts` after the next transaction of `slot` in the
+    /// `account_history` column family, and returns the index of that transaction within the slot.
+    pub fn put_account_versions(
+        &self,
+        slot: u64,
+        accounts: &[(Pubkey, Option<AccountSharedData>)],
+    ) -> Result<u64> {
+        let history_cf = self.account_history_cf();
+        let tx_index = self.recorded_tx_count(slot)?;
+        let mut batch = rocksdb::WriteBatch::default();
+        for (pubkey, account) in accounts {
+            let key = DbKey::AccountVersion(*pubkey, slot, tx_index);
+            batch.put_cf(history_cf, key.to_bytes(), bincode::serialize(account)?);
+        }
+        batch.put_cf(
+            history_cf,
+            DbKey::SlotTxCount(slot).to_bytes(),
+            (tx_index + 1).to_le_bytes(),
+        );
+        self.db.write(batch)?;
+        Ok(tx_index)
+    }
+
+    /// Returns every recorded state of the account at `pubkey`, oldest first.
+    pub fn get_account_versions(&self, pubkey: &Pubkey) -> Result<Vec<AccountVersion>> {
+        let mut prefix = vec![KeyPrefix::AccountVersion as u8];
+        prefix.extend_from_slice(pubkey.as_ref());
+        let mut versions = Vec::new();
+        let iter = self.db.iterator_cf(
+            self.account_history_cf(),
+            IteratorMode::From(&prefix, Direction::Forward),
+        );
+        for item in iter {
+            let (key, value) = item?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            let (slot, tx_index) = key[prefix.len()..].split_at(8);
+            let (Ok(slot), Ok(tx_index)) = (slot.try_into(), tx_index.try_into()) else {
+                return Err(StoreError::InvalidMetadata("malformed account history key"));
+            };
+            versions.push(AccountVersion {
+                slot: u64::from_be_bytes(slot),
+                tx_index: u64::from_be_bytes(tx_index),
+                account: bincode::deserialize(&value)?,
+            });
+        }
+        Ok(versions)
+    }
+
+    /// Returns how many transactions of `slot` have had their account states recorded.
+    pub fn recorded_tx_count(&self, slot: u64) -> Result<u64> {
+        let key = DbKey::SlotTxCount(slot).to_bytes();
+        let Some(bytes) = self.db.get_cf(self.account_history_cf(), key)? else {
+            return Ok(0);
+        };
+        let bytes = bytes
+            .try_into()
+            .map_err(|_| StoreError::InvalidMetadata("transaction count must be 8 bytes"))?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// Iterates over every account held in the `accounts` column family.
+    pub fn iter_accounts(&self) -> AccountsIter<'_> {
+        let prefix = KeyPrefix::Account as u8;
+        let iter = self
+            .db
+            .iterator_cf(
+                self.accounts_cf(),
+                IteratorMode::From(&[prefix], Direction::Forward),
+            )
+            .map(|item| item.map_err(Into::into))
+            .take_while(move |item| {
+                item.as_ref()
+                    .map_or(true, |(key, _)| key.first() == Some(&prefix))
+            })
+            .filter_map(|item| match item {
+                Ok((key, value)) => {
+                    let pubkey = Pubkey::try_from(&key[1..]).ok()?;
+                    Some(
+                        bincode::deserialize(&value)
+                            .map(|account| (pubkey, account))
+                            .map_err(Into::into),
+                    )
+                }
+                Err(e) => Some(Err(e)),
+            });
+        Box::new(iter)
+    }
+
+    /// Returns the serialized LiteSVM metadata, if any.
+    pub fn get_metadata(&self) -> Result<Option<Vec<u8>>> {
+        let key = DbKey::Metadata.to_bytes();
+        self.db.get_cf(self.metadata_cf(), &key).map_err(Into::into)
+    }
+
+    /// Stores the serialized LiteSVM metadata.
+    pub fn put_metadata(&self, metadata: &[u8]) -> Result<()> {
+        let key = DbKey::Metadata.to_bytes();
+        self.db.put_cf(self.metadata_cf(), &key, metadata)?;
+        Ok(())
+    }
+
+    /// Writes a consistent copy of the database to `path` as a RocksDB checkpoint,
+    /// which hard links the database files where possible.
+    pub fn create_checkpoint<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let checkpoint = rocksdb::checkpoint::Checkpoint::new(&self.db)?;
+        checkpoint.create_checkpoint(path)?;
+        Ok(())
+    }
+
+    /// Returns the underlying RocksDB instance.
+    pub fn clone_db(&self) -> Arc<DB> {
+        self.db.clone()
+    }
+}
+
+impl AccountStore for RocksDBStore {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<AccountSharedData>> {
+        RocksDBStore::get_account(self, pubkey)
+    }
+
+    fn put_account(&self, pubkey: &Pubkey, account: &AccountSharedData) -> Result<()> {
+        RocksDBStore::put_account(self, pubkey, account)
+    }
+
+    fn put_accounts(&self, accounts: &[(Pubkey, AccountSharedData)]) -> Result<()> {
+        RocksDBStore::put_accounts(self, accounts)
+    }
+
+    fn delete_account(&self, pubkey: &Pubkey) -> Result<()> {
+        RocksDBStore::delete_account(self, pubkey)
+    }
+
+    fn write_accounts(&self, accounts: &[(Pubkey, Option<AccountSharedData>)]) -> Result<()> {
+        RocksDBStore::write_accounts(self, accounts)
+    }
+
+    fn iter_accounts(&self) -> Result<AccountsIter<'_>> {
+        Ok(RocksDBStore::iter_accounts(self))
+    }
+
+    fn get_program_account_keys(&self, owner: &Pubkey) -> Result<Vec<Pubkey>> {
+        RocksDBStore::get_program_account_keys(self, owner)
+    }
+
+    fn put_account_versions(
+        &self,
+        slot: u64,
+        accounts: &[(Pubkey, Option<AccountSharedData>)],
+    ) -> Result<u64> {
+        RocksDBStore::put_account_versions(self, slot, accounts)
+    }
+
+    fn get_account_versions(&self, pubkey: &Pubkey) -> Result<Vec<AccountVersion>> {
+        RocksDBStore::get_account_versions(self, pubkey)
+    }
+
+    fn recorded_tx_count(&self, slot: u64) -> Result<u64> {
+        RocksDBStore::recorded_tx_count(self, slot)
+    }
+
+    fn get_metadata(&self) -> Result<Option<Vec<u8>>> {
+        RocksDBStore::get_metadata(self)
+    }
+
+    fn put_metadata(&self, metadata: &[u8]) -> Result<()> {
+        RocksDBStore::put_metadata(self, metadata)
+    }
+
+    fn write_accounts_with_metadata(
+        &self,
+        accounts: &[(Pubkey, Option<AccountSharedData>)],
+        metadata: &[u8],
+    ) -> Result<()> {
+        RocksDBStore::write_accounts_with_metadata(self, accounts, metadata)
+    }
+
+    fn save_checkpoint(&self, path: &Path) -> Result<bool> {
+        self.create_checkpoint(path)?;
+        Ok(true)
+    }
+}
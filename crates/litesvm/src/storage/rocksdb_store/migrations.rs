@@ -0,0 +1,102 @@
+use {
+    super::{DbKey, RocksDBStore},
+    crate::storage::{Result, StoreError},
+    log::info,
+    rocksdb::WriteBatch,
+    solana_account::ReadableAccount,
+};
+
+/// Version of the on-disk layout written by this version of LiteSVM.
+///
+/// Bump it once for each release that changes the keys, the column families or the encoding
+/// of stored values, and add a migration from the previous release's version to [`MIGRATIONS`].
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Databases without a schema version were written by LiteSVM 0.6, before versioning was introduced.
+const UNVERSIONED: u32 = 1;
+
+struct Migration {
+    /// The version this migration upgrades from. It upgrades to `from + 1`.
+    from: u32,
+    description: &'static str,
+    run: fn(&RocksDBStore, &mut WriteBatch) -> Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    description: "build the owner index",
+    run: build_owner_index,
+}];
+
+/// Brings the database up to [`SCHEMA_VERSION`], one version at a time.
+///
+/// Each step is written atomically along with the new version, so an interrupted
+/// upgrade resumes from the last completed step the next time the database is opened.
+pub(super) fn migrate(store: &RocksDBStore) -> Result<()> {
+    let mut version = match store.schema_version()? {
+        Some(version) => version,
+        None if store.is_empty() => {
+            store.put_schema_version(SCHEMA_VERSION)?;
+            return Ok(());
+        }
+        None => UNVERSIONED,
+    };
+    let incompatible = |found| StoreError::IncompatibleSchema {
+        found,
+        supported: SCHEMA_VERSION,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(incompatible(version));
+    }
+    while version < SCHEMA_VERSION {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|migration| migration.from == version)
+            .ok_or_else(|| incompatible(version))?;
+        info!(
+            "Migrating database from schema version {version}: {}",
+            migration.description
+        );
+        let mut batch = WriteBatch::default();
+        (migration.run)(store, &mut batch)?;
+        version += 1;
+        batch.put_cf(
+            store.metadata_cf(),
+            DbKey::SchemaVersion.to_bytes(),
+            version.to_le_bytes(),
+        );
+        store.db.write(batch)?;
+    }
+    Ok(())
+}
+
+/// Checks that a database opened without write access is already at [`SCHEMA_VERSION`],
+/// since it can't be migrated.
+pub(super) fn check(store: &RocksDBStore) -> Result<()> {
+    let version = match store.schema_version()? {
+        Some(version) => version,
+        None if store.is_empty() => return Ok(()),
+        None => UNVERSIONED,
+    };
+    if version != SCHEMA_VERSION {
+        return Err(StoreError::IncompatibleSchema {
+            found: version,
+            supported: SCHEMA_VERSION,
+        });
+    }
+    Ok(())
+}
+
+/// LiteSVM 0.6 only stored accounts. The column families added since are created when the
+/// database is opened, and only the owner index has to be filled from the existing accounts.
+fn build_owner_index(store: &RocksDBStore, batch: &mut WriteBatch) -> Result<()> {
+    for item in store.iter_accounts() {
+        let (pubkey, account) = item?;
+        let key = DbKey::OwnerIndex {
+            owner: *account.owner(),
+            pubkey,
+        };
+        batch.put_cf(store.owner_index_cf(), key.to_bytes(), b"");
+    }
+    Ok(())
+}
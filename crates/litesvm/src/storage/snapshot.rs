@@ -0,0 +1,72 @@
+use {
+    super::{Result, StoreError, SCHEMA_VERSION},
+    crate::types::TransactionResult,
+    solana_signature::Signature,
+    std::path::Path,
+};
+
+/// Bumped whenever the manifest layout changes.
+const SNAPSHOT_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "manifest.bin";
+/// The RocksDB directory holding the accounts, next to the manifest.
+pub(crate) const ACCOUNTS_DIR: &str = "accounts";
+
+/// Everything in a snapshot apart from the accounts.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SnapshotManifest {
+    /// The encoded [`SvmMetadata`](super::SvmMetadata), in the layout of [`SCHEMA_VERSION`].
+    pub(crate) metadata: Vec<u8>,
+    pub(crate) history_capacity: usize,
+    /// Oldest first.
+    pub(crate) history: Vec<(Signature, TransactionResult)>,
+}
+
+/// On-disk layout of [`SnapshotManifest`], prefixed with [`SNAPSHOT_VERSION`].
+/// The metadata follows the schema version it was written with.
+type EncodedManifest = (u32, u32, Vec<u8>, u64, Vec<(Vec<u8>, TransactionResult)>);
+
+impl SnapshotManifest {
+    pub(crate) fn write(&self, dir: &Path) -> Result<()> {
+        let encoded: EncodedManifest = (
+            SNAPSHOT_VERSION,
+            SCHEMA_VERSION,
+            self.metadata.clone(),
+            self.history_capacity as u64,
+            self.history
+                .iter()
+                .map(|(signature, result)| (signature.as_ref().to_vec(), result.clone()))
+                .collect(),
+        );
+        std::fs::write(dir.join(MANIFEST_FILE), bincode::serialize(&encoded)?)?;
+        Ok(())
+    }
+
+    pub(crate) fn read(dir: &Path) -> Result<Self> {
+        let bytes = std::fs::read(dir.join(MANIFEST_FILE))?;
+        let (version, schema_version, metadata, history_capacity, history): EncodedManifest =
+            bincode::deserialize(&bytes)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(StoreError::InvalidMetadata("unsupported snapshot version"));
+        }
+        // no earlier schema version had metadata to upgrade
+        if schema_version != SCHEMA_VERSION {
+            return Err(StoreError::IncompatibleSchema {
+                found: schema_version,
+                supported: SCHEMA_VERSION,
+            });
+        }
+        let history = history
+            .into_iter()
+            .map(|(signature, result)| {
+                let signature = <[u8; 64]>::try_from(signature)
+                    .map_err(|_| StoreError::InvalidMetadata("signatures must be 64 bytes"))?;
+                Ok((Signature::from(signature), result))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            metadata,
+            history_capacity: history_capacity as usize,
+            history,
+        })
+    }
+}
@@ -10,8 +10,13 @@ use {
     solana_transaction_error::{TransactionError, TransactionResult as Result},
 };
 
+/// What a transaction did when it was executed.
+///
+/// The struct is `#[non_exhaustive]`, so fields can be added without breaking callers. Outside
+/// this crate, build one from [`Default::default`] and set the fields you need.
 #[derive(Debug, Default, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct TransactionMetadata {
     #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_with_str"))]
     pub signature: Signature,
@@ -19,6 +24,8 @@ pub struct TransactionMetadata {
     pub inner_instructions: InnerInstructionsList,
     pub compute_units_consumed: u64,
     pub return_data: TransactionReturnData,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fees: FeeBreakdown,
 }
 
 impl TransactionMetadata {
@@ -27,6 +34,30 @@ impl TransactionMetadata {
     }
 }
 
+/// The fee a transaction paid, and where it went.
+///
+/// Rejected transactions pay nothing, and neither do failed ones whose fee couldn't be
+/// withdrawn, so their breakdown is all zeros.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FeeBreakdown {
+    /// The fee for the signatures.
+    pub transaction_fee: u64,
+    /// The compute unit price times the compute unit limit.
+    pub prioritization_fee: u64,
+    /// The part of the fee that was destroyed.
+    pub burned: u64,
+    /// The part of the fee credited to the fee collector.
+    pub collected: u64,
+}
+
+impl FeeBreakdown {
+    /// The fee taken from the fee payer.
+    pub fn total(&self) -> u64 {
+        self.transaction_fee + self.prioritization_fee
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SimulatedTransactionInfo {
@@ -55,6 +86,191 @@ impl From<ProgramError> for FailedTransactionMetadata {
 
 pub type TransactionResult = std::result::Result<TransactionMetadata, FailedTransactionMetadata>;
 
+/// A filter for [`LiteSVM::get_program_accounts`](crate::LiteSVM::get_program_accounts),
+/// with the same semantics as the `getProgramAccounts` RPC filters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountFilter {
+    /// Matches accounts whose data is exactly this many bytes long.
+    DataSize(u64),
+    /// Matches accounts whose data holds `bytes` starting at `offset`.
+    Memcmp { offset: usize, bytes: Vec<u8> },
+}
+
+impl AccountFilter {
+    pub fn memcmp(offset: usize, bytes: impl Into<Vec<u8>>) -> Self {
+        Self::Memcmp {
+            offset,
+            bytes: bytes.into(),
+        }
+    }
+
+    /// Returns whether an account with this data passes the filter.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        match self {
+            Self::DataSize(size) => data.len() as u64 == *size,
+            Self::Memcmp { offset, bytes } => data
+                .get(*offset..)
+                .and_then(|data| data.get(..bytes.len()))
+                .is_some_and(|data| data == bytes.as_slice()),
+        }
+    }
+}
+
+/// Limits the account data returned by [`LiteSVM::get_program_accounts`](crate::LiteSVM::get_program_accounts)
+/// to `length` bytes starting at `offset`. Like the RPC, the slice is cut short at the end of the data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataSlice {
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl DataSlice {
+    pub(crate) fn apply(&self, data: &[u8]) -> Vec<u8> {
+        let start = self.offset.min(data.len());
+        let end = self.offset.saturating_add(self.length).min(data.len());
+        data[start..end].to_vec()
+    }
+}
+
+/// Counters of the in-memory account cache, returned by
+/// [`LiteSVM::account_cache_stats`](crate::LiteSVM::account_cache_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AccountCacheStats {
+    /// Reads served from memory.
+    pub hits: u64,
+    /// Reads that had to go to the store.
+    pub misses: u64,
+    /// Accounts evicted to stay within the capacity.
+    pub evictions: u64,
+    /// Accounts currently held in memory, including the sysvars.
+    pub len: usize,
+    /// The configured capacity, or `None` if the cache is unbounded.
+    pub capacity: Option<usize>,
+}
+
+/// The state of an account after a transaction, recorded when account history is enabled
+/// with [`LiteSVM::with_account_history`](crate::LiteSVM::with_account_history).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountVersion {
+    pub slot: u64,
+    /// The position of the transaction within its slot.
+    pub tx_index: u64,
+    /// `None` if the transaction removed the account.
+    pub account: Option<AccountSharedData>,
+}
+
+/// Selects the accounts [`LiteSVM::import_snapshot`](crate::LiteSVM::import_snapshot) imports.
+///
+/// An account is imported if its address or its owner was selected. The default filter
+/// selects nothing, which still derives the feature set from the snapshot.
+#[cfg(feature = "agave-snapshot")]
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotFilter {
+    pub(crate) pubkeys: std::collections::HashSet<Pubkey>,
+    pub(crate) owners: std::collections::HashSet<Pubkey>,
+    pub(crate) all: bool,
+    pub(crate) keep_feature_set: bool,
+}
+
+#[cfg(feature = "agave-snapshot")]
+impl SnapshotFilter {
+    /// Selects every account in the snapshot.
+    pub fn all() -> Self {
+        Self {
+            all: true,
+            ..Self::default()
+        }
+    }
+
+    /// Selects the accounts at these addresses.
+    pub fn with_pubkeys(mut self, pubkeys: impl IntoIterator<Item = Pubkey>) -> Self {
+        self.pubkeys.extend(pubkeys);
+        self
+    }
+
+    /// Selects the accounts owned by these programs.
+    pub fn with_owners(mut self, owners: impl IntoIterator<Item = Pubkey>) -> Self {
+        self.owners.extend(owners);
+        self
+    }
+
+    /// Keeps the current feature set instead of replacing it with the one derived from the snapshot.
+    pub fn with_current_feature_set(mut self) -> Self {
+        self.keep_feature_set = true;
+        self
+    }
+
+    pub(crate) fn matches(&self, pubkey: &Pubkey, owner: &Pubkey) -> bool {
+        self.all || self.pubkeys.contains(pubkey) || self.owners.contains(owner)
+    }
+}
+
+/// What [`LiteSVM::import_snapshot`](crate::LiteSVM::import_snapshot) found in the snapshot archives.
+#[cfg(feature = "agave-snapshot")]
+#[derive(Debug, Clone)]
+pub struct SnapshotInfo {
+    /// The slot of the newest snapshot.
+    pub slot: u64,
+    /// The number of accounts imported, not counting the ones the snapshot shows as closed.
+    pub accounts: usize,
+    /// The features activated in the snapshot, with every other feature inactive.
+    pub feature_set: agave_feature_set::FeatureSet,
+}
+
+/// The outcome of [`LiteSVM::replay_transaction`](crate::LiteSVM::replay_transaction),
+/// with every way it differs from what was recorded on chain.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayReport {
+    pub result: TransactionResult,
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+#[cfg(feature = "serde")]
+impl ReplayReport {
+    /// Returns whether the transaction behaved exactly as recorded.
+    pub fn is_match(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// A difference between a replayed transaction and its recording.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayMismatch {
+    /// The transaction failed differently, with `None` meaning it succeeded.
+    Result {
+        expected: Option<TransactionError>,
+        actual: Option<TransactionError>,
+    },
+    /// The first log line that differs, with `None` past the end of the logs.
+    Log {
+        index: usize,
+        expected: Option<String>,
+        actual: Option<String>,
+    },
+    ComputeUnits {
+        expected: u64,
+        actual: u64,
+    },
+    /// The balance of an account after the transaction.
+    PostBalance {
+        pubkey: Pubkey,
+        expected: u64,
+        actual: u64,
+    },
+}
+
+/// Identifies a checkpoint created by [`LiteSVM::checkpoint`](crate::LiteSVM::checkpoint).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CheckpointId(pub(crate) u64);
+
+impl std::fmt::Display for CheckpointId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 pub(crate) struct ExecutionResult {
     pub(crate) post_accounts: Vec<(Pubkey, AccountSharedData)>,
     pub(crate) tx_result: Result<()>,
@@ -62,6 +278,7 @@ pub(crate) struct ExecutionResult {
     pub(crate) compute_units_consumed: u64,
     pub(crate) inner_instructions: InnerInstructionsList,
     pub(crate) return_data: TransactionReturnData,
+    pub(crate) fees: FeeBreakdown,
     /// Whether the transaction can be included in a block
     pub(crate) included: bool,
 }
@@ -75,6 +292,7 @@ impl Default for ExecutionResult {
             compute_units_consumed: Default::default(),
             inner_instructions: Default::default(),
             return_data: Default::default(),
+            fees: Default::default(),
             included: false,
         }
     }
@@ -0,0 +1,87 @@
+use {
+    litesvm::{storage::MemoryStore, LiteSVM},
+    solana_account::Account,
+    solana_keypair::Keypair,
+    solana_message::Message,
+    solana_pubkey::Pubkey,
+    solana_signer::Signer,
+    solana_system_interface::instruction::transfer,
+    solana_transaction::Transaction,
+};
+
+fn account(lamports: u64, owner: Pubkey) -> Account {
+    Account {
+        lamports,
+        data: vec![],
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+#[test]
+fn test_evicted_accounts_are_read_back() {
+    let owner = Pubkey::new_unique();
+    let mut svm = LiteSVM::new()
+        .with_store(MemoryStore::new())
+        .with_account_cache_size(Some(2));
+    let pubkeys = (0..5).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+    for (i, pubkey) in pubkeys.iter().enumerate() {
+        svm.set_account(*pubkey, account(1_000 + i as u64, owner))
+            .unwrap();
+    }
+    let stats = svm.account_cache_stats();
+    assert_eq!(stats.capacity, Some(2));
+    assert!(stats.evictions >= 3);
+
+    // the oldest accounts were evicted, so reading them goes to the store
+    let misses = stats.misses;
+    assert_eq!(svm.get_balance(&pubkeys[0]), Some(1_000));
+    assert_eq!(svm.account_cache_stats().misses, misses + 1);
+    // and brings them back into memory
+    let hits = svm.account_cache_stats().hits;
+    assert_eq!(svm.get_balance(&pubkeys[0]), Some(1_000));
+    assert_eq!(svm.account_cache_stats().hits, hits + 1);
+
+    let found = svm.get_program_accounts(&owner, &[], None).unwrap();
+    assert_eq!(found.len(), 5);
+}
+
+#[test]
+fn test_transactions_with_bounded_cache() {
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+    let mut svm = LiteSVM::new()
+        .with_store(MemoryStore::new())
+        .with_account_cache_size(Some(1));
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    for _ in 0..3 {
+        let tx = Transaction::new(
+            &[&payer],
+            Message::new(
+                &[transfer(&payer.pubkey(), &to, 100)],
+                Some(&payer.pubkey()),
+            ),
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+        svm.expire_blockhash();
+    }
+    assert_eq!(svm.get_balance(&to), Some(300));
+    assert!(svm.account_cache_stats().evictions > 0);
+    // sysvars are never evicted
+    assert_eq!(svm.get_sysvar::<solana_clock::Clock>().slot, 0);
+}
+
+#[test]
+fn test_unbounded_cache_by_default() {
+    let mut svm = LiteSVM::new();
+    let pubkey = Pubkey::new_unique();
+    svm.set_account(pubkey, account(1, Pubkey::new_unique()))
+        .unwrap();
+    svm.get_account(&pubkey).unwrap();
+    let stats = svm.account_cache_stats();
+    assert_eq!(stats.capacity, None);
+    assert_eq!(stats.evictions, 0);
+    assert!(stats.hits > 0);
+}
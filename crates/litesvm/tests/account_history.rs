@@ -0,0 +1,88 @@
+use {
+    litesvm::LiteSVM, solana_account::ReadableAccount, solana_keypair::Keypair,
+    solana_message::Message, solana_pubkey::Pubkey, solana_signer::Signer,
+    solana_system_interface::instruction::transfer, solana_transaction::Transaction,
+};
+
+fn transfer_tx(svm: &LiteSVM, payer: &Keypair, to: &Pubkey, lamports: u64) -> Transaction {
+    Transaction::new(
+        &[payer],
+        Message::new(
+            &[transfer(&payer.pubkey(), to, lamports)],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    )
+}
+
+#[test]
+fn test_walk_back_through_account_history() {
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+    let mut svm = LiteSVM::new().with_account_history(true);
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+
+    for _ in 0..3 {
+        svm.send_transaction(transfer_tx(&svm, &payer, &to, 100))
+            .unwrap();
+        svm.expire_blockhash();
+    }
+    svm.warp_to_slot(5);
+    svm.send_transaction(transfer_tx(&svm, &payer, &to, 100))
+        .unwrap();
+
+    let history = svm.account_history(&to).unwrap();
+    let versions = history
+        .iter()
+        .map(|version| {
+            (
+                version.slot,
+                version.tx_index,
+                version.account.as_ref().unwrap().lamports(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        versions,
+        [(0, 0, 100), (0, 1, 200), (0, 2, 300), (5, 0, 400)]
+    );
+    assert_eq!(svm.get_account_at(&to, 4).unwrap().unwrap().lamports, 300);
+    assert_eq!(svm.get_account_at(&to, 5).unwrap().unwrap().lamports, 400);
+    // the airdrop didn't go through send_transaction, so the payer's history starts with the transfers
+    assert_eq!(svm.account_history(&payer.pubkey()).unwrap().len(), 4);
+}
+
+#[test]
+fn test_removed_accounts_are_recorded() {
+    let payer = Keypair::new();
+    let drained = Keypair::new();
+    let mut svm = LiteSVM::new().with_account_history(true);
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    svm.airdrop(&drained.pubkey(), 1_000).unwrap();
+
+    let tx = Transaction::new(
+        &[&payer, &drained],
+        Message::new(
+            &[transfer(&drained.pubkey(), &payer.pubkey(), 1_000)],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    );
+    svm.send_transaction(tx).unwrap();
+
+    let history = svm.account_history(&drained.pubkey()).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].account, None);
+    assert_eq!(svm.get_account_at(&drained.pubkey(), 0).unwrap(), None);
+}
+
+#[test]
+fn test_account_history_is_opt_in() {
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+    let mut svm = LiteSVM::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    svm.send_transaction(transfer_tx(&svm, &payer, &to, 100))
+        .unwrap();
+    assert!(svm.account_history(&to).unwrap().is_empty());
+}
@@ -0,0 +1,260 @@
+#![cfg(feature = "rpc")]
+
+use {
+    base64::{prelude::BASE64_STANDARD, Engine},
+    litesvm::{account_source::RpcAccountSource, LiteSVM},
+    serde_json::{json, Value},
+    solana_account::Account,
+    solana_instruction::Instruction,
+    solana_keypair::Keypair,
+    solana_loader_v3_interface::get_program_data_address,
+    solana_message::Message,
+    solana_pubkey::Pubkey,
+    solana_signer::Signer,
+    solana_transaction::Transaction,
+    std::{
+        collections::HashMap,
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::{Arc, Mutex},
+    },
+};
+
+/// Each request's method along with the addresses it asked for.
+type Requests = Vec<(String, Vec<Pubkey>)>;
+
+/// A JSON-RPC server answering `getAccountInfo` and `getMultipleAccounts` from a fixed set of
+/// accounts, recording the addresses asked for by each request.
+struct MockRpc {
+    url: String,
+    requests: Arc<Mutex<Requests>>,
+}
+
+impl MockRpc {
+    fn start(accounts: HashMap<Pubkey, Account>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                handle(stream.unwrap(), &accounts, &recorded);
+            }
+        });
+        Self { url, requests }
+    }
+
+    fn source(&self) -> RpcAccountSource {
+        RpcAccountSource::new(&self.url)
+    }
+
+    fn requests(&self) -> Requests {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn handle(stream: TcpStream, accounts: &HashMap<Pubkey, Account>, requests: &Mutex<Requests>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+    let request: Value = serde_json::from_slice(&body).unwrap();
+
+    let method = request["method"].as_str().unwrap().to_string();
+    let ui_account = |pubkey: &Pubkey| match accounts.get(pubkey) {
+        Some(account) => json!({
+            "lamports": account.lamports,
+            "data": [BASE64_STANDARD.encode(&account.data), "base64"],
+            "owner": account.owner.to_string(),
+            "executable": account.executable,
+            "rentEpoch": account.rent_epoch,
+            "space": account.data.len(),
+        }),
+        None => Value::Null,
+    };
+    let (pubkeys, value) = match method.as_str() {
+        "getAccountInfo" => {
+            let pubkey = request["params"][0].as_str().unwrap().parse().unwrap();
+            (vec![pubkey], ui_account(&pubkey))
+        }
+        "getMultipleAccounts" => {
+            let pubkeys = request["params"][0]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|pubkey| pubkey.as_str().unwrap().parse().unwrap())
+                .collect::<Vec<Pubkey>>();
+            let value = pubkeys.iter().map(ui_account).collect();
+            (pubkeys, value)
+        }
+        method => panic!("unexpected method {method}"),
+    };
+    requests.lock().unwrap().push((method, pubkeys));
+
+    let response = json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "result": {"context": {"slot": 1}, "value": value},
+    })
+    .to_string();
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+        response.len()
+    )
+    .unwrap();
+}
+
+fn wallet(lamports: u64) -> Account {
+    Account {
+        lamports,
+        data: vec![],
+        owner: solana_sdk_ids::system_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// The accounts of the memo program deployed under the upgradeable loader.
+fn memo_program(program_id: Pubkey) -> HashMap<Pubkey, Account> {
+    let mut so_path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    so_path.push("src/spl/programs/spl_memo-3.0.0.so");
+    let mut svm = LiteSVM::new();
+    svm.load_programs(&[(program_id, so_path)]).unwrap();
+    [program_id, get_program_data_address(&program_id)]
+        .into_iter()
+        .map(|pubkey| (pubkey, svm.get_account(&pubkey).unwrap()))
+        .collect()
+}
+
+fn memo_tx(svm: &mut LiteSVM, program_id: Pubkey) -> Transaction {
+    let payer = Keypair::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    Transaction::new(
+        &[&payer],
+        Message::new(
+            &[Instruction {
+                program_id,
+                accounts: vec![],
+                data: b"hello".to_vec(),
+            }],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    )
+}
+
+#[test]
+fn test_accounts_are_fetched_once_on_a_miss() {
+    let remote = Pubkey::new_unique();
+    let missing = Pubkey::new_unique();
+    let rpc = MockRpc::start(HashMap::from([(remote, wallet(42))]));
+    let mut svm = LiteSVM::new().with_account_source(rpc.source());
+
+    assert_eq!(svm.get_account(&remote), Some(wallet(42)));
+    assert_eq!(svm.get_account(&remote), Some(wallet(42)));
+    assert_eq!(svm.get_account(&missing), None);
+    assert_eq!(svm.get_account(&missing), None);
+    assert_eq!(
+        rpc.requests(),
+        [
+            ("getAccountInfo".to_string(), vec![remote]),
+            ("getAccountInfo".to_string(), vec![missing]),
+        ]
+    );
+
+    // removed accounts aren't brought back
+    svm.remove_account(&remote).unwrap();
+    assert_eq!(svm.get_account(&remote), None);
+    // and local accounts are never fetched
+    let local = Pubkey::new_unique();
+    svm.airdrop(&local, 1).unwrap();
+    assert_eq!(svm.get_balance(&local), Some(1));
+    assert_eq!(rpc.requests().len(), 2);
+}
+
+#[test]
+fn test_transactions_fetch_programs_with_their_program_data() {
+    let program_id = Pubkey::new_unique();
+    let rpc = MockRpc::start(memo_program(program_id));
+    let mut svm = LiteSVM::new().with_account_source(rpc.source());
+
+    let tx = memo_tx(&mut svm, program_id);
+    let payer = tx.message.account_keys[0];
+    svm.send_transaction(tx).unwrap();
+    // the transaction's missing accounts, then the program data, each in a single request
+    let requests = rpc.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].0, "getMultipleAccounts");
+    assert!(requests[0].1.contains(&program_id));
+    assert!(!requests[0].1.contains(&payer));
+    assert_eq!(
+        requests[1],
+        (
+            "getMultipleAccounts".to_string(),
+            vec![get_program_data_address(&program_id)]
+        )
+    );
+
+    // the program is cached now
+    svm.expire_blockhash();
+    let tx = memo_tx(&mut svm, program_id);
+    svm.send_transaction(tx).unwrap();
+    assert_eq!(rpc.requests().len(), 2);
+}
+
+#[test]
+fn test_simulation_loads_fetched_programs() {
+    let program_id = Pubkey::new_unique();
+    let rpc = MockRpc::start(memo_program(program_id));
+    let mut svm = LiteSVM::new().with_account_source(rpc.source());
+
+    let tx = memo_tx(&mut svm, program_id);
+    svm.simulate_transaction(tx).unwrap();
+    assert!(rpc
+        .requests()
+        .iter()
+        .any(|(_, pubkeys)| pubkeys == &[get_program_data_address(&program_id)]));
+}
+
+#[cfg(feature = "rocksdb")]
+#[test]
+fn test_fetched_accounts_are_persisted() {
+    let remote = Pubkey::new_unique();
+    let rpc = MockRpc::start(HashMap::from([(remote, wallet(42))]));
+    let tmpdir = tempfile::tempdir().unwrap();
+    {
+        let svm = LiteSVM::open(tmpdir.path())
+            .unwrap()
+            .with_account_source(rpc.source());
+        assert_eq!(svm.get_balance(&remote), Some(42));
+    }
+    // later runs don't need the source
+    let svm = LiteSVM::open(tmpdir.path()).unwrap();
+    assert_eq!(svm.get_balance(&remote), Some(42));
+}
+
+#[test]
+fn test_removing_an_account_does_not_fetch_it() {
+    let remote = Pubkey::new_unique();
+    let rpc = MockRpc::start(HashMap::from([(remote, wallet(42))]));
+    let mut svm = LiteSVM::new().with_account_source(rpc.source());
+
+    svm.remove_account(&remote).unwrap();
+    assert_eq!(svm.get_account(&remote), None);
+    assert!(rpc.requests().is_empty());
+}
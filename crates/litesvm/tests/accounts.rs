@@ -0,0 +1,88 @@
+use {
+    litesvm::{storage::MemoryStore, LiteSVM},
+    solana_account::Account,
+    solana_pubkey::Pubkey,
+};
+
+#[test]
+fn test_accounts_lists_memory_and_store() {
+    let mut svm = LiteSVM::new()
+        .with_store(MemoryStore::new())
+        .with_account_cache_size(Some(1));
+    let owner = Pubkey::new_unique();
+    let pubkeys = (0..3).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+    for pubkey in &pubkeys {
+        svm.set_account(
+            *pubkey,
+            Account {
+                lamports: 1,
+                data: vec![],
+                owner,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .unwrap();
+    }
+
+    let accounts = svm
+        .accounts()
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    // evicted accounts are listed too
+    let owned = accounts
+        .iter()
+        .filter(|(_, account)| account.owner == owner)
+        .count();
+    assert_eq!(owned, 3);
+    // each account is listed once, whether it's held in memory, in the store or both
+    let mut pubkeys = accounts
+        .iter()
+        .map(|(pubkey, _)| *pubkey)
+        .collect::<Vec<_>>();
+    pubkeys.sort_unstable();
+    pubkeys.dedup();
+    assert_eq!(pubkeys.len(), accounts.len());
+    assert!(accounts
+        .iter()
+        .any(|(pubkey, _)| *pubkey == solana_sdk_ids::system_program::id()));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_export_accounts_json() {
+    let mut svm = LiteSVM::new();
+    let pubkey = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    svm.set_account(
+        pubkey,
+        Account {
+            lamports: 42,
+            data: vec![1, 2, 3],
+            owner,
+            executable: false,
+            rent_epoch: u64::MAX,
+        },
+    )
+    .unwrap();
+
+    let mut json = Vec::new();
+    svm.export_accounts_json(&mut json, |_, account| account.owner == owner)
+        .unwrap();
+    let exported: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(
+        exported,
+        serde_json::json!([{
+            "pubkey": pubkey.to_string(),
+            "account": {
+                "lamports": 42,
+                "data": ["AQID", "base64"],
+                "owner": owner.to_string(),
+                "executable": false,
+                "rentEpoch": u64::MAX,
+                "space": 3,
+            },
+        }])
+    );
+}
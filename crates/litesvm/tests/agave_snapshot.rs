@@ -0,0 +1,201 @@
+#![cfg(feature = "agave-snapshot")]
+
+use {
+    litesvm::{
+        error::LiteSVMError,
+        types::{SnapshotFilter, SnapshotInfo},
+        LiteSVM,
+    },
+    solana_account::{AccountSharedData, ReadableAccount},
+    solana_feature_gate_interface::Feature,
+    solana_instruction::Instruction,
+    solana_keypair::Keypair,
+    solana_loader_v3_interface::get_program_data_address,
+    solana_message::Message,
+    solana_pubkey::Pubkey,
+    solana_signer::Signer,
+    solana_transaction::Transaction,
+    std::{fs::File, path::Path},
+};
+
+/// Lays the accounts out like an Agave append vec.
+fn append_vec(accounts: &[(Pubkey, AccountSharedData)]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (write_version, (pubkey, account)) in accounts.iter().enumerate() {
+        bytes.extend((write_version as u64).to_le_bytes());
+        bytes.extend((account.data().len() as u64).to_le_bytes());
+        bytes.extend(pubkey.as_ref());
+        bytes.extend(account.lamports().to_le_bytes());
+        bytes.extend(account.rent_epoch().to_le_bytes());
+        bytes.extend(account.owner().as_ref());
+        bytes.push(account.executable() as u8);
+        bytes.extend([0; 7]);
+        // obsolete account hash
+        bytes.extend([0; 32]);
+        bytes.extend(account.data());
+        bytes.resize(bytes.len().next_multiple_of(8), 0);
+    }
+    // unused capacity at the end of the storage
+    bytes.extend([0; 256]);
+    bytes
+}
+
+fn write_archive(path: &Path, slot: u64, storages: &[(u64, Vec<(Pubkey, AccountSharedData)>)]) {
+    let encoder = zstd::Encoder::new(File::create(path).unwrap(), 0)
+        .unwrap()
+        .auto_finish();
+    let mut builder = tar::Builder::new(encoder);
+    let mut append = |name: &str, data: &[u8]| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, data).unwrap();
+    };
+    append("version", b"1.2.0");
+    append(&format!("snapshots/{slot}/{slot}"), b"bank fields");
+    append("snapshots/status_cache", b"status cache");
+    for (storage_slot, accounts) in storages {
+        append(&format!("accounts/{storage_slot}.0"), &append_vec(accounts));
+    }
+    builder.into_inner().unwrap();
+}
+
+fn account(lamports: u64, owner: Pubkey) -> AccountSharedData {
+    AccountSharedData::new(lamports, 8, &owner)
+}
+
+fn feature(activated_at: Option<u64>) -> AccountSharedData {
+    solana_feature_gate_interface::create_account(&Feature { activated_at }, 1_000_000)
+}
+
+#[test]
+fn test_import_full_and_incremental_snapshots() {
+    let dir = tempfile::tempdir().unwrap();
+    let owner = Pubkey::new_unique();
+    let [selected, owned, closed, unrelated, active_feature, pending_feature] =
+        std::array::from_fn(|_| Pubkey::new_unique());
+    let full = dir.path().join("snapshot-100-hash.tar.zst");
+    write_archive(
+        &full,
+        100,
+        &[(
+            90,
+            vec![
+                (selected, account(1, Pubkey::new_unique())),
+                (owned, account(10, owner)),
+                (closed, account(20, owner)),
+                (unrelated, account(30, Pubkey::new_unique())),
+                (active_feature, feature(Some(5))),
+                (pending_feature, feature(None)),
+            ],
+        )],
+    );
+    let incremental = dir.path().join("incremental-snapshot-100-150-hash.tar.zst");
+    write_archive(
+        &incremental,
+        150,
+        &[(
+            120,
+            vec![
+                (selected, account(2, Pubkey::new_unique())),
+                (closed, account(0, owner)),
+            ],
+        )],
+    );
+
+    let mut svm = LiteSVM::new();
+    svm.set_account(closed, account(20, owner).into()).unwrap();
+    let SnapshotInfo {
+        slot,
+        accounts,
+        feature_set,
+    } = svm
+        .import_snapshot(
+            &[&full, &incremental],
+            &SnapshotFilter::default()
+                .with_pubkeys([selected])
+                .with_owners([owner]),
+        )
+        .unwrap();
+
+    assert_eq!(slot, 150);
+    assert_eq!(accounts, 2);
+    assert_eq!(svm.get_balance(&selected), Some(2));
+    assert_eq!(svm.get_balance(&owned), Some(10));
+    assert_eq!(svm.get_account(&closed), None);
+    assert_eq!(svm.get_account(&unrelated), None);
+    // the feature accounts aren't imported unless selected, but they set the feature set
+    assert_eq!(svm.get_account(&active_feature), None);
+    assert_eq!(feature_set.activated_slot(&active_feature), Some(5));
+    assert!(!feature_set.is_active(&pending_feature));
+    assert!(svm.feature_set.is_active(&active_feature));
+    assert!(!svm
+        .feature_set
+        .is_active(&agave_feature_set::enable_loader_v4::id()));
+}
+
+#[test]
+fn test_import_snapshot_programs() {
+    // deploy the program elsewhere to get its accounts
+    let mut source = LiteSVM::new();
+    let program_id = Pubkey::new_unique();
+    let mut so_path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    so_path.push("src/spl/programs/spl_memo-3.0.0.so");
+    source.load_programs(&[(program_id, so_path)]).unwrap();
+    let program_accounts = [program_id, get_program_data_address(&program_id)]
+        .map(|pubkey| (pubkey, source.get_account(&pubkey).unwrap().into()));
+
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("snapshot-10-hash.tar.zst");
+    write_archive(&archive, 10, &[(10, program_accounts.to_vec())]);
+
+    let mut svm = LiteSVM::new();
+    svm.import_snapshot(
+        &[&archive],
+        &SnapshotFilter::default()
+            .with_owners([solana_sdk_ids::bpf_loader_upgradeable::id()])
+            .with_current_feature_set(),
+    )
+    .unwrap();
+
+    let payer = Keypair::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    let tx = Transaction::new(
+        &[&payer],
+        Message::new(
+            &[Instruction {
+                program_id,
+                accounts: vec![],
+                data: b"hello".to_vec(),
+            }],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    );
+    svm.send_transaction(tx).unwrap();
+}
+
+#[test]
+fn test_invalid_snapshot_archives() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut svm = LiteSVM::new();
+
+    let not_zstd = dir.path().join("not-zstd.tar.zst");
+    std::fs::write(&not_zstd, b"not a snapshot").unwrap();
+    let result = svm.import_snapshot(&[&not_zstd], &SnapshotFilter::all());
+    assert!(matches!(result, Err(LiteSVMError::InvalidSnapshot { path, .. }) if path == not_zstd));
+
+    let missing = dir.path().join("missing.tar.zst");
+    let result = svm.import_snapshot(&[&missing], &SnapshotFilter::all());
+    assert!(matches!(result, Err(LiteSVMError::Io(_))));
+
+    // an archive without a bank isn't a snapshot
+    let no_bank = dir.path().join("no-bank.tar.zst");
+    let encoder = zstd::Encoder::new(File::create(&no_bank).unwrap(), 0)
+        .unwrap()
+        .auto_finish();
+    tar::Builder::new(encoder).into_inner().unwrap();
+    let result = svm.import_snapshot(&[&no_bank], &SnapshotFilter::all());
+    assert!(matches!(result, Err(LiteSVMError::InvalidSnapshot { .. })));
+}
@@ -0,0 +1,108 @@
+use {
+    litesvm::LiteSVM,
+    solana_clock::Clock,
+    solana_epoch_schedule::EpochSchedule,
+    solana_keypair::Keypair,
+    solana_message::Message,
+    solana_pubkey::Pubkey,
+    solana_signer::Signer,
+    solana_slot_hashes::SlotHashes,
+    solana_slot_history::{Check, SlotHistory},
+    solana_system_interface::instruction::transfer,
+    solana_transaction::Transaction,
+    std::time::Duration,
+};
+
+fn transfer_tx(svm: &LiteSVM, payer: &Keypair, lamports: u64) -> Transaction {
+    Transaction::new(
+        &[payer],
+        Message::new(
+            &[transfer(&payer.pubkey(), &Pubkey::new_unique(), lamports)],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    )
+}
+
+#[test]
+fn test_produce_block_moves_the_sysvars_together() {
+    let mut svm = LiteSVM::new();
+    let start = svm.get_sysvar::<Clock>();
+    let blockhashes: Vec<_> = (0..3)
+        .map(|_| {
+            let blockhash = svm.latest_blockhash();
+            svm.produce_block().unwrap();
+            blockhash
+        })
+        .collect();
+
+    let clock = svm.get_sysvar::<Clock>();
+    assert_eq!(clock.slot, start.slot + 3);
+    // 3 slots of 400ms
+    assert_eq!(clock.unix_timestamp, start.unix_timestamp + 1);
+    assert!(!blockhashes.contains(&svm.latest_blockhash()));
+
+    let slot_hashes = svm.get_sysvar::<SlotHashes>();
+    for (slot, blockhash) in blockhashes.iter().enumerate() {
+        assert_eq!(slot_hashes.get(&(slot as u64)), Some(blockhash));
+    }
+    assert_eq!(slot_hashes.get(&3), None);
+
+    let slot_history = svm.get_sysvar::<SlotHistory>();
+    assert_eq!(slot_history.check(2), Check::Found);
+    assert_eq!(slot_history.check(3), Check::Future);
+
+    // blocks continue from a warped slot, with the slots in between skipped
+    svm.warp_to_slot(10);
+    svm.produce_block().unwrap();
+    assert_eq!(svm.get_sysvar::<Clock>().slot, 11);
+    let slot_history = svm.get_sysvar::<SlotHistory>();
+    assert_eq!(slot_history.check(5), Check::NotFound);
+    assert_eq!(slot_history.check(10), Check::Found);
+}
+
+#[test]
+fn test_produce_block_crosses_epochs() {
+    let mut svm = LiteSVM::new().with_slot_duration(Duration::from_secs(2));
+    svm.set_sysvar(&EpochSchedule::custom(32, 32, false));
+
+    for _ in 0..31 {
+        svm.produce_block().unwrap();
+    }
+    let clock = svm.get_sysvar::<Clock>();
+    assert_eq!((clock.slot, clock.epoch), (31, 0));
+    assert_eq!(clock.unix_timestamp, 62);
+    assert_eq!(clock.epoch_start_timestamp, 0);
+    assert_eq!(clock.leader_schedule_epoch, 1);
+
+    svm.produce_block().unwrap();
+    let clock = svm.get_sysvar::<Clock>();
+    assert_eq!((clock.slot, clock.epoch), (32, 1));
+    assert_eq!(clock.unix_timestamp, 64);
+    assert_eq!(clock.epoch_start_timestamp, 64);
+    assert_eq!(clock.leader_schedule_epoch, 2);
+}
+
+#[test]
+fn test_auto_produce_blocks() {
+    let mut svm = LiteSVM::new().with_auto_produce_blocks(true);
+    let payer = Keypair::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+
+    let blockhash = svm.latest_blockhash();
+    svm.send_transaction(transfer_tx(&svm, &payer, 1_000_000))
+        .unwrap();
+    assert_eq!(svm.get_sysvar::<Clock>().slot, 1);
+    assert_ne!(svm.latest_blockhash(), blockhash);
+
+    // failed transactions that pay fees are committed in a block too
+    svm.send_transaction(transfer_tx(&svm, &payer, u64::MAX))
+        .unwrap_err();
+    assert_eq!(svm.get_sysvar::<Clock>().slot, 2);
+
+    // but rejected ones aren't
+    let unfunded = Keypair::new();
+    svm.send_transaction(transfer_tx(&svm, &unfunded, 1))
+        .unwrap_err();
+    assert_eq!(svm.get_sysvar::<Clock>().slot, 2);
+}
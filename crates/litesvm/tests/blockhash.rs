@@ -84,3 +84,73 @@ fn test_durable_nonce() {
     svm.expire_blockhash();
     svm.send_transaction(tx_using_nonce).unwrap();
 }
+
+fn transfer_tx(payer: &Keypair, blockhash: solana_hash::Hash) -> Transaction {
+    Transaction::new(
+        &[payer],
+        Message::new(
+            &[transfer(&payer.pubkey(), &Pubkey::new_unique(), 1_000_000)],
+            Some(&payer.pubkey()),
+        ),
+        blockhash,
+    )
+}
+
+#[test_log::test]
+fn test_recent_blockhashes_expire_with_age() {
+    let mut svm = LiteSVM::new().with_max_blockhash_age(2);
+    let payer = Keypair::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+
+    let (blockhash, last_valid_block_height) =
+        svm.get_latest_blockhash_with_last_valid_block_height();
+    assert_eq!(svm.get_block_height(), 0);
+    assert_eq!(last_valid_block_height, 2);
+
+    svm.produce_block().unwrap();
+    svm.produce_block().unwrap();
+    assert_eq!(svm.get_block_height(), 2);
+    assert!(svm.is_blockhash_valid(&blockhash));
+    svm.send_transaction(transfer_tx(&payer, blockhash))
+        .unwrap();
+
+    svm.produce_block().unwrap();
+    assert!(!svm.is_blockhash_valid(&blockhash));
+    let err = svm
+        .send_transaction(transfer_tx(&payer, blockhash))
+        .unwrap_err()
+        .err;
+    assert_eq!(err, TransactionError::BlockhashNotFound);
+
+    // the sysvar lists the valid blockhashes, newest first
+    #[allow(deprecated)]
+    {
+        use solana_sysvar::recent_blockhashes::RecentBlockhashes;
+        let recent_blockhashes = svm.get_sysvar::<RecentBlockhashes>();
+        assert_eq!(recent_blockhashes.len(), 3);
+        assert_eq!(recent_blockhashes[0].blockhash, svm.latest_blockhash());
+    }
+}
+
+#[test_log::test]
+fn test_expire_blockhash_expires_every_recent_blockhash() {
+    let mut svm = LiteSVM::new();
+    let payer = Keypair::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    let first = svm.latest_blockhash();
+    svm.produce_block().unwrap();
+    let second = svm.latest_blockhash();
+    assert!(svm.is_blockhash_valid(&first));
+
+    svm.expire_blockhash();
+    assert_eq!(svm.get_block_height(), 2);
+    assert!(!svm.is_blockhash_valid(&first));
+    assert!(!svm.is_blockhash_valid(&second));
+    let err = svm
+        .send_transaction(transfer_tx(&payer, second))
+        .unwrap_err()
+        .err;
+    assert_eq!(err, TransactionError::BlockhashNotFound);
+    svm.send_transaction(transfer_tx(&payer, svm.latest_blockhash()))
+        .unwrap();
+}
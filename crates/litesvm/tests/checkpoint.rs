@@ -0,0 +1,110 @@
+use {
+    litesvm::{error::LiteSVMError, LiteSVM},
+    solana_keypair::Keypair,
+    solana_message::Message,
+    solana_pubkey::Pubkey,
+    solana_signer::Signer,
+    solana_system_interface::instruction::transfer,
+    solana_transaction::Transaction,
+};
+
+fn transfer_tx(svm: &LiteSVM, payer: &Keypair, to: &Pubkey, lamports: u64) -> Transaction {
+    Transaction::new(
+        &[payer],
+        Message::new(
+            &[transfer(&payer.pubkey(), to, lamports)],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    )
+}
+
+#[test]
+fn test_revert_restores_accounts_blockhash_and_history() {
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+    let mut svm = LiteSVM::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+
+    let checkpoint = svm.checkpoint();
+    let blockhash = svm.latest_blockhash();
+    let payer_balance = svm.get_balance(&payer.pubkey());
+
+    // try a few different paths from the same starting point
+    for lamports in [100, 200, 300] {
+        let tx = transfer_tx(&svm, &payer, &to, lamports);
+        let signature = tx.signatures[0];
+        svm.send_transaction(tx).unwrap();
+        svm.expire_blockhash();
+        assert_eq!(svm.get_balance(&to), Some(lamports));
+        assert!(svm.get_transaction(&signature).is_some());
+
+        svm.revert(checkpoint).unwrap();
+        assert_eq!(svm.get_balance(&to), None);
+        assert_eq!(svm.get_balance(&payer.pubkey()), payer_balance);
+        assert_eq!(svm.latest_blockhash(), blockhash);
+        assert!(svm.get_transaction(&signature).is_none());
+    }
+}
+
+#[test]
+fn test_revert_restores_sysvars() {
+    let mut svm = LiteSVM::new();
+    let checkpoint = svm.checkpoint();
+    svm.warp_to_slot(1_000);
+    assert_eq!(svm.get_sysvar::<solana_clock::Clock>().slot, 1_000);
+
+    svm.revert(checkpoint).unwrap();
+    assert_eq!(svm.get_sysvar::<solana_clock::Clock>().slot, 0);
+}
+
+#[test]
+fn test_nested_checkpoints() {
+    let user = Pubkey::new_unique();
+    let mut svm = LiteSVM::new();
+
+    let outer = svm.checkpoint();
+    svm.airdrop(&user, 1).unwrap();
+    let inner = svm.checkpoint();
+    svm.airdrop(&user, 2).unwrap();
+
+    svm.revert(inner).unwrap();
+    assert_eq!(svm.get_balance(&user), Some(1));
+    svm.revert(outer).unwrap();
+    assert_eq!(svm.get_balance(&user), None);
+
+    // reverting to `outer` discarded the checkpoints taken after it
+    assert!(matches!(
+        svm.revert(inner),
+        Err(LiteSVMError::CheckpointNotFound(id)) if id == inner
+    ));
+
+    svm.clear_checkpoints();
+    assert!(svm.revert(outer).is_err());
+}
+
+#[test]
+fn test_revert_keeps_history_from_before_checkpoint() {
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+    let mut svm = LiteSVM::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    let before = transfer_tx(&svm, &payer, &to, 100);
+    svm.send_transaction(before.clone()).unwrap();
+
+    let outer = svm.checkpoint();
+    let after = transfer_tx(&svm, &payer, &to, 200);
+    svm.send_transaction(after.clone()).unwrap();
+    let inner = svm.checkpoint();
+    svm.send_transaction(transfer_tx(&svm, &payer, &to, 300))
+        .unwrap();
+
+    svm.revert(inner).unwrap();
+    assert!(svm.get_transaction(&after.signatures[0]).is_some());
+    svm.revert(outer).unwrap();
+    assert!(svm.get_transaction(&before.signatures[0]).is_some());
+    assert!(svm.get_transaction(&after.signatures[0]).is_none());
+    // the reverted transaction can be sent again
+    svm.send_transaction(after).unwrap();
+    assert_eq!(svm.get_balance(&to), Some(300));
+}
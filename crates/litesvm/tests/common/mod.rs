@@ -0,0 +1,78 @@
+//! Helpers shared by the integration tests. Each test file only uses some of them.
+#![allow(dead_code)]
+
+use {
+    litesvm::LiteSVM,
+    solana_account::Account,
+    solana_clock::Clock,
+    solana_native_token::LAMPORTS_PER_SOL,
+    solana_pubkey::Pubkey,
+    solana_stake_interface::{
+        stake_flags::StakeFlags,
+        state::{Authorized, Delegation, Meta, Stake, StakeStateV2},
+    },
+    solana_vote_program::vote_state::{self, VoteInit, VoteState, VoteStateVersions},
+};
+
+/// A stake large enough for others to warm up against, like the stake of a genesis validator.
+pub const BOOTSTRAP_STAKE: u64 = 1_000_000 * LAMPORTS_PER_SOL;
+
+pub fn add_vote_account(svm: &mut LiteSVM, commission: u8) -> Pubkey {
+    let pubkey = Pubkey::new_unique();
+    let vote_state = VoteState::new(
+        &VoteInit {
+            node_pubkey: Pubkey::new_unique(),
+            authorized_voter: Pubkey::new_unique(),
+            authorized_withdrawer: Pubkey::new_unique(),
+            commission,
+        },
+        &svm.get_sysvar::<Clock>(),
+    );
+    let mut account = Account::new(
+        svm.minimum_balance_for_rent_exemption(VoteState::size_of()),
+        VoteState::size_of(),
+        &solana_sdk_ids::vote::id(),
+    );
+    vote_state::to(&VoteStateVersions::new_current(vote_state), &mut account).unwrap();
+    svm.set_account(pubkey, account).unwrap();
+    pubkey
+}
+
+pub fn add_vote_credits(svm: &mut LiteSVM, vote_pubkey: Pubkey, credits: u64) {
+    let mut account = svm.get_account(&vote_pubkey).unwrap();
+    let mut vote_state = vote_state::from(&account).unwrap();
+    let epoch = svm.get_sysvar::<Clock>().epoch;
+    for _ in 0..credits {
+        vote_state.increment_credits(epoch, 1);
+    }
+    vote_state::to(&VoteStateVersions::new_current(vote_state), &mut account).unwrap();
+    svm.set_account(vote_pubkey, account).unwrap();
+}
+
+pub fn add_stake_account(svm: &mut LiteSVM, staker: &Pubkey, delegation: Delegation) -> Pubkey {
+    let pubkey = Pubkey::new_unique();
+    let rent_exempt_reserve = svm.minimum_balance_for_rent_exemption(StakeStateV2::size_of());
+    let state = StakeStateV2::Stake(
+        Meta {
+            rent_exempt_reserve,
+            authorized: Authorized::auto(staker),
+            ..Meta::default()
+        },
+        Stake {
+            delegation,
+            credits_observed: 0,
+        },
+        StakeFlags::empty(),
+    );
+    let mut data = vec![0; StakeStateV2::size_of()];
+    bincode::serialize_into(&mut data[..], &state).unwrap();
+    let account = Account {
+        lamports: rent_exempt_reserve + delegation.stake,
+        data,
+        owner: solana_sdk_ids::stake::id(),
+        executable: false,
+        rent_epoch: 0,
+    };
+    svm.set_account(pubkey, account).unwrap();
+    pubkey
+}
@@ -1,19 +1,15 @@
 use {
-    litesvm::{storage::RocksDBStore, LiteSVM},
     serial_test::serial,
-    solana_account::{Account, ReadableAccount},
+    solana_account::Account,
     solana_instruction::{account_meta::AccountMeta, Instruction},
     solana_keypair::Keypair,
-    solana_message::{Message, VersionedMessage as VMsg},
-    solana_native_token::LAMPORTS_PER_SOL,
-    solana_pubkey::{pubkey, Pubkey},
+    solana_message::Message,
+    solana_pubkey::Pubkey,
     solana_rent::Rent,
     solana_signature::Signature,
     solana_signer::Signer,
-    solana_system_interface::instruction::transfer,
-    solana_transaction::{versioned::VersionedTransaction, Transaction},
+    solana_transaction::Transaction,
     std::path::PathBuf,
-    tempfile::TempDir,
 };
 
 const NUM_GREETINGS: u8 = 127;
@@ -113,82 +109,6 @@ async fn do_program_test(program_id: Pubkey, counter_address: Pubkey) {
     assert_eq!(fetched, NUM_GREETINGS);
 }
 
-#[test]
-#[serial]
-fn test_rocksdb_persistence_via_direct_store() {
-    // 1) 用 TempDir 自动管理生命周期
-    let tmpdir = TempDir::new().expect("create tempdir");
-    let path = tmpdir.path();
-
-    // ——— 写阶段 ———
-    {
-        let mut svm = LiteSVM::new_with_db_path(path)
-            .with_builtins()
-            .with_lamports(1_000_000 * LAMPORTS_PER_SOL)
-            .with_sysvars()
-            .with_spl_programs();
-        let payer = Keypair::new();
-        let key = pubkey!("J39wvrFY2AkoAUCke5347RMNk3ditxZfVidoZ7U6Fguf");
-        let program = pubkey!("GtdambwDgHWrDJdVPBkEHGhCwokqgAoch162teUjJse2");
-        svm.airdrop(&payer.pubkey(), 1_000).unwrap();
-        svm.set_account(
-            key,
-            Account {
-                lamports: 7,
-                data: (123u32).to_le_bytes().to_vec(),
-                owner: program,
-                ..Default::default()
-            },
-        )
-        .unwrap();
-
-        // 触发一次写入
-        let tx = VersionedTransaction::try_new(
-            VMsg::Legacy(Message::new_with_blockhash(
-                &[transfer(&payer.pubkey(), &key, 0)],
-                Some(&payer.pubkey()),
-                &svm.latest_blockhash(),
-            )),
-            &[&payer],
-        )
-        .unwrap();
-        let _ = svm.send_transaction(tx);
-        // drop(svm) 释放文件锁
-    }
-
-    // ——— 读阶段 ———
-    {
-        {
-            // a) 通过 LiteSVM 打开
-            let svm2 = LiteSVM::new_with_db_path(path)
-                .with_builtins()
-                .with_lamports(1_000_000 * LAMPORTS_PER_SOL)
-                .with_sysvars()
-                .with_spl_programs();
-            let acc = svm2
-                .get_account(&pubkey!("J39wvrFY2AkoAUCke5347RMNk3ditxZfVidoZ7U6Fguf"))
-                .unwrap();
-            // **注意**：用方法访问
-            assert_eq!(acc.lamports(), 7);
-            assert_eq!(u32::from_le_bytes(acc.data()[..4].try_into().unwrap()), 123);
-            drop(svm2);
-        }
-
-        // b) 直接打开底层 RocksDBStore 再验证一次
-        let store = RocksDBStore::open(path).expect("open store");
-        let acc2 = store
-            .get_account(&pubkey!("J39wvrFY2AkoAUCke5347RMNk3ditxZfVidoZ7U6Fguf"))
-            .unwrap()
-            .unwrap();
-        assert_eq!(acc2.lamports(), 7);
-        assert_eq!(
-            u32::from_le_bytes(acc2.data()[..4].try_into().unwrap()),
-            123
-        );
-    }
-    // TempDir 离开作用域自动删除
-}
-
 // #[test]
 // #[serial]
 // fn test_rocksdb_persistence_via_direct_store() {
@@ -0,0 +1,354 @@
+mod common;
+
+use {
+    common::{add_stake_account, add_vote_account, add_vote_credits, BOOTSTRAP_STAKE},
+    litesvm::{storage::MemoryStore, LiteSVM},
+    solana_clock::Clock,
+    solana_epoch_rewards::EpochRewards,
+    solana_epoch_schedule::EpochSchedule,
+    solana_inflation::Inflation,
+    solana_instruction::{error::InstructionError, Instruction},
+    solana_keypair::Keypair,
+    solana_native_token::LAMPORTS_PER_SOL,
+    solana_program_runtime::declare_process_instruction,
+    solana_pubkey::Pubkey,
+    solana_signer::Signer,
+    solana_stake_interface::{
+        error::StakeError,
+        instruction as stake_instruction,
+        stake_history::{StakeHistory, StakeHistoryEntry},
+        state::{Delegation, Stake, StakeStateV2},
+    },
+    solana_transaction::Transaction,
+    solana_transaction_error::TransactionError,
+    std::sync::Arc,
+};
+
+fn get_stake(svm: &LiteSVM, pubkey: &Pubkey) -> Stake {
+    let account = svm.get_account(pubkey).unwrap();
+    bincode::deserialize::<StakeStateV2>(&account.data)
+        .unwrap()
+        .stake()
+        .unwrap()
+}
+
+#[test]
+fn test_warp_to_epoch_warms_up_and_cools_down_stake() {
+    let mut svm = LiteSVM::new();
+    svm.set_sysvar(&EpochSchedule::without_warmup());
+    let vote_pubkey = add_vote_account(&mut svm, 0);
+    let staker = Keypair::new();
+    svm.airdrop(&staker.pubkey(), LAMPORTS_PER_SOL).unwrap();
+    // a genesis stake, effective from the start, for the new stake to warm up against
+    add_stake_account(
+        &mut svm,
+        &Pubkey::new_unique(),
+        Delegation::new(&vote_pubkey, BOOTSTRAP_STAKE, u64::MAX),
+    );
+    let stake = 10 * LAMPORTS_PER_SOL;
+    let stake_pubkey = add_stake_account(
+        &mut svm,
+        &staker.pubkey(),
+        Delegation::new(&vote_pubkey, stake, 0),
+    );
+
+    svm.warp_to_epoch(1).unwrap();
+    let clock = svm.get_sysvar::<Clock>();
+    assert_eq!((clock.slot, clock.epoch), (432_000, 1));
+    assert_eq!(clock.leader_schedule_epoch, 2);
+    assert_eq!(
+        svm.get_sysvar::<StakeHistory>().get(0),
+        Some(&StakeHistoryEntry::with_effective_and_activating(
+            BOOTSTRAP_STAKE,
+            stake
+        ))
+    );
+
+    let tx = Transaction::new_signed_with_payer(
+        &[stake_instruction::deactivate_stake(
+            &stake_pubkey,
+            &staker.pubkey(),
+        )],
+        Some(&staker.pubkey()),
+        &[&staker],
+        svm.latest_blockhash(),
+    );
+    svm.send_transaction(tx).unwrap();
+
+    svm.warp_to_epoch(3).unwrap();
+    let stake_history = svm.get_sysvar::<StakeHistory>();
+    // fully effective at the start of epoch 1, then deactivating during it
+    assert_eq!(
+        stake_history.get(1),
+        Some(&StakeHistoryEntry {
+            effective: BOOTSTRAP_STAKE + stake,
+            activating: 0,
+            deactivating: stake,
+        })
+    );
+    assert_eq!(
+        stake_history.get(2),
+        Some(&StakeHistoryEntry::with_effective(BOOTSTRAP_STAKE))
+    );
+    // newest first, as on a cluster
+    assert_eq!(stake_history.first().map(|(epoch, _)| *epoch), Some(2));
+}
+
+#[test]
+fn test_partitioned_epoch_rewards() {
+    let mut svm = LiteSVM::new().with_inflation(Inflation::default());
+    svm.set_sysvar(&EpochSchedule::without_warmup());
+    let vote_pubkey = add_vote_account(&mut svm, 10);
+    let staker = Keypair::new();
+    svm.airdrop(&staker.pubkey(), LAMPORTS_PER_SOL).unwrap();
+    let stake_pubkey = add_stake_account(
+        &mut svm,
+        &staker.pubkey(),
+        Delegation::new(&vote_pubkey, BOOTSTRAP_STAKE, u64::MAX),
+    );
+    // too small to be rewarded
+    let small_stake_pubkey = add_stake_account(
+        &mut svm,
+        &staker.pubkey(),
+        Delegation::new(&vote_pubkey, LAMPORTS_PER_SOL / 2, u64::MAX),
+    );
+    add_vote_credits(&mut svm, vote_pubkey, 100);
+    let vote_balance = svm.get_balance(&vote_pubkey).unwrap();
+    let stake_balance = svm.get_balance(&stake_pubkey).unwrap();
+    let small_stake_balance = svm.get_balance(&small_stake_pubkey).unwrap();
+
+    svm.warp_to_epoch(1).unwrap();
+    let epoch_rewards = svm.get_sysvar::<EpochRewards>();
+    assert!(epoch_rewards.active);
+    assert!(epoch_rewards.total_rewards > 0);
+    assert_eq!(epoch_rewards.num_partitions, 1);
+    assert_eq!(
+        epoch_rewards.distribution_starting_block_height,
+        svm.get_block_height() + 1
+    );
+    assert_eq!(
+        epoch_rewards.total_points,
+        u128::from(BOOTSTRAP_STAKE) * 100
+    );
+    // the vote account's commission is paid right away
+    let voter_rewards = epoch_rewards.total_rewards / 10;
+    assert_eq!(epoch_rewards.distributed_rewards, voter_rewards);
+    assert_eq!(
+        svm.get_balance(&vote_pubkey),
+        Some(vote_balance + voter_rewards)
+    );
+    assert_eq!(svm.get_balance(&stake_pubkey), Some(stake_balance));
+
+    // the stake program is locked while rewards are being paid
+    let tx = Transaction::new_signed_with_payer(
+        &[stake_instruction::deactivate_stake(
+            &stake_pubkey,
+            &staker.pubkey(),
+        )],
+        Some(&staker.pubkey()),
+        &[&staker],
+        svm.latest_blockhash(),
+    );
+    let err = svm.send_transaction(tx).unwrap_err().err;
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakeError::EpochRewardsActive as u32)
+        )
+    );
+
+    svm.produce_block().unwrap();
+    let staker_rewards = epoch_rewards.total_rewards * 9 / 10;
+    let epoch_rewards = svm.get_sysvar::<EpochRewards>();
+    assert!(!epoch_rewards.active);
+    assert_eq!(
+        epoch_rewards.distributed_rewards,
+        voter_rewards + staker_rewards
+    );
+    assert_eq!(
+        svm.get_balance(&stake_pubkey),
+        Some(stake_balance + staker_rewards)
+    );
+    let stake = get_stake(&svm, &stake_pubkey);
+    assert_eq!(stake.delegation.stake, BOOTSTRAP_STAKE + staker_rewards);
+    assert_eq!(stake.credits_observed, 100);
+    assert_eq!(
+        svm.get_balance(&small_stake_pubkey),
+        Some(small_stake_balance)
+    );
+
+    // no new credits, no new rewards
+    svm.warp_to_epoch(2).unwrap();
+    assert_eq!(svm.get_sysvar::<EpochRewards>().total_rewards, 0);
+    svm.produce_block().unwrap();
+    assert_eq!(
+        svm.get_balance(&stake_pubkey),
+        Some(stake_balance + staker_rewards)
+    );
+}
+
+#[test]
+fn test_produce_block_crosses_into_epoch_rewards() {
+    let mut svm = LiteSVM::new().with_inflation(Inflation::default());
+    svm.set_sysvar(&EpochSchedule::custom(32, 32, false));
+    let vote_pubkey = add_vote_account(&mut svm, 0);
+    let stake_pubkey = add_stake_account(
+        &mut svm,
+        &Pubkey::new_unique(),
+        Delegation::new(&vote_pubkey, BOOTSTRAP_STAKE, u64::MAX),
+    );
+    add_vote_credits(&mut svm, vote_pubkey, 10);
+    let stake_balance = svm.get_balance(&stake_pubkey).unwrap();
+
+    for _ in 0..32 {
+        svm.produce_block().unwrap();
+    }
+    assert_eq!(svm.get_sysvar::<Clock>().epoch, 1);
+    assert!(svm.get_sysvar::<StakeHistory>().get(0).is_some());
+    let epoch_rewards = svm.get_sysvar::<EpochRewards>();
+    assert!(epoch_rewards.active);
+
+    svm.produce_block().unwrap();
+    assert!(!svm.get_sysvar::<EpochRewards>().active);
+    assert_eq!(
+        svm.get_balance(&stake_pubkey),
+        Some(stake_balance + epoch_rewards.total_rewards)
+    );
+}
+
+#[test]
+fn test_reopening_mid_distribution_pays_each_reward_once() {
+    let store = Arc::new(MemoryStore::new());
+    let (stake_pubkey, stake_balance, epoch_rewards) = {
+        let mut svm = LiteSVM::open_store(store.clone())
+            .unwrap()
+            .with_inflation(Inflation::default());
+        svm.set_sysvar(&EpochSchedule::without_warmup());
+        let vote_pubkey = add_vote_account(&mut svm, 0);
+        let stake_pubkey = add_stake_account(
+            &mut svm,
+            &Pubkey::new_unique(),
+            Delegation::new(&vote_pubkey, BOOTSTRAP_STAKE, u64::MAX),
+        );
+        add_vote_credits(&mut svm, vote_pubkey, 10);
+        svm.save_metadata().unwrap();
+        let stake_balance = svm.get_balance(&stake_pubkey).unwrap();
+        svm.warp_to_epoch(1).unwrap();
+        (
+            stake_pubkey,
+            stake_balance,
+            svm.get_sysvar::<EpochRewards>(),
+        )
+    };
+    assert!(epoch_rewards.active);
+
+    // no save_metadata, yet the rewards still to be paid were stored with the sysvar
+    let staker_rewards = epoch_rewards.total_rewards - epoch_rewards.distributed_rewards;
+    {
+        let mut svm = LiteSVM::open_store(store.clone()).unwrap();
+        assert!(svm.get_sysvar::<EpochRewards>().active);
+        svm.produce_block().unwrap();
+        assert!(!svm.get_sysvar::<EpochRewards>().active);
+        assert_eq!(
+            svm.get_balance(&stake_pubkey),
+            Some(stake_balance + staker_rewards)
+        );
+    }
+
+    // and once paid, they aren't paid again
+    let mut svm = LiteSVM::open_store(store).unwrap();
+    svm.produce_block().unwrap();
+    assert_eq!(
+        svm.get_balance(&stake_pubkey),
+        Some(stake_balance + staker_rewards)
+    );
+}
+
+/// A vote account for the builtin below to read the stake of.
+const VOTE_PUBKEY: Pubkey = Pubkey::new_from_array([7; 32]);
+
+// returns the total epoch stake, or the stake of `VOTE_PUBKEY` if the instruction has data
+declare_process_instruction!(EpochStakeProgram, 150, |invoke_context| {
+    let instruction_context = invoke_context
+        .transaction_context
+        .get_current_instruction_context()?;
+    let program_id =
+        *instruction_context.get_last_program_key(invoke_context.transaction_context)?;
+    let stake = if instruction_context.get_instruction_data().is_empty() {
+        invoke_context.get_epoch_stake()
+    } else {
+        invoke_context.get_epoch_stake_for_vote_account(&VOTE_PUBKEY)
+    };
+    invoke_context
+        .transaction_context
+        .set_return_data(program_id, stake.to_le_bytes().to_vec())
+});
+
+fn read_epoch_stake(svm: &LiteSVM, program_id: Pubkey, data: Vec<u8>) -> u64 {
+    let payer = Keypair::new();
+    let tx = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![],
+            data,
+        }],
+        Some(&payer.pubkey()),
+        &[&payer],
+        svm.latest_blockhash(),
+    );
+    let mut svm = svm.clone();
+    svm.airdrop(&payer.pubkey(), LAMPORTS_PER_SOL).unwrap();
+    let return_data = svm.simulate_transaction(tx).unwrap().meta.return_data;
+    u64::from_le_bytes(return_data.data.try_into().unwrap())
+}
+
+#[test]
+fn test_epoch_stakes_are_taken_at_epoch_boundaries() {
+    let mut svm = LiteSVM::new();
+    svm.set_sysvar(&EpochSchedule::without_warmup());
+    let vote_pubkey = add_vote_account(&mut svm, 0);
+    add_stake_account(
+        &mut svm,
+        &Pubkey::new_unique(),
+        Delegation::new(&vote_pubkey, BOOTSTRAP_STAKE, u64::MAX),
+    );
+    let stake = 10 * LAMPORTS_PER_SOL;
+    add_stake_account(
+        &mut svm,
+        &Pubkey::new_unique(),
+        Delegation::new(&vote_pubkey, stake, 0),
+    );
+    // only stake delegated to vote accounts counts
+    add_stake_account(
+        &mut svm,
+        &Pubkey::new_unique(),
+        Delegation::new(&Pubkey::new_unique(), stake, u64::MAX),
+    );
+    assert_eq!(svm.get_total_epoch_stake(), 0);
+
+    svm.warp_to_epoch(1).unwrap();
+    assert_eq!(svm.get_epoch_stake(&vote_pubkey), BOOTSTRAP_STAKE + stake);
+    assert_eq!(svm.get_total_epoch_stake(), BOOTSTRAP_STAKE + stake);
+
+    // set stakes last until the next epoch boundary
+    let other_vote_pubkey = Pubkey::new_unique();
+    svm.set_epoch_stake(other_vote_pubkey, stake);
+    assert_eq!(svm.get_total_epoch_stake(), BOOTSTRAP_STAKE + 2 * stake);
+    svm.warp_to_epoch(2).unwrap();
+    assert_eq!(svm.get_epoch_stake(&other_vote_pubkey), 0);
+    assert_eq!(svm.get_total_epoch_stake(), BOOTSTRAP_STAKE + stake);
+}
+
+#[test]
+fn test_programs_read_the_epoch_stakes() {
+    let mut svm = LiteSVM::new();
+    let program_id = Pubkey::new_unique();
+    svm.add_builtin(program_id, EpochStakeProgram::vm);
+    assert_eq!(read_epoch_stake(&svm, program_id, vec![]), 0);
+
+    svm.set_epoch_stake(VOTE_PUBKEY, 1_000);
+    svm.set_epoch_stake(Pubkey::new_unique(), 500);
+    assert_eq!(read_epoch_stake(&svm, program_id, vec![]), 1_500);
+    assert_eq!(read_epoch_stake(&svm, program_id, vec![1]), 1_000);
+}
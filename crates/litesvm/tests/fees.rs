@@ -1,5 +1,7 @@
 use {
-    litesvm::LiteSVM,
+    litesvm::{error::LiteSVMError, types::FeeBreakdown, LiteSVM},
+    solana_compute_budget_interface::ComputeBudgetInstruction,
+    solana_fee_structure::FeeStructure,
     solana_instruction::{error::InstructionError, Instruction},
     solana_keypair::Keypair,
     solana_message::Message,
@@ -72,3 +74,143 @@ fn test_fees_failed_transaction() {
     assert_eq!(initial_balance - balance_after, expected_fee);
     assert!(svm.get_transaction(&signature).unwrap().is_err());
 }
+
+fn transfer_tx(svm: &LiteSVM, payer: &Keypair, lamports: u64, micro_lamports: u64) -> Transaction {
+    Transaction::new(
+        &[payer],
+        Message::new(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(100_000),
+                ComputeBudgetInstruction::set_compute_unit_price(micro_lamports),
+                transfer(&payer.pubkey(), &Pubkey::new_unique(), lamports),
+            ],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    )
+}
+
+#[test]
+fn test_prioritization_fee() {
+    let mut svm = LiteSVM::new();
+    let payer = Keypair::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+
+    // 100_000 compute units at 3 lamports each
+    let meta = svm
+        .send_transaction(transfer_tx(&svm, &payer, 1_000, 3_000_000))
+        .unwrap();
+    assert_eq!(
+        meta.fees,
+        FeeBreakdown {
+            transaction_fee: 5_000,
+            prioritization_fee: 300_000,
+            burned: 305_000,
+            collected: 0,
+        }
+    );
+    assert_eq!(
+        svm.get_balance(&payer.pubkey()),
+        Some(1_000_000_000 - 1_000 - meta.fees.total())
+    );
+
+    // the simulation reports the same fees
+    let tx = transfer_tx(&svm, &payer, 2_000, 3_000_000);
+    assert_eq!(svm.simulate_transaction(tx).unwrap().meta.fees, meta.fees);
+}
+
+#[test]
+fn test_burn_percent_over_100_is_rejected() {
+    assert!(matches!(
+        LiteSVM::new().try_with_fee_burn_percent(101),
+        Err(LiteSVMError::InvalidBurnPercent(101))
+    ));
+    assert!(LiteSVM::new().try_with_fee_burn_percent(100).is_ok());
+}
+
+#[test]
+fn test_fee_collector() {
+    let collector = Pubkey::new_unique();
+    let mut svm = LiteSVM::new()
+        .with_fee_structure(FeeStructure {
+            lamports_per_signature: 10_000,
+            ..FeeStructure::default()
+        })
+        .with_fee_collector(collector)
+        .with_fee_burn_percent(20);
+    let payer = Keypair::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+
+    // the collector isn't rent exempt yet, so everything is burned
+    let meta = svm
+        .send_transaction(transfer_tx(&svm, &payer, 1_000, 1_000_000))
+        .unwrap();
+    assert_eq!(meta.fees.total(), 110_000);
+    assert_eq!((meta.fees.burned, meta.fees.collected), (110_000, 0));
+    assert_eq!(svm.get_balance(&collector), None);
+
+    let rent_exempt_balance = svm.get_sysvar::<Rent>().minimum_balance(0);
+    svm.airdrop(&collector, rent_exempt_balance).unwrap();
+    let meta = svm
+        .send_transaction(transfer_tx(&svm, &payer, 1_000, 1_000_000))
+        .unwrap();
+    assert_eq!((meta.fees.burned, meta.fees.collected), (2_000, 108_000));
+    assert_eq!(
+        svm.get_balance(&collector),
+        Some(rent_exempt_balance + 108_000)
+    );
+
+    // failed transactions pay the collector too
+    let failed = svm
+        .send_transaction(transfer_tx(&svm, &payer, u64::MAX, 1_000_000))
+        .unwrap_err();
+    assert_eq!(failed.meta.fees, meta.fees);
+    assert_eq!(
+        svm.get_balance(&collector),
+        Some(rent_exempt_balance + 216_000)
+    );
+
+    // and so do transactions signed by the collector
+    let collector_keypair = Keypair::new();
+    let mut svm = LiteSVM::new().with_fee_collector(collector_keypair.pubkey());
+    svm.airdrop(&collector_keypair.pubkey(), 1_000_000_000)
+        .unwrap();
+    let meta = svm
+        .send_transaction(transfer_tx(&svm, &collector_keypair, 1_000, 0))
+        .unwrap();
+    assert_eq!((meta.fees.burned, meta.fees.collected), (2_500, 2_500));
+    assert_eq!(
+        svm.get_balance(&collector_keypair.pubkey()),
+        Some(1_000_000_000 - 1_000 - 2_500)
+    );
+}
+
+#[test]
+fn test_simulation_doesnt_pay_the_collector() {
+    let collector = Pubkey::new_unique();
+    let mut svm = LiteSVM::new().with_fee_collector(collector);
+    let payer = Keypair::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    svm.airdrop(&collector, 1_000_000_000).unwrap();
+
+    let simulated = svm
+        .simulate_transaction(transfer_tx(&svm, &payer, 1_000, 0))
+        .unwrap();
+    // the fees are reported as they would be distributed
+    assert_eq!(
+        (simulated.meta.fees.burned, simulated.meta.fees.collected),
+        (2_500, 2_500)
+    );
+    assert!(simulated
+        .post_accounts
+        .iter()
+        .all(|(pubkey, _)| pubkey != &collector));
+    assert_eq!(svm.get_balance(&collector), Some(1_000_000_000));
+
+    // sending it does pay the collector
+    let meta = svm
+        .send_transaction(transfer_tx(&svm, &payer, 1_000, 0))
+        .unwrap();
+    assert_eq!(meta.fees, simulated.meta.fees);
+    assert_eq!(svm.get_balance(&collector), Some(1_000_002_500));
+}
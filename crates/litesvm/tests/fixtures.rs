@@ -0,0 +1,186 @@
+use {
+    litesvm::LiteSVM,
+    solana_account::state_traits::StateMut,
+    solana_instruction::Instruction,
+    solana_keypair::Keypair,
+    solana_loader_v3_interface::{get_program_data_address, state::UpgradeableLoaderState},
+    solana_message::Message,
+    solana_pubkey::Pubkey,
+    solana_signer::Signer,
+    solana_transaction::Transaction,
+    std::path::PathBuf,
+};
+
+fn memo_program_path() -> PathBuf {
+    let mut so_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    so_path.push("src/spl/programs/spl_memo-3.0.0.so");
+    so_path
+}
+
+fn send_memo(svm: &mut LiteSVM, program_id: Pubkey) {
+    let payer = Keypair::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    let tx = Transaction::new(
+        &[&payer],
+        Message::new(
+            &[Instruction {
+                program_id,
+                accounts: vec![],
+                data: b"hello".to_vec(),
+            }],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    );
+    svm.send_transaction(tx).unwrap();
+}
+
+fn programdata_authority(svm: &LiteSVM, program_id: &Pubkey) -> Option<Pubkey> {
+    let programdata = svm
+        .get_account(&get_program_data_address(program_id))
+        .unwrap();
+    match programdata.state().unwrap() {
+        UpgradeableLoaderState::ProgramData {
+            upgrade_authority_address,
+            ..
+        } => upgrade_authority_address,
+        state => panic!("unexpected program data state {state:?}"),
+    }
+}
+
+#[test]
+fn test_load_programs() {
+    let mut svm = LiteSVM::new();
+    let program_id = Pubkey::new_unique();
+    svm.load_programs(&[(program_id, memo_program_path())])
+        .unwrap();
+
+    let program = svm.get_account(&program_id).unwrap();
+    assert_eq!(program.owner, solana_sdk_ids::bpf_loader_upgradeable::id());
+    assert!(program.executable);
+    assert_eq!(programdata_authority(&svm, &program_id), None);
+    send_memo(&mut svm, program_id);
+}
+
+#[test]
+fn test_load_upgradeable_program() {
+    let mut svm = LiteSVM::new();
+    let program_id = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    svm.load_upgradeable_program(program_id, memo_program_path(), authority)
+        .unwrap();
+    assert_eq!(programdata_authority(&svm, &program_id), Some(authority));
+    send_memo(&mut svm, program_id);
+}
+
+#[test]
+fn test_load_programs_missing_file() {
+    let mut svm = LiteSVM::new();
+    let program_id = Pubkey::new_unique();
+    let result = svm.load_programs(&[(program_id, "does/not/exist.so")]);
+    assert!(matches!(result, Err(litesvm::error::LiteSVMError::Io(_))));
+    assert!(svm.get_account(&program_id).is_none());
+}
+
+#[cfg(feature = "serde")]
+mod account_files {
+    use {
+        super::*,
+        base64::{prelude::BASE64_STANDARD, Engine},
+        litesvm::error::LiteSVMError,
+        solana_account::Account,
+    };
+
+    fn write_account_file(dir: &std::path::Path, pubkey: &Pubkey, account: &Account) {
+        let json = serde_json::json!({
+            "pubkey": pubkey.to_string(),
+            "account": {
+                "lamports": account.lamports,
+                "data": [BASE64_STANDARD.encode(&account.data), "base64"],
+                "owner": account.owner.to_string(),
+                "executable": account.executable,
+                "rentEpoch": account.rent_epoch,
+                "space": account.data.len(),
+            },
+        });
+        std::fs::write(
+            dir.join(format!("{pubkey}.json")),
+            serde_json::to_vec_pretty(&json).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_load_account_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let pubkey = Pubkey::new_unique();
+        let account = Account {
+            lamports: 42,
+            data: vec![1, 2, 3],
+            owner: Pubkey::new_unique(),
+            executable: false,
+            rent_epoch: u64::MAX,
+        };
+        write_account_file(dir.path(), &pubkey, &account);
+        let path = dir.path().join(format!("{pubkey}.json"));
+
+        let mut svm = LiteSVM::new();
+        assert_eq!(svm.load_account_file(None, &path).unwrap(), pubkey);
+        assert_eq!(svm.get_account(&pubkey), Some(account.clone()));
+
+        let other = Pubkey::new_unique();
+        assert_eq!(svm.load_account_file(Some(other), &path).unwrap(), other);
+        assert_eq!(svm.get_account(&other), Some(account));
+    }
+
+    #[test]
+    fn test_load_accounts_dir_with_programs() {
+        // dump a deployed program and its program data, then load them into a fresh instance
+        let mut source = LiteSVM::new();
+        let program_id = Pubkey::new_unique();
+        source
+            .load_programs(&[(program_id, memo_program_path())])
+            .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        for pubkey in [program_id, get_program_data_address(&program_id)] {
+            write_account_file(dir.path(), &pubkey, &source.get_account(&pubkey).unwrap());
+        }
+        std::fs::write(dir.path().join("README.md"), "not an account").unwrap();
+
+        let mut svm = LiteSVM::new();
+        svm.load_accounts_dir(dir.path()).unwrap();
+        assert_eq!(
+            svm.get_account(&program_id),
+            source.get_account(&program_id)
+        );
+        send_memo(&mut svm, program_id);
+    }
+
+    #[test]
+    fn test_invalid_account_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let valid = Pubkey::new_unique();
+        write_account_file(
+            dir.path(),
+            &valid,
+            &Account {
+                lamports: 1,
+                ..Default::default()
+            },
+        );
+        let invalid = dir.path().join("invalid.json");
+        std::fs::write(
+            &invalid,
+            r#"{"pubkey": "not a pubkey", "account": {"lamports": 1}}"#,
+        )
+        .unwrap();
+
+        let mut svm = LiteSVM::new();
+        match svm.load_accounts_dir(dir.path()) {
+            Err(LiteSVMError::InvalidAccountFile { path, .. }) => assert_eq!(path, invalid),
+            result => panic!("unexpected result {result:?}"),
+        }
+        // nothing is written unless every file is valid
+        assert!(svm.get_account(&valid).is_none());
+    }
+}
@@ -0,0 +1,143 @@
+use {
+    litesvm::{
+        storage::{AccountStore, MemoryStore},
+        LiteSVM,
+    },
+    solana_account::ReadableAccount,
+    solana_keypair::Keypair,
+    solana_message::Message,
+    solana_pubkey::Pubkey,
+    solana_signer::Signer,
+    solana_system_interface::instruction::transfer,
+    solana_transaction::Transaction,
+    std::sync::Arc,
+};
+
+fn transfer_tx(svm: &LiteSVM, payer: &Keypair, to: &Pubkey, lamports: u64) -> Transaction {
+    Transaction::new(
+        &[payer],
+        Message::new(
+            &[transfer(&payer.pubkey(), to, lamports)],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    )
+}
+
+#[test]
+fn test_fork_writes_do_not_reach_parent() {
+    let store = Arc::new(MemoryStore::new());
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    let mut parent = LiteSVM::new().with_store(store.clone());
+    parent.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+
+    let mut fork = parent.fork();
+    fork.send_transaction(transfer_tx(&fork, &payer, &to, 100))
+        .unwrap();
+    fork.airdrop(&Pubkey::new_unique(), 1).unwrap();
+
+    assert_eq!(fork.get_balance(&to), Some(100));
+    assert_eq!(parent.get_balance(&to), None);
+    assert!(store.get_account(&to).unwrap().is_none());
+    assert_eq!(
+        parent.get_balance(&payer.pubkey()),
+        Some(1_000_000_000),
+        "the fee charged on the fork must not leak into the parent"
+    );
+
+    // the parent keeps working independently of the fork
+    parent
+        .send_transaction(transfer_tx(&parent, &payer, &to, 7))
+        .unwrap();
+    assert_eq!(parent.get_balance(&to), Some(7));
+    assert_eq!(fork.get_balance(&to), Some(100));
+}
+
+#[test]
+fn test_clone_is_isolated() {
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    let mut svm = LiteSVM::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    let mut clone = svm.clone();
+    let tx = transfer_tx(&clone, &payer, &to, 100);
+    let signature = tx.signatures[0];
+    clone.send_transaction(tx).unwrap();
+
+    assert_eq!(svm.get_balance(&to), None);
+    assert_eq!(clone.get_balance(&to), Some(100));
+    // the clone keeps recording transactions
+    assert!(clone.get_transaction(&signature).is_some());
+}
+
+#[test]
+fn test_parent_writes_after_fork_do_not_reach_fork() {
+    let evicted = Pubkey::new_unique();
+    let mut parent = LiteSVM::new().with_account_cache_size(Some(1));
+    parent.airdrop(&evicted, 1_000).unwrap();
+    // push the account out of memory, so the fork has to read it from the store
+    parent.airdrop(&Pubkey::new_unique(), 1).unwrap();
+    parent.airdrop(&Pubkey::new_unique(), 1).unwrap();
+
+    let fork = parent.fork();
+    parent.airdrop(&evicted, 2_000).unwrap();
+    let created = Pubkey::new_unique();
+    parent.airdrop(&created, 1).unwrap();
+
+    assert_eq!(parent.get_balance(&evicted), Some(2_000));
+    assert_eq!(fork.get_balance(&evicted), Some(1_000));
+    assert_eq!(fork.get_balance(&created), None);
+}
+
+#[test]
+fn test_fork_shares_the_parent_store() {
+    let store = Arc::new(MemoryStore::new());
+    let account = Pubkey::new_unique();
+    let mut parent = LiteSVM::new().with_store(store.clone());
+    parent.airdrop(&account, 1_000).unwrap();
+
+    let mut fork = parent.fork();
+    let mut grandchild = fork.fork();
+    fork.airdrop(&account, 500).unwrap();
+    parent.airdrop(&account, 2_000).unwrap();
+
+    // only the parent writes through to the store it was given
+    assert_eq!(
+        store.get_account(&account).unwrap().unwrap().lamports(),
+        2_000
+    );
+    assert_eq!(fork.get_balance(&account), Some(500));
+    assert_eq!(grandchild.get_balance(&account), Some(1_000));
+    grandchild.airdrop(&account, 1).unwrap();
+    assert_eq!(grandchild.get_balance(&account), Some(1));
+    assert_eq!(fork.get_balance(&account), Some(500));
+}
+
+#[test]
+fn test_fork_does_not_see_parent_history_after_fork() {
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+    let mut parent = LiteSVM::new().with_account_history(true);
+    parent.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    parent
+        .send_transaction(transfer_tx(&parent, &payer, &to, 100))
+        .unwrap();
+
+    let mut fork = parent.fork();
+    parent
+        .send_transaction(transfer_tx(&parent, &payer, &to, 200))
+        .unwrap();
+    assert_eq!(parent.account_history(&to).unwrap().len(), 2);
+    assert_eq!(fork.account_history(&to).unwrap().len(), 1);
+
+    // the fork numbers its own transactions after the ones it shares with the parent
+    fork.send_transaction(transfer_tx(&fork, &payer, &to, 300))
+        .unwrap();
+    let versions = fork.account_history(&to).unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[1].tx_index, versions[0].tx_index + 1);
+    assert_eq!(versions[1].account.as_ref().unwrap().lamports(), 400);
+}
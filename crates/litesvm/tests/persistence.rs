@@ -0,0 +1,604 @@
+#![cfg(feature = "rocksdb")]
+
+mod common;
+
+use {
+    common::{add_stake_account, add_vote_account, add_vote_credits, BOOTSTRAP_STAKE},
+    litesvm::{
+        storage::{DbKey, RocksDBStore, StoreError, SCHEMA_VERSION},
+        types::AccountFilter,
+        LiteSVM,
+    },
+    serial_test::serial,
+    solana_account::{Account, AccountSharedData, ReadableAccount},
+    solana_epoch_rewards::EpochRewards,
+    solana_epoch_schedule::EpochSchedule,
+    solana_fee_structure::FeeStructure,
+    solana_inflation::Inflation,
+    solana_instruction::Instruction,
+    solana_keypair::Keypair,
+    solana_message::{Message, VersionedMessage as VMsg},
+    solana_native_token::LAMPORTS_PER_SOL,
+    solana_pubkey::{pubkey, Pubkey},
+    solana_signer::Signer,
+    solana_stake_interface::state::Delegation,
+    solana_system_interface::instruction::transfer,
+    solana_transaction::{versioned::VersionedTransaction, Transaction},
+    tempfile::TempDir,
+};
+
+const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+fn memo_tx(svm: &LiteSVM, payer: &Keypair, memo: &str) -> Transaction {
+    let ix = Instruction {
+        program_id: MEMO_PROGRAM_ID,
+        accounts: vec![],
+        data: memo.as_bytes().to_vec(),
+    };
+    Transaction::new(
+        &[payer],
+        Message::new(&[ix], Some(&payer.pubkey())),
+        svm.latest_blockhash(),
+    )
+}
+
+#[test_log::test]
+fn test_reopen_restores_state() {
+    let tmpdir = TempDir::new().unwrap();
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    let blockhash = {
+        let mut svm = LiteSVM::open(tmpdir.path())
+            .unwrap()
+            .with_log_bytes_limit(Some(1_000));
+        svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+        let tx = Transaction::new(
+            &[&payer],
+            Message::new(
+                &[transfer(&payer.pubkey(), &to, 100)],
+                Some(&payer.pubkey()),
+            ),
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).unwrap();
+        svm.expire_blockhash();
+        svm.save_metadata().unwrap();
+        svm.latest_blockhash()
+    };
+
+    let mut svm = LiteSVM::open(tmpdir.path()).unwrap();
+    assert_eq!(svm.latest_blockhash(), blockhash);
+    assert_eq!(svm.get_balance(&to), Some(100));
+    assert!(svm.get_sigverify());
+
+    // the program and sysvar caches must be usable without re-adding anything
+    let tx = memo_tx(&svm, &payer, "hello again");
+    let meta = svm.send_transaction(tx).unwrap();
+    assert!(meta.logs.iter().any(|log| log.contains("hello again")));
+    let tx = Transaction::new(
+        &[&payer],
+        Message::new(&[transfer(&payer.pubkey(), &to, 1)], Some(&payer.pubkey())),
+        svm.latest_blockhash(),
+    );
+    svm.send_transaction(tx).unwrap();
+    assert_eq!(svm.get_balance(&to), Some(101));
+}
+
+#[test_log::test]
+fn test_reopen_restores_fee_config() {
+    let tmpdir = TempDir::new().unwrap();
+    let collector = Pubkey::new_unique();
+    {
+        let svm = LiteSVM::open(tmpdir.path())
+            .unwrap()
+            .with_fee_structure(FeeStructure {
+                lamports_per_signature: 7_000,
+                ..FeeStructure::default()
+            })
+            .with_fee_collector(collector)
+            .with_fee_burn_percent(0);
+        svm.save_metadata().unwrap();
+    }
+
+    let mut svm = LiteSVM::open(tmpdir.path()).unwrap();
+    let payer = Keypair::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    svm.airdrop(&collector, 1_000_000_000).unwrap();
+    let fees = svm
+        .send_transaction(memo_tx(&svm, &payer, "fees"))
+        .unwrap()
+        .fees;
+    assert_eq!((fees.transaction_fee, fees.collected), (7_000, 7_000));
+    assert_eq!(svm.get_balance(&collector), Some(1_000_007_000));
+}
+
+#[test_log::test]
+fn test_reopen_resumes_epoch_rewards() {
+    let tmpdir = TempDir::new().unwrap();
+    let (vote_pubkey, stake_pubkey, stake_balance) = {
+        let mut svm = LiteSVM::open(tmpdir.path())
+            .unwrap()
+            .with_inflation(Inflation::default());
+        svm.set_sysvar(&EpochSchedule::without_warmup());
+        let vote_pubkey = add_vote_account(&mut svm, 0);
+        let stake_pubkey = add_stake_account(
+            &mut svm,
+            &Pubkey::new_unique(),
+            Delegation::new(&vote_pubkey, BOOTSTRAP_STAKE, u64::MAX),
+        );
+        add_vote_credits(&mut svm, vote_pubkey, 100);
+        let stake_balance = svm.get_balance(&stake_pubkey).unwrap();
+        svm.warp_to_epoch(1).unwrap();
+        assert!(svm.get_sysvar::<EpochRewards>().active);
+        svm.save_metadata().unwrap();
+        (vote_pubkey, stake_pubkey, stake_balance)
+    };
+
+    // the stake rewards are still paid by the next block
+    let mut svm = LiteSVM::open(tmpdir.path()).unwrap();
+    assert!(svm.get_sysvar::<EpochRewards>().active);
+    svm.produce_block().unwrap();
+    let epoch_rewards = svm.get_sysvar::<EpochRewards>();
+    assert!(!epoch_rewards.active);
+    assert_eq!(
+        epoch_rewards.distributed_rewards,
+        epoch_rewards.total_rewards
+    );
+    assert!(svm.get_balance(&stake_pubkey).unwrap() > stake_balance);
+
+    // and the inflation is restored for the next epochs
+    add_vote_credits(&mut svm, vote_pubkey, 100);
+    svm.warp_to_epoch(2).unwrap();
+    assert!(svm.get_sysvar::<EpochRewards>().active);
+}
+
+#[test_log::test]
+fn test_reopen_restores_recent_blockhashes() {
+    let tmpdir = TempDir::new().unwrap();
+    let (first, second) = {
+        let mut svm = LiteSVM::open(tmpdir.path())
+            .unwrap()
+            .with_max_blockhash_age(3);
+        let first = svm.latest_blockhash();
+        svm.produce_block().unwrap();
+        let second = svm.latest_blockhash();
+        svm.produce_block().unwrap();
+        svm.save_metadata().unwrap();
+        (first, second)
+    };
+
+    let mut svm = LiteSVM::open(tmpdir.path()).unwrap();
+    assert_eq!(svm.get_block_height(), 2);
+    assert!(svm.is_blockhash_valid(&first));
+    assert!(svm.is_blockhash_valid(&second));
+    // the max age is restored too
+    svm.produce_block().unwrap();
+    svm.produce_block().unwrap();
+    assert!(!svm.is_blockhash_valid(&first));
+    assert!(svm.is_blockhash_valid(&second));
+}
+
+#[test_log::test]
+fn test_reopen_charges_fee_from_persisted_payer() {
+    let tmpdir = TempDir::new().unwrap();
+    let payer = Keypair::new();
+
+    {
+        let mut svm = LiteSVM::open(tmpdir.path()).unwrap();
+        svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    }
+
+    let mut svm = LiteSVM::open(tmpdir.path()).unwrap();
+    // a failing transaction still has its fee withdrawn from the payer, which only lives in the store
+    let ix = Instruction {
+        program_id: MEMO_PROGRAM_ID,
+        accounts: vec![],
+        data: vec![0xff],
+    };
+    let tx = Transaction::new(
+        &[&payer],
+        Message::new(&[ix], Some(&payer.pubkey())),
+        svm.latest_blockhash(),
+    );
+    svm.send_transaction(tx).unwrap_err();
+    assert_eq!(svm.get_balance(&payer.pubkey()), Some(1_000_000_000 - 5000));
+}
+
+fn transfer_tx(svm: &LiteSVM, payer: &Keypair, to: &Pubkey, lamports: u64) -> Transaction {
+    Transaction::new(
+        &[payer],
+        Message::new(
+            &[transfer(&payer.pubkey(), to, lamports)],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    )
+}
+
+#[test_log::test]
+fn test_snapshot_roundtrip() {
+    let tmpdir = TempDir::new().unwrap();
+    let snapshot_dir = tmpdir.path().join("snapshot");
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    // an in-memory instance has its accounts copied into the snapshot
+    let mut svm = LiteSVM::new().with_transaction_history(10);
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    let tx = transfer_tx(&svm, &payer, &to, 100);
+    let signature = tx.signatures[0];
+    svm.send_transaction(tx).unwrap();
+    svm.expire_blockhash();
+    svm.save_snapshot(&snapshot_dir).unwrap();
+    assert!(svm.save_snapshot(&snapshot_dir).is_err());
+
+    for _ in 0..2 {
+        let mut loaded = LiteSVM::load_snapshot(&snapshot_dir).unwrap();
+        assert_eq!(loaded.latest_blockhash(), svm.latest_blockhash());
+        assert_eq!(loaded.get_balance(&to), Some(100));
+        assert_eq!(
+            loaded.get_transaction(&signature),
+            svm.get_transaction(&signature)
+        );
+        assert!(loaded.get_sigverify());
+
+        // changes made after loading don't end up in the snapshot
+        loaded
+            .send_transaction(transfer_tx(&loaded, &payer, &to, 1))
+            .unwrap();
+        assert_eq!(loaded.get_balance(&to), Some(101));
+    }
+}
+
+#[test_log::test]
+fn test_snapshot_from_rocksdb_store() {
+    let tmpdir = TempDir::new().unwrap();
+    let snapshot_dir = tmpdir.path().join("snapshot");
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    {
+        let mut svm = LiteSVM::open(tmpdir.path().join("db")).unwrap();
+        svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+        svm.send_transaction(transfer_tx(&svm, &payer, &to, 100))
+            .unwrap();
+        svm.save_snapshot(&snapshot_dir).unwrap();
+    }
+
+    let mut svm = LiteSVM::load_snapshot(&snapshot_dir).unwrap();
+    let meta = svm.send_transaction(memo_tx(&svm, &payer, "from a snapshot"));
+    assert!(meta
+        .unwrap()
+        .logs
+        .iter()
+        .any(|log| log.contains("from a snapshot")));
+    assert_eq!(svm.get_balance(&to), Some(100));
+}
+
+#[test_log::test]
+fn test_reopen_keeps_owner_index() {
+    let tmpdir = TempDir::new().unwrap();
+    let program_id = Pubkey::new_unique();
+    let address = Pubkey::new_unique();
+
+    {
+        let mut svm = LiteSVM::open(tmpdir.path()).unwrap();
+        svm.set_account(
+            address,
+            Account {
+                lamports: LAMPORTS_PER_SOL,
+                data: vec![7; 16],
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .unwrap();
+    }
+
+    // the account now only lives in the store, so it's found through the persisted index
+    let svm = LiteSVM::open(tmpdir.path()).unwrap();
+    let accounts = svm
+        .get_program_accounts(&program_id, &[AccountFilter::DataSize(16)], None)
+        .unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].0, address);
+    drop(svm);
+    let store = RocksDBStore::open(tmpdir.path()).unwrap();
+    assert_eq!(
+        store.get_program_account_keys(&program_id).unwrap(),
+        [address]
+    );
+}
+
+#[test_log::test]
+fn test_owner_index_follows_writes_within_a_batch() {
+    let tmpdir = TempDir::new().unwrap();
+    let program_id = Pubkey::new_unique();
+    let other_program = Pubkey::new_unique();
+    let address = Pubkey::new_unique();
+    let owned_by = |owner: Pubkey| {
+        AccountSharedData::from(Account {
+            lamports: LAMPORTS_PER_SOL,
+            data: vec![],
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        })
+    };
+
+    let store = RocksDBStore::open(tmpdir.path()).unwrap();
+    store
+        .write_accounts(&[
+            (address, Some(owned_by(program_id))),
+            (address, Some(owned_by(other_program))),
+        ])
+        .unwrap();
+    assert!(store
+        .get_program_account_keys(&program_id)
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        store.get_program_account_keys(&other_program).unwrap(),
+        [address]
+    );
+
+    store
+        .write_accounts(&[(address, Some(owned_by(program_id))), (address, None)])
+        .unwrap();
+    assert!(store
+        .get_program_account_keys(&program_id)
+        .unwrap()
+        .is_empty());
+    assert!(store
+        .get_program_account_keys(&other_program)
+        .unwrap()
+        .is_empty());
+}
+
+#[test_log::test]
+fn test_bounded_cache_reads_through_rocksdb() {
+    let tmpdir = TempDir::new().unwrap();
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    let mut svm = LiteSVM::open(tmpdir.path())
+        .unwrap()
+        .with_account_cache_size(Some(1));
+    svm.airdrop(&payer.pubkey(), LAMPORTS_PER_SOL).unwrap();
+    svm.send_transaction(transfer_tx(&svm, &payer, &to, 100))
+        .unwrap();
+    svm.send_transaction(memo_tx(&svm, &payer, "evicted"))
+        .unwrap();
+
+    assert_eq!(svm.get_balance(&to), Some(100));
+    let stats = svm.account_cache_stats();
+    assert!(stats.evictions > 0);
+    assert!(stats.misses > 0);
+}
+
+#[test_log::test]
+fn test_read_only_instances_share_a_database() {
+    let tmpdir = TempDir::new().unwrap();
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    {
+        let mut svm = LiteSVM::open(tmpdir.path()).unwrap();
+        svm.airdrop(&payer.pubkey(), LAMPORTS_PER_SOL).unwrap();
+    }
+
+    let mut first = LiteSVM::open_read_only(tmpdir.path()).unwrap();
+    let mut second = LiteSVM::open_read_only(tmpdir.path()).unwrap();
+    first
+        .send_transaction(transfer_tx(&first, &payer, &to, 100))
+        .unwrap();
+    second
+        .send_transaction(transfer_tx(&second, &payer, &to, 7))
+        .unwrap();
+    assert_eq!(first.get_balance(&to), Some(100));
+    assert_eq!(second.get_balance(&to), Some(7));
+    drop((first, second));
+
+    // the writes only ever reached the overlays
+    let store = RocksDBStore::open_read_only(tmpdir.path()).unwrap();
+    assert!(store.get_account(&to).unwrap().is_none());
+    assert_eq!(
+        store
+            .get_account(&payer.pubkey())
+            .unwrap()
+            .unwrap()
+            .lamports(),
+        LAMPORTS_PER_SOL
+    );
+    assert!(store.put_account(&to, &Account::default().into()).is_err());
+}
+
+#[test_log::test]
+fn test_secondary_follows_primary() {
+    let primary_dir = TempDir::new().unwrap();
+    let secondary_dir = TempDir::new().unwrap();
+    let address = Pubkey::new_unique();
+
+    let mut primary = LiteSVM::open(primary_dir.path()).unwrap();
+    let secondary = LiteSVM::open_secondary(primary_dir.path(), secondary_dir.path()).unwrap();
+    assert_eq!(secondary.latest_blockhash(), primary.latest_blockhash());
+
+    let store = RocksDBStore::open_as_secondary(primary_dir.path(), secondary_dir.path()).unwrap();
+    primary.airdrop(&address, 42).unwrap();
+    assert!(store.get_account(&address).unwrap().is_none());
+    store.try_catch_up_with_primary().unwrap();
+    assert_eq!(store.get_account(&address).unwrap().unwrap().lamports(), 42);
+}
+
+#[test_log::test]
+fn test_account_history_survives_reopen() {
+    let tmpdir = TempDir::new().unwrap();
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    {
+        let mut svm = LiteSVM::open(tmpdir.path())
+            .unwrap()
+            .with_account_history(true);
+        svm.airdrop(&payer.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        svm.send_transaction(transfer_tx(&svm, &payer, &to, 100))
+            .unwrap();
+    }
+
+    let mut svm = LiteSVM::open(tmpdir.path())
+        .unwrap()
+        .with_account_history(true);
+    svm.send_transaction(transfer_tx(&svm, &payer, &to, 7))
+        .unwrap();
+    let history = svm.account_history(&to).unwrap();
+    // the second instance keeps numbering the slot's transactions where the first left off
+    assert_eq!(
+        history
+            .iter()
+            .map(|version| (
+                version.tx_index,
+                version.account.as_ref().unwrap().lamports()
+            ))
+            .collect::<Vec<_>>(),
+        [(0, 100), (1, 107)]
+    );
+}
+
+#[test_log::test]
+fn test_unversioned_database_is_migrated() {
+    let tmpdir = TempDir::new().unwrap();
+    let owner = Pubkey::new_unique();
+    let address = Pubkey::new_unique();
+
+    {
+        let store = RocksDBStore::open(tmpdir.path()).unwrap();
+        assert_eq!(store.schema_version().unwrap(), Some(SCHEMA_VERSION));
+        let account = Account {
+            lamports: LAMPORTS_PER_SOL,
+            data: vec![],
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        };
+        store.put_account(&address, &account.into()).unwrap();
+
+        // strip what the first schema version didn't have
+        let db = store.clone_db();
+        db.delete_cf(
+            db.cf_handle("metadata").unwrap(),
+            DbKey::SchemaVersion.to_bytes(),
+        )
+        .unwrap();
+        db.delete_cf(
+            db.cf_handle("owner_index").unwrap(),
+            DbKey::OwnerIndex {
+                owner,
+                pubkey: address,
+            }
+            .to_bytes(),
+        )
+        .unwrap();
+    }
+
+    let store = RocksDBStore::open(tmpdir.path()).unwrap();
+    assert_eq!(store.schema_version().unwrap(), Some(SCHEMA_VERSION));
+    assert_eq!(store.get_program_account_keys(&owner).unwrap(), [address]);
+}
+
+#[test_log::test]
+fn test_newer_schema_is_rejected() {
+    let tmpdir = TempDir::new().unwrap();
+    {
+        let store = RocksDBStore::open(tmpdir.path()).unwrap();
+        let db = store.clone_db();
+        db.put_cf(
+            db.cf_handle("metadata").unwrap(),
+            DbKey::SchemaVersion.to_bytes(),
+            (SCHEMA_VERSION + 1).to_le_bytes(),
+        )
+        .unwrap();
+    }
+
+    let err = RocksDBStore::open(tmpdir.path()).err().unwrap();
+    assert!(matches!(
+        err,
+        StoreError::IncompatibleSchema { found, supported }
+            if found == SCHEMA_VERSION + 1 && supported == SCHEMA_VERSION
+    ));
+    assert!(LiteSVM::open(tmpdir.path()).is_err());
+}
+
+#[test]
+#[serial]
+fn test_rocksdb_persistence_via_direct_store() {
+    let tmpdir = TempDir::new().expect("create tempdir");
+    let path = tmpdir.path();
+
+    // write phase
+    {
+        let mut svm = LiteSVM::new_with_db_path(path)
+            .with_builtins()
+            .with_lamports(1_000_000 * LAMPORTS_PER_SOL)
+            .with_sysvars()
+            .with_spl_programs();
+        let payer = Keypair::new();
+        let key = pubkey!("J39wvrFY2AkoAUCke5347RMNk3ditxZfVidoZ7U6Fguf");
+        let program = pubkey!("GtdambwDgHWrDJdVPBkEHGhCwokqgAoch162teUjJse2");
+        svm.airdrop(&payer.pubkey(), 1_000).unwrap();
+        svm.set_account(
+            key,
+            Account {
+                lamports: 7,
+                data: (123u32).to_le_bytes().to_vec(),
+                owner: program,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // commit a transaction through the store
+        let tx = VersionedTransaction::try_new(
+            VMsg::Legacy(Message::new_with_blockhash(
+                &[transfer(&payer.pubkey(), &key, 0)],
+                Some(&payer.pubkey()),
+                &svm.latest_blockhash(),
+            )),
+            &[&payer],
+        )
+        .unwrap();
+        let _ = svm.send_transaction(tx);
+        // dropping the instance releases the database lock
+    }
+
+    // read phase
+    {
+        {
+            // through LiteSVM
+            let svm2 = LiteSVM::new_with_db_path(path)
+                .with_builtins()
+                .with_lamports(1_000_000 * LAMPORTS_PER_SOL)
+                .with_sysvars()
+                .with_spl_programs();
+            let acc = svm2
+                .get_account(&pubkey!("J39wvrFY2AkoAUCke5347RMNk3ditxZfVidoZ7U6Fguf"))
+                .unwrap();
+            assert_eq!(acc.lamports(), 7);
+            assert_eq!(u32::from_le_bytes(acc.data()[..4].try_into().unwrap()), 123);
+            drop(svm2);
+        }
+
+        // and through the RocksDBStore directly
+        let store = RocksDBStore::open(path).expect("open store");
+        let acc2 = store
+            .get_account(&pubkey!("J39wvrFY2AkoAUCke5347RMNk3ditxZfVidoZ7U6Fguf"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(acc2.lamports(), 7);
+        assert_eq!(
+            u32::from_le_bytes(acc2.data()[..4].try_into().unwrap()),
+            123
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use {
+    litesvm::{
+        storage::{AccountStore, MemoryStore},
+        types::{AccountFilter, DataSlice},
+        LiteSVM,
+    },
+    solana_account::{Account, AccountSharedData},
+    solana_pubkey::Pubkey,
+};
+
+fn account(owner: Pubkey, data: Vec<u8>) -> Account {
+    Account {
+        lamports: 1_000_000_000,
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+#[test]
+fn test_get_program_accounts_filters() {
+    let program_id = Pubkey::new_unique();
+    let mut svm = LiteSVM::new();
+    let mut keys = (0..4).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+    keys.sort();
+    svm.set_account(keys[0], account(program_id, vec![1, 2, 3, 4]))
+        .unwrap();
+    svm.set_account(keys[1], account(program_id, vec![1, 2, 9]))
+        .unwrap();
+    svm.set_account(keys[2], account(program_id, vec![]))
+        .unwrap();
+    svm.set_account(keys[3], account(Pubkey::new_unique(), vec![1, 2, 3, 4]))
+        .unwrap();
+
+    let addresses = |filters: &[AccountFilter]| {
+        svm.get_program_accounts(&program_id, filters, None)
+            .unwrap()
+            .into_iter()
+            .map(|(pubkey, _)| pubkey)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(addresses(&[]), keys[..3]);
+    assert_eq!(addresses(&[AccountFilter::DataSize(3)]), [keys[1]]);
+    assert_eq!(addresses(&[AccountFilter::memcmp(1, [2])]), keys[..2]);
+    assert_eq!(addresses(&[AccountFilter::memcmp(2, [3, 4])]), [keys[0]]);
+    // a comparison running past the end of the data doesn't match
+    assert_eq!(addresses(&[AccountFilter::memcmp(2, [9, 0])]), []);
+    assert_eq!(addresses(&[AccountFilter::memcmp(9, [])]), []);
+    assert_eq!(
+        addresses(&[AccountFilter::memcmp(0, [1]), AccountFilter::DataSize(4)]),
+        [keys[0]]
+    );
+
+    let sliced = svm
+        .get_program_accounts(
+            &program_id,
+            &[AccountFilter::memcmp(0, [1])],
+            Some(DataSlice {
+                offset: 2,
+                length: 5,
+            }),
+        )
+        .unwrap();
+    assert_eq!(sliced[0].1.data, [3, 4]);
+    assert_eq!(sliced[1].1.data, [9]);
+}
+
+#[test]
+fn test_get_program_accounts_follows_owner_changes() {
+    let program_id = Pubkey::new_unique();
+    let other_program = Pubkey::new_unique();
+    let address = Pubkey::new_unique();
+    let mut svm = LiteSVM::new();
+
+    svm.set_account(address, account(program_id, vec![0; 8]))
+        .unwrap();
+    assert_eq!(
+        svm.get_program_accounts(&program_id, &[], None)
+            .unwrap()
+            .len(),
+        1
+    );
+
+    svm.set_account(address, account(other_program, vec![0; 8]))
+        .unwrap();
+    assert!(svm
+        .get_program_accounts(&program_id, &[], None)
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        svm.get_program_accounts(&other_program, &[], None)
+            .unwrap()
+            .len(),
+        1
+    );
+
+    svm.remove_account(&address).unwrap();
+    assert!(svm
+        .get_program_accounts(&other_program, &[], None)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_memory_store_indexes_owners() {
+    let program_id = Pubkey::new_unique();
+    let other_program = Pubkey::new_unique();
+    let address = Pubkey::new_unique();
+    let store = MemoryStore::new();
+    let owned_by = |owner: Pubkey| AccountSharedData::from(account(owner, vec![]));
+
+    store.put_account(&address, &owned_by(program_id)).unwrap();
+    assert_eq!(
+        store.get_program_account_keys(&program_id).unwrap(),
+        [address]
+    );
+
+    // the owner changes twice within a single write
+    store
+        .write_accounts(&[
+            (address, Some(owned_by(other_program))),
+            (address, Some(owned_by(program_id))),
+        ])
+        .unwrap();
+    assert_eq!(
+        store.get_program_account_keys(&program_id).unwrap(),
+        [address]
+    );
+    assert!(store
+        .get_program_account_keys(&other_program)
+        .unwrap()
+        .is_empty());
+
+    store.delete_account(&address).unwrap();
+    assert!(store
+        .get_program_account_keys(&program_id)
+        .unwrap()
+        .is_empty());
+}
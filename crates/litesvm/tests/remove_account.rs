@@ -0,0 +1,89 @@
+use {
+    litesvm::{
+        storage::{AccountStore, MemoryStore},
+        LiteSVM,
+    },
+    solana_instruction::Instruction,
+    solana_keypair::Keypair,
+    solana_message::Message,
+    solana_pubkey::{pubkey, Pubkey},
+    solana_signer::Signer,
+    solana_system_interface::instruction::transfer,
+    solana_transaction::Transaction,
+    std::sync::Arc,
+};
+
+const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+#[test]
+fn test_drained_account_is_purged() {
+    let store = Arc::new(MemoryStore::new());
+    let payer = Keypair::new();
+    let closed = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    let mut svm = LiteSVM::new().with_store(store.clone());
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    svm.airdrop(&closed.pubkey(), 1_000_000_000).unwrap();
+    assert!(store.get_account(&closed.pubkey()).unwrap().is_some());
+
+    // the payer covers the fee so `closed` can send away all of its lamports
+    let tx = Transaction::new(
+        &[&payer, &closed],
+        Message::new(
+            &[transfer(&closed.pubkey(), &to, 1_000_000_000)],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    );
+    svm.send_transaction(tx).unwrap();
+
+    assert_eq!(svm.get_balance(&to), Some(1_000_000_000));
+    assert!(svm.get_account(&closed.pubkey()).is_none());
+    assert!(store.get_account(&closed.pubkey()).unwrap().is_none());
+}
+
+#[test]
+fn test_remove_account() {
+    let store = Arc::new(MemoryStore::new());
+    let user = Pubkey::new_unique();
+
+    let mut svm = LiteSVM::new().with_store(store.clone());
+    svm.airdrop(&user, 1_000_000_000).unwrap();
+    let checkpoint = svm.checkpoint();
+
+    svm.remove_account(&user).unwrap();
+    assert!(svm.get_account(&user).is_none());
+    assert!(store.get_account(&user).unwrap().is_none());
+    // removing it again is a no-op
+    svm.remove_account(&user).unwrap();
+
+    svm.revert(checkpoint).unwrap();
+    assert_eq!(svm.get_balance(&user), Some(1_000_000_000));
+    assert!(store.get_account(&user).unwrap().is_some());
+}
+
+#[test]
+fn test_remove_program() {
+    let payer = Keypair::new();
+    let mut svm = LiteSVM::new();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+
+    let memo_tx = |svm: &LiteSVM, memo: &str| {
+        let ix = Instruction {
+            program_id: MEMO_PROGRAM_ID,
+            accounts: vec![],
+            data: memo.as_bytes().to_vec(),
+        };
+        Transaction::new(
+            &[&payer],
+            Message::new(&[ix], Some(&payer.pubkey())),
+            svm.latest_blockhash(),
+        )
+    };
+    svm.send_transaction(memo_tx(&svm, "before")).unwrap();
+
+    svm.remove_account(&MEMO_PROGRAM_ID).unwrap();
+    assert!(svm.get_account(&MEMO_PROGRAM_ID).is_none());
+    assert!(svm.send_transaction(memo_tx(&svm, "after")).is_err());
+}
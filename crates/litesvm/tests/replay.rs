@@ -0,0 +1,220 @@
+#![cfg(feature = "serde")]
+
+use {
+    base64::{prelude::BASE64_STANDARD, Engine},
+    litesvm::{
+        error::LiteSVMError,
+        types::{ReplayMismatch, TransactionResult},
+        LiteSVM,
+    },
+    serde_json::{json, Value},
+    solana_account::Account,
+    solana_address_lookup_table_interface::state::{AddressLookupTable, LookupTableMeta},
+    solana_clock::Clock,
+    solana_hash::Hash,
+    solana_keypair::Keypair,
+    solana_message::{v0, AddressLookupTableAccount, VersionedMessage},
+    solana_pubkey::Pubkey,
+    solana_signer::Signer,
+    solana_system_interface::instruction::transfer,
+    solana_transaction::versioned::VersionedTransaction,
+    std::{borrow::Cow, path::Path},
+};
+
+const SLOT: u64 = 1_000;
+const BLOCK_TIME: i64 = 1_700_000_000;
+
+struct Recording {
+    tx: VersionedTransaction,
+    accounts: Vec<(Pubkey, Account)>,
+    recipient: Pubkey,
+}
+
+/// A transfer to an address from a lookup table, with the accounts it needs.
+fn recording() -> Recording {
+    let payer = Keypair::new();
+    let recipient = Pubkey::new_unique();
+    let table_key = Pubkey::new_unique();
+    let table = AddressLookupTable {
+        meta: LookupTableMeta::default(),
+        addresses: Cow::Owned(vec![recipient]),
+    };
+    let table_account = Account {
+        lamports: 1_000_000_000,
+        data: table.serialize_for_tests().unwrap(),
+        owner: solana_sdk_ids::address_lookup_table::id(),
+        executable: false,
+        rent_epoch: 0,
+    };
+    let message = v0::Message::try_compile(
+        &payer.pubkey(),
+        &[transfer(&payer.pubkey(), &recipient, 1_000_000)],
+        &[AddressLookupTableAccount {
+            key: table_key,
+            addresses: vec![recipient],
+        }],
+        Hash::new_unique(),
+    )
+    .unwrap();
+    let tx = VersionedTransaction::try_new(VersionedMessage::V0(message), &[&payer]).unwrap();
+    let payer_account = Account::new(1_000_000_000, 0, &solana_sdk_ids::system_program::id());
+    Recording {
+        tx,
+        accounts: vec![(payer.pubkey(), payer_account), (table_key, table_account)],
+        recipient,
+    }
+}
+
+/// Runs the transaction the way mainnet would have, returning its `getTransaction` response.
+fn get_transaction_response(recording: &Recording) -> Value {
+    let mut svm = LiteSVM::new().with_blockhash_check(false);
+    for (pubkey, account) in &recording.accounts {
+        svm.set_account(*pubkey, account.clone()).unwrap();
+    }
+    svm.warp_to_slot(SLOT);
+    let result = svm.send_transaction(recording.tx.clone());
+    let (err, meta) = match &result {
+        Ok(meta) => (None, meta),
+        Err(failed) => (Some(&failed.err), &failed.meta),
+    };
+    let mut keys = recording.tx.message.static_account_keys().to_vec();
+    keys.push(recording.recipient);
+    let post_balances: Vec<u64> = keys
+        .iter()
+        .map(|pubkey| svm.get_balance(pubkey).unwrap_or_default())
+        .collect();
+    json!({
+        "slot": SLOT,
+        "blockTime": BLOCK_TIME,
+        "transaction": [
+            BASE64_STANDARD.encode(bincode::serialize(&recording.tx).unwrap()),
+            "base64",
+        ],
+        "meta": {
+            "err": err,
+            "fee": 5000,
+            "preBalances": [],
+            "postBalances": post_balances,
+            "logMessages": meta.logs,
+            "computeUnitsConsumed": meta.compute_units_consumed,
+            "loadedAddresses": {
+                "writable": [recording.recipient.to_string()],
+                "readonly": [],
+            },
+        },
+        "version": 0,
+    })
+}
+
+fn write_replay_file(path: &Path, transaction: Value, accounts: &[(Pubkey, Account)]) {
+    let accounts: Vec<Value> = accounts
+        .iter()
+        .map(|(pubkey, account)| {
+            json!({
+                "pubkey": pubkey.to_string(),
+                "account": {
+                    "lamports": account.lamports,
+                    "data": [BASE64_STANDARD.encode(&account.data), "base64"],
+                    "owner": account.owner.to_string(),
+                    "executable": account.executable,
+                    "rentEpoch": account.rent_epoch,
+                    "space": account.data.len(),
+                },
+            })
+        })
+        .collect();
+    let file = json!({"transaction": transaction, "accounts": accounts});
+    std::fs::write(path, file.to_string()).unwrap();
+}
+
+#[test]
+fn test_replay_matches_recording() {
+    let recording = recording();
+    let response = json!({
+        "jsonrpc": "2.0",
+        "result": get_transaction_response(&recording),
+        "id": 1,
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tx.json");
+    write_replay_file(&path, response, &recording.accounts);
+
+    let mut svm = LiteSVM::new();
+    let report = svm.replay_transaction(&path).unwrap();
+    assert!(report.result.is_ok(), "{:?}", report.result);
+    assert_eq!(report.mismatches, []);
+    assert!(report.is_match());
+    assert_eq!(svm.get_balance(&recording.recipient), Some(1_000_000));
+    let clock = svm.get_sysvar::<Clock>();
+    assert_eq!(clock.slot, SLOT);
+    assert_eq!(clock.unix_timestamp, BLOCK_TIME);
+}
+
+#[test]
+fn test_replay_reports_mismatches() {
+    let recording = recording();
+    let mut response = get_transaction_response(&recording);
+    let meta = &mut response["meta"];
+    meta["logMessages"][1] = json!("Program log: something else");
+    meta["computeUnitsConsumed"] = json!(1);
+    meta["postBalances"][2] = json!(2_000_000);
+    meta["err"] = json!({"InstructionError": [0, {"Custom": 1}]});
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tx.json");
+    write_replay_file(&path, response, &recording.accounts);
+
+    let mut svm = LiteSVM::new();
+    let report = svm.replay_transaction(&path).unwrap();
+    let TransactionResult::Ok(meta) = &report.result else {
+        panic!("{:?}", report.result);
+    };
+    assert_eq!(
+        report.mismatches,
+        [
+            ReplayMismatch::Result {
+                expected: Some(
+                    solana_transaction_error::TransactionError::InstructionError(
+                        0,
+                        solana_instruction::error::InstructionError::Custom(1)
+                    )
+                ),
+                actual: None,
+            },
+            ReplayMismatch::Log {
+                index: 1,
+                expected: Some("Program log: something else".to_string()),
+                actual: meta.logs.get(1).cloned(),
+            },
+            ReplayMismatch::ComputeUnits {
+                expected: 1,
+                actual: meta.compute_units_consumed,
+            },
+            ReplayMismatch::PostBalance {
+                pubkey: recording.recipient,
+                expected: 2_000_000,
+                actual: 1_000_000,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_replay_file() {
+    let recording = recording();
+    let mut response = get_transaction_response(&recording);
+    response["transaction"] = json!({"signatures": [], "message": {}});
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tx.json");
+    write_replay_file(&path, response.clone(), &recording.accounts);
+
+    let mut svm = LiteSVM::new();
+    let result = svm.replay_transaction(&path);
+    assert!(matches!(result, Err(LiteSVMError::InvalidReplayFile { path: p, .. }) if p == path));
+
+    response["transaction"] = json!(["tx", "base58"]);
+    write_replay_file(&path, response, &recording.accounts);
+    let result = svm.replay_transaction(&path);
+    assert!(
+        matches!(result, Err(LiteSVMError::InvalidReplayFile { reason, .. }) if reason.contains("base58"))
+    );
+}
@@ -0,0 +1,234 @@
+#![cfg(feature = "serde")]
+
+use {
+    litesvm::{error::LiteSVMError, LiteSVM},
+    serde_json::Value,
+    solana_account::Account,
+    solana_clock::Clock,
+    solana_epoch_rewards::EpochRewards,
+    solana_inflation::Inflation,
+    solana_instruction::Instruction,
+    solana_keypair::Keypair,
+    solana_message::Message,
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    solana_signer::Signer,
+    solana_stake_interface::stake_history::StakeHistory,
+    solana_system_interface::instruction::transfer,
+    solana_transaction::Transaction,
+    std::path::Path,
+};
+
+fn memo_program() -> Vec<u8> {
+    let mut so_path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    so_path.push("src/spl/programs/spl_memo-3.0.0.so");
+    std::fs::read(so_path).unwrap()
+}
+
+fn memo_tx(svm: &LiteSVM, payer: &Keypair, program_id: Pubkey) -> Transaction {
+    Transaction::new(
+        &[payer],
+        Message::new(
+            &[Instruction {
+                program_id,
+                accounts: vec![],
+                data: b"hello".to_vec(),
+            }],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    )
+}
+
+fn lines(path: &Path) -> Vec<Value> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_replay_rebuilds_the_session() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.jsonl");
+    let mut svm = LiteSVM::new().with_session_recording(&path);
+
+    let payer = Keypair::new();
+    let program_id = Pubkey::new_unique();
+    let [recipient, stored, removed] = std::array::from_fn(|_| Pubkey::new_unique());
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    svm.add_program(program_id, &memo_program());
+    svm.set_account(stored, Account::new(5_000_000, 16, &program_id))
+        .unwrap();
+    svm.set_account(removed, Account::new(1_000_000, 0, &program_id))
+        .unwrap();
+    svm.remove_account(&removed).unwrap();
+    svm.set_sysvar(&Rent {
+        lamports_per_byte_year: 1,
+        ..Rent::default()
+    });
+    svm.warp_to_slot(50);
+    let memo = svm.send_transaction(memo_tx(&svm, &payer, program_id));
+    svm.expire_blockhash();
+    let tx = Transaction::new(
+        &[&payer],
+        Message::new(
+            &[transfer(&payer.pubkey(), &recipient, 10_000_000)],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    );
+    let transferred = svm.send_transaction(tx);
+    // a failed transaction is recorded with its error
+    let tx = Transaction::new(
+        &[&payer],
+        Message::new(
+            &[transfer(&payer.pubkey(), &recipient, u64::MAX)],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    );
+    let failed = svm.send_transaction(tx);
+    assert!(memo.is_ok() && transferred.is_ok() && failed.is_err());
+
+    // clones don't record
+    let mut clone = svm.clone();
+    clone.airdrop(&recipient, 1).unwrap();
+
+    let calls: Vec<_> = lines(&path)
+        .iter()
+        .map(|line| line["call"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(
+        calls,
+        [
+            "start",
+            "airdrop",
+            "add_program",
+            "set_account",
+            "set_account",
+            "remove_account",
+            "set_sysvar",
+            "warp_to_slot",
+            "send_transaction",
+            "expire_blockhash",
+            "send_transaction",
+            "send_transaction",
+        ]
+    );
+
+    let replayed = LiteSVM::replay(&path).unwrap();
+    for pubkey in [payer.pubkey(), program_id, recipient, stored, removed] {
+        assert_eq!(replayed.get_account(&pubkey), svm.get_account(&pubkey));
+    }
+    assert_eq!(replayed.latest_blockhash(), svm.latest_blockhash());
+    assert_eq!(replayed.get_sysvar::<Clock>(), svm.get_sysvar::<Clock>());
+    assert_eq!(replayed.get_sysvar::<Rent>(), svm.get_sysvar::<Rent>());
+    for result in [&memo, &transferred, &failed] {
+        let signature = match result {
+            Ok(meta) => meta.signature,
+            Err(failed) => failed.meta.signature,
+        };
+        assert_eq!(replayed.get_transaction(&signature), Some(result));
+    }
+}
+
+#[test]
+fn test_replay_detects_divergence() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.jsonl");
+    let mut svm = LiteSVM::new().with_session_recording(&path);
+    let payer = Keypair::new();
+    let program_id = Pubkey::new_unique();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    svm.add_program(program_id, &memo_program());
+    svm.send_transaction(memo_tx(&svm, &payer, program_id))
+        .unwrap();
+
+    let mut lines = lines(&path);
+    lines[3]["result"]["Ok"]["compute_units_consumed"] = 1.into();
+    let recording = lines
+        .iter()
+        .map(|line| line.to_string() + "\n")
+        .collect::<String>();
+    std::fs::write(&path, recording).unwrap();
+
+    let result = LiteSVM::replay(&path);
+    assert!(matches!(result, Err(LiteSVMError::SessionDiverged { path: p, line: 4 }) if p == path));
+}
+
+#[test]
+fn test_invalid_session_recordings() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.jsonl");
+
+    std::fs::write(&path, "").unwrap();
+    let result = LiteSVM::replay(&path);
+    assert!(matches!(result, Err(LiteSVMError::InvalidSession { .. })));
+
+    // the first line has to be the start of a recording
+    std::fs::write(&path, "{\"call\":\"expire_blockhash\"}\n").unwrap();
+    let result = LiteSVM::replay(&path);
+    assert!(matches!(
+        result,
+        Err(LiteSVMError::InvalidSession { line: 1, .. })
+    ));
+
+    LiteSVM::new().with_session_recording(&path);
+    let mut recording = std::fs::read_to_string(&path).unwrap();
+    recording.push_str("{\"call\":\"warp_to_slot\"}\n");
+    std::fs::write(&path, recording).unwrap();
+    let result = LiteSVM::replay(&path);
+    assert!(matches!(
+        result,
+        Err(LiteSVMError::InvalidSession { line: 2, .. })
+    ));
+}
+
+#[test]
+fn test_replay_produced_blocks() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.jsonl");
+    // the slot duration set before recording is recorded too
+    let mut svm = LiteSVM::new()
+        .with_slot_duration(std::time::Duration::from_millis(700))
+        .with_session_recording(&path)
+        .with_auto_produce_blocks(true)
+        .with_inflation(Inflation::default());
+    let payer = Keypair::new();
+    let program_id = Pubkey::new_unique();
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    svm.add_program(program_id, &memo_program());
+    for _ in 0..3 {
+        svm.send_transaction(memo_tx(&svm, &payer, program_id))
+            .unwrap();
+        svm.produce_block().unwrap();
+    }
+
+    // blocks produced after each transaction are recorded like the others
+    let replayed = LiteSVM::replay(&path).unwrap();
+    assert_eq!(replayed.get_sysvar::<Clock>(), svm.get_sysvar::<Clock>());
+    assert_eq!(replayed.get_sysvar::<Clock>().slot, 6);
+    assert_eq!(replayed.latest_blockhash(), svm.latest_blockhash());
+    assert_eq!(
+        replayed.get_balance(&payer.pubkey()),
+        svm.get_balance(&payer.pubkey())
+    );
+
+    // so is the inflation, for the rewards paid at epoch boundaries
+    let recording = std::fs::read_to_string(&path).unwrap();
+    assert!(recording.contains(r#"{"call":"set_inflation","#));
+    assert!(recording.contains(r#"{"call":"produce_block"}"#));
+    svm.warp_to_epoch(1).unwrap();
+    let replayed = LiteSVM::replay(&path).unwrap();
+    assert!(replayed.get_sysvar::<EpochRewards>().active);
+    assert_eq!(
+        replayed.get_sysvar::<EpochRewards>(),
+        svm.get_sysvar::<EpochRewards>()
+    );
+    assert_eq!(
+        replayed.get_sysvar::<StakeHistory>(),
+        svm.get_sysvar::<StakeHistory>()
+    );
+}
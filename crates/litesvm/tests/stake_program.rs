@@ -132,13 +132,8 @@ fn create_vote(
 
 fn advance_epoch(svm: &mut LiteSVM) {
     refresh_blockhash(svm);
-    let old_clock = svm.get_sysvar::<Clock>();
-    let root_slot = old_clock.slot;
-    let slots_per_epoch = svm.get_sysvar::<EpochSchedule>().slots_per_epoch;
-    svm.warp_to_slot(root_slot + slots_per_epoch);
-    let mut new_clock = old_clock;
-    new_clock.epoch += 1;
-    svm.set_sysvar::<Clock>(&new_clock)
+    let epoch = svm.get_sysvar::<Clock>().epoch;
+    svm.warp_to_epoch(epoch + 1).unwrap();
 }
 
 fn refresh_blockhash(svm: &mut LiteSVM) {
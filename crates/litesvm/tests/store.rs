@@ -0,0 +1,406 @@
+use {
+    litesvm::{
+        error::LiteSVMError,
+        storage::{AccountStore, AccountsIter, MemoryStore, NullStore, Result, StoreError},
+        types::FeeBreakdown,
+        LiteSVM,
+    },
+    solana_account::{AccountSharedData, ReadableAccount, WritableAccount},
+    solana_clock::Clock,
+    solana_keypair::Keypair,
+    solana_message::Message,
+    solana_pubkey::Pubkey,
+    solana_signer::Signer,
+    solana_system_interface::instruction::transfer,
+    solana_transaction::Transaction,
+    solana_transaction_error::TransactionError,
+    std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+fn transfer_tx(svm: &LiteSVM, payer: &Keypair, to: &Pubkey, lamports: u64) -> Transaction {
+    Transaction::new(
+        &[payer],
+        Message::new(
+            &[transfer(&payer.pubkey(), to, lamports)],
+            Some(&payer.pubkey()),
+        ),
+        svm.latest_blockhash(),
+    )
+}
+
+#[test]
+fn test_with_store_copies_and_writes_through() {
+    let store = Arc::new(MemoryStore::new());
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    let mut svm = LiteSVM::new().with_store(store.clone());
+    // accounts set up before the store was attached are copied into it
+    assert!(store
+        .get_account(&solana_sdk_ids::system_program::id())
+        .unwrap()
+        .is_some());
+
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    svm.send_transaction(transfer_tx(&svm, &payer, &to, 100))
+        .unwrap();
+    assert_eq!(store.get_account(&to).unwrap().unwrap().lamports(), 100);
+}
+
+#[test]
+fn test_null_store() {
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    let mut svm = LiteSVM::new().with_store(NullStore);
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    svm.send_transaction(transfer_tx(&svm, &payer, &to, 100))
+        .unwrap();
+    assert_eq!(svm.get_balance(&to), Some(100));
+    assert_eq!(NullStore.iter_accounts().unwrap().count(), 0);
+}
+
+#[test]
+fn test_open_store() {
+    let store = Arc::new(MemoryStore::new());
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    let blockhash = {
+        let mut svm = LiteSVM::open_store(store.clone()).unwrap();
+        svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+        svm.send_transaction(transfer_tx(&svm, &payer, &to, 100))
+            .unwrap();
+        svm.expire_blockhash();
+        svm.save_metadata().unwrap();
+        svm.latest_blockhash()
+    };
+
+    let mut svm = LiteSVM::open_store(store).unwrap();
+    assert_eq!(svm.latest_blockhash(), blockhash);
+    svm.send_transaction(transfer_tx(&svm, &payer, &to, 1))
+        .unwrap();
+    assert_eq!(svm.get_balance(&to), Some(101));
+}
+
+#[test]
+fn test_block_production_writes_the_metadata() {
+    let store = Arc::new(MemoryStore::new());
+
+    let (blockhash, block_height) = {
+        let mut svm = LiteSVM::open_store(store.clone()).unwrap();
+        svm.produce_block().unwrap();
+        svm.produce_block().unwrap();
+        (svm.latest_blockhash(), svm.get_block_height())
+    };
+
+    // no save_metadata, yet the blockhash queue matches the stored sysvars
+    let svm = LiteSVM::open_store(store).unwrap();
+    assert_eq!(svm.latest_blockhash(), blockhash);
+    assert_eq!(svm.get_block_height(), block_height);
+    assert_eq!(svm.get_sysvar::<Clock>().slot, 2);
+}
+
+#[test]
+fn test_open_store_skips_programs_that_fail_to_load() {
+    let store = Arc::new(MemoryStore::new());
+    let program = Pubkey::new_unique();
+    LiteSVM::open_store(store.clone()).unwrap();
+    let mut broken = AccountSharedData::new(1, 4, &solana_sdk_ids::bpf_loader::id());
+    broken.set_data_from_slice(b"nope");
+    broken.set_executable(true);
+    store.put_account(&program, &broken).unwrap();
+
+    let svm = LiteSVM::open_store(store).unwrap();
+    assert_eq!(svm.get_account(&program).unwrap().data, b"nope");
+}
+
+/// A [`MemoryStore`] whose account writes can be made to fail.
+#[derive(Default)]
+struct FailingStore {
+    inner: MemoryStore,
+    fail: AtomicBool,
+    commits: AtomicUsize,
+}
+
+impl FailingStore {
+    fn check(&self) -> Result<()> {
+        if self.fail.load(Ordering::SeqCst) {
+            return Err(StoreError::Io(std::io::Error::other("disk full")));
+        }
+        Ok(())
+    }
+}
+
+impl AccountStore for FailingStore {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<AccountSharedData>> {
+        self.inner.get_account(pubkey)
+    }
+
+    fn put_account(&self, pubkey: &Pubkey, account: &AccountSharedData) -> Result<()> {
+        self.check()?;
+        self.inner.put_account(pubkey, account)
+    }
+
+    fn put_accounts(&self, accounts: &[(Pubkey, AccountSharedData)]) -> Result<()> {
+        self.check()?;
+        self.inner.put_accounts(accounts)
+    }
+
+    fn delete_account(&self, pubkey: &Pubkey) -> Result<()> {
+        self.check()?;
+        self.inner.delete_account(pubkey)
+    }
+
+    fn write_accounts(&self, accounts: &[(Pubkey, Option<AccountSharedData>)]) -> Result<()> {
+        self.check()?;
+        self.commits.fetch_add(1, Ordering::SeqCst);
+        self.inner.write_accounts(accounts)
+    }
+
+    fn iter_accounts(&self) -> Result<AccountsIter<'_>> {
+        self.inner.iter_accounts()
+    }
+}
+
+#[test]
+fn test_transactions_commit_in_one_write() {
+    let store = Arc::new(FailingStore::default());
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    let mut svm = LiteSVM::new().with_store(store.clone());
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    svm.send_transaction(transfer_tx(&svm, &payer, &to, 100))
+        .unwrap();
+    assert_eq!(store.commits.load(Ordering::SeqCst), 1);
+
+    // a failed transaction commits its fee withdrawal the same way
+    let balance = svm.get_balance(&payer.pubkey()).unwrap();
+    let err = svm
+        .send_transaction(transfer_tx(&svm, &payer, &to, u64::MAX))
+        .unwrap_err();
+    assert!(matches!(err.err, TransactionError::InstructionError(..)));
+    assert_eq!(store.commits.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        store
+            .get_account(&payer.pubkey())
+            .unwrap()
+            .unwrap()
+            .lamports(),
+        balance - 5000
+    );
+}
+
+#[test]
+fn test_store_errors_are_surfaced() {
+    let store = Arc::new(FailingStore::default());
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    let mut svm = LiteSVM::new().with_store(store.clone());
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    store.fail.store(true, Ordering::SeqCst);
+
+    assert!(matches!(
+        svm.airdrop(&to, 1),
+        Err(LiteSVMError::Store(StoreError::Io(_)))
+    ));
+    let tx = transfer_tx(&svm, &payer, &to, 100);
+    let signature = tx.signatures[0];
+    let err = svm.send_transaction(tx).unwrap_err();
+    assert_eq!(err.err, TransactionError::CommitCancelled);
+    assert!(matches!(
+        svm.try_send_transaction(transfer_tx(&svm, &payer, &to, 200)),
+        Err(LiteSVMError::Store(StoreError::Io(_)))
+    ));
+    // nothing was applied, so the transaction can be sent again once the store recovers
+    assert_eq!(svm.get_balance(&to), None);
+    assert_eq!(svm.get_balance(&payer.pubkey()), Some(1_000_000_000));
+    assert!(svm.get_transaction(&signature).is_none());
+
+    store.fail.store(false, Ordering::SeqCst);
+    svm.send_transaction(transfer_tx(&svm, &payer, &to, 100))
+        .unwrap();
+    assert_eq!(svm.get_balance(&to), Some(100));
+}
+
+#[test]
+fn test_try_variants_return_store_errors() {
+    let store = Arc::new(FailingStore::default());
+    store.fail.store(true, Ordering::SeqCst);
+    assert!(matches!(
+        LiteSVM::new().try_with_store(store.clone()),
+        Err(LiteSVMError::Store(StoreError::Io(_)))
+    ));
+
+    store.fail.store(false, Ordering::SeqCst);
+    let mut svm = LiteSVM::new().with_store(store.clone());
+    let clock = svm.get_sysvar::<Clock>();
+    store.fail.store(true, Ordering::SeqCst);
+    let is_store_error = |result| matches!(result, Err(LiteSVMError::Store(StoreError::Io(_))));
+    assert!(is_store_error(svm.try_set_sysvar(&Clock {
+        slot: 10,
+        ..clock.clone()
+    })));
+    assert!(is_store_error(svm.try_warp_to_slot(10)));
+    let blockhash = svm.latest_blockhash();
+    assert!(is_store_error(svm.try_expire_blockhash()));
+    assert!(is_store_error(svm.produce_block()));
+    assert_eq!(svm.latest_blockhash(), blockhash);
+    assert_eq!(svm.get_block_height(), 0);
+    assert!(is_store_error(svm.try_add_program(
+        Pubkey::new_unique(),
+        include_bytes!("../src/spl/programs/spl_memo-3.0.0.so"),
+    )));
+    assert_eq!(svm.get_sysvar::<Clock>(), clock);
+}
+
+/// A [`MemoryStore`] that fails to read an account once another account was read.
+#[derive(Default)]
+struct ForgetfulStore {
+    inner: MemoryStore,
+    /// The account that can't be read anymore once the first one was.
+    forget: Mutex<Option<(Pubkey, Pubkey)>>,
+    forgetting: AtomicBool,
+}
+
+impl AccountStore for ForgetfulStore {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<AccountSharedData>> {
+        if let Some((trigger, forgotten)) = *self.forget.lock().unwrap() {
+            if pubkey == &trigger {
+                self.forgetting.store(true, Ordering::SeqCst);
+            } else if pubkey == &forgotten && self.forgetting.load(Ordering::SeqCst) {
+                return Err(StoreError::Io(std::io::Error::other("read failed")));
+            }
+        }
+        self.inner.get_account(pubkey)
+    }
+
+    fn put_account(&self, pubkey: &Pubkey, account: &AccountSharedData) -> Result<()> {
+        self.inner.put_account(pubkey, account)
+    }
+
+    fn put_accounts(&self, accounts: &[(Pubkey, AccountSharedData)]) -> Result<()> {
+        self.inner.put_accounts(accounts)
+    }
+
+    fn delete_account(&self, pubkey: &Pubkey) -> Result<()> {
+        self.inner.delete_account(pubkey)
+    }
+
+    fn iter_accounts(&self) -> Result<AccountsIter<'_>> {
+        self.inner.iter_accounts()
+    }
+}
+
+#[test_log::test]
+fn test_fees_are_not_distributed_if_they_cant_be_withdrawn() {
+    let store = Arc::new(ForgetfulStore::default());
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+    let collector = Pubkey::new_unique();
+
+    // every read goes to the store
+    let mut svm = LiteSVM::new()
+        .with_store(store.clone())
+        .with_account_cache_size(Some(0))
+        .with_fee_collector(collector);
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    svm.airdrop(&collector, 1_000_000_000).unwrap();
+    let total = |svm: &LiteSVM| {
+        svm.get_balance(&payer.pubkey()).unwrap() + svm.get_balance(&collector).unwrap()
+    };
+    let before = total(&svm);
+
+    // the payer is read to check the fee, then can't be read again to withdraw it
+    // once the transfer failed
+    let tx = transfer_tx(&svm, &payer, &to, u64::MAX);
+    *store.forget.lock().unwrap() = Some((to, payer.pubkey()));
+    let failed = svm.send_transaction(tx).unwrap_err();
+    *store.forget.lock().unwrap() = None;
+    assert_eq!(failed.err, TransactionError::AccountNotFound);
+    assert_eq!(failed.meta.fees, FeeBreakdown::default());
+    assert_eq!(total(&svm), before);
+    assert_eq!(svm.get_balance(&collector), Some(1_000_000_000));
+}
+
+#[test_log::test]
+fn test_store_read_errors_cancel_the_commit() {
+    let store = Arc::new(ForgetfulStore::default());
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    let mut svm = LiteSVM::new()
+        .with_store(store.clone())
+        .with_account_cache_size(Some(0));
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+
+    // the payer can't be read anymore when the transaction is committed
+    let tx = transfer_tx(&svm, &payer, &to, 100);
+    *store.forget.lock().unwrap() = Some((to, payer.pubkey()));
+    let err = svm.send_transaction(tx).unwrap_err();
+    *store.forget.lock().unwrap() = None;
+    assert_eq!(err.err, TransactionError::CommitCancelled);
+    assert_eq!(svm.get_balance(&to), None);
+    assert_eq!(svm.get_balance(&payer.pubkey()), Some(1_000_000_000));
+}
+
+#[test]
+fn test_revert_writes_the_metadata() {
+    let store = Arc::new(MemoryStore::new());
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    let blockhash = {
+        let mut svm = LiteSVM::open_store(store.clone()).unwrap();
+        svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+        let checkpoint = svm.checkpoint();
+        let blockhash = svm.latest_blockhash();
+        svm.send_transaction(transfer_tx(&svm, &payer, &to, 100))
+            .unwrap();
+        svm.produce_block().unwrap();
+        svm.revert(checkpoint).unwrap();
+        blockhash
+    };
+
+    // no save_metadata, yet the blockhash queue matches the reverted sysvars
+    let svm = LiteSVM::open_store(store).unwrap();
+    assert_eq!(svm.latest_blockhash(), blockhash);
+    assert_eq!(svm.get_block_height(), 0);
+    assert_eq!(svm.get_balance(&to), None);
+}
+
+#[test]
+fn test_failed_revert_changes_nothing() {
+    let store = Arc::new(FailingStore::default());
+    let payer = Keypair::new();
+    let to = Pubkey::new_unique();
+
+    let mut svm = LiteSVM::new().with_store(store.clone());
+    svm.airdrop(&payer.pubkey(), 1_000_000_000).unwrap();
+    let checkpoint = svm.checkpoint();
+    svm.send_transaction(transfer_tx(&svm, &payer, &to, 100))
+        .unwrap();
+    svm.produce_block().unwrap();
+    let blockhash = svm.latest_blockhash();
+
+    store.fail.store(true, Ordering::SeqCst);
+    assert!(matches!(
+        svm.revert(checkpoint),
+        Err(LiteSVMError::Store(StoreError::Io(_)))
+    ));
+    assert_eq!(svm.get_balance(&to), Some(100));
+    assert_eq!(svm.latest_blockhash(), blockhash);
+    assert_eq!(svm.get_block_height(), 1);
+
+    // the checkpoint is still there to revert to once the store recovers
+    store.fail.store(false, Ordering::SeqCst);
+    svm.revert(checkpoint).unwrap();
+    assert_eq!(svm.get_balance(&to), None);
+    assert_eq!(svm.get_block_height(), 0);
+    assert_eq!(store.get_account(&to).unwrap(), None);
+}
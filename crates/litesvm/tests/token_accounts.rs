@@ -0,0 +1,213 @@
+use {
+    litesvm::LiteSVM,
+    solana_instruction::{AccountMeta, Instruction},
+    solana_keypair::Keypair,
+    solana_program_pack::Pack,
+    solana_pubkey::{pubkey, Pubkey},
+    solana_signer::Signer,
+    solana_system_interface::instruction::create_account,
+    solana_transaction::Transaction,
+    spl_token::instruction::AuthorityType,
+};
+
+const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+/// A Token-2022 account with the `ImmutableOwner` extension:
+/// the base account, the account type and an empty extension header.
+const TOKEN_2022_ACCOUNT_WITH_EXTENSION_LEN: usize = 165 + 1 + 4;
+
+fn send(svm: &mut LiteSVM, payer: &Keypair, signers: &[&Keypair], ixs: &[Instruction]) {
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(
+        ixs,
+        Some(&payer.pubkey()),
+        &all_signers,
+        svm.latest_blockhash(),
+    );
+    svm.send_transaction(tx).unwrap();
+}
+
+fn create_mint(svm: &mut LiteSVM, payer: &Keypair, program_id: &Pubkey) -> Pubkey {
+    let mint = Keypair::new();
+    let len = spl_token::state::Mint::LEN;
+    // `InitializeMint2` has the same layout in both token programs
+    let mut data = vec![20, 0];
+    data.extend_from_slice(payer.pubkey().as_ref());
+    data.push(0);
+    let init = Instruction::new_with_bytes(
+        *program_id,
+        &data,
+        vec![AccountMeta::new(mint.pubkey(), false)],
+    );
+    let create = create_account(
+        &payer.pubkey(),
+        &mint.pubkey(),
+        svm.minimum_balance_for_rent_exemption(len),
+        len as u64,
+        program_id,
+    );
+    send(svm, payer, &[&mint], &[create, init]);
+    mint.pubkey()
+}
+
+fn create_token_account(
+    svm: &mut LiteSVM,
+    payer: &Keypair,
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Pubkey {
+    let account = Keypair::new();
+    let with_extension = program_id == &TOKEN_2022_PROGRAM_ID;
+    let len = if with_extension {
+        TOKEN_2022_ACCOUNT_WITH_EXTENSION_LEN
+    } else {
+        spl_token::state::Account::LEN
+    };
+    let mut ixs = vec![create_account(
+        &payer.pubkey(),
+        &account.pubkey(),
+        svm.minimum_balance_for_rent_exemption(len),
+        len as u64,
+        program_id,
+    )];
+    if with_extension {
+        // InitializeImmutableOwner
+        ixs.push(Instruction::new_with_bytes(
+            *program_id,
+            &[22],
+            vec![AccountMeta::new(account.pubkey(), false)],
+        ));
+    }
+    // InitializeAccount3
+    let mut data = vec![18];
+    data.extend_from_slice(owner.as_ref());
+    ixs.push(Instruction::new_with_bytes(
+        *program_id,
+        &data,
+        vec![
+            AccountMeta::new(account.pubkey(), false),
+            AccountMeta::new_readonly(*mint, false),
+        ],
+    ));
+    send(svm, payer, &[&account], &ixs);
+    account.pubkey()
+}
+
+fn addresses(accounts: Vec<(Pubkey, solana_account::Account)>) -> Vec<Pubkey> {
+    accounts.into_iter().map(|(pubkey, _)| pubkey).collect()
+}
+
+#[test]
+fn test_token_account_indexes() {
+    let mut svm = LiteSVM::new();
+    let payer = Keypair::new();
+    let wallet = Keypair::new();
+    svm.airdrop(&payer.pubkey(), 10_000_000_000).unwrap();
+    svm.airdrop(&wallet.pubkey(), 10_000_000_000).unwrap();
+
+    let mint = create_mint(&mut svm, &payer, &spl_token::id());
+    let mint_2022 = create_mint(&mut svm, &payer, &TOKEN_2022_PROGRAM_ID);
+    let token_account =
+        create_token_account(&mut svm, &payer, &spl_token::id(), &mint, &wallet.pubkey());
+    let token_2022_account = create_token_account(
+        &mut svm,
+        &payer,
+        &TOKEN_2022_PROGRAM_ID,
+        &mint_2022,
+        &wallet.pubkey(),
+    );
+    let other_account = create_token_account(
+        &mut svm,
+        &payer,
+        &spl_token::id(),
+        &mint,
+        &Pubkey::new_unique(),
+    );
+
+    let mut owned = vec![token_account, token_2022_account];
+    owned.sort();
+    assert_eq!(
+        addresses(svm.get_token_accounts_by_owner(&wallet.pubkey())),
+        owned
+    );
+    let mut holding_mint = vec![token_account, other_account];
+    holding_mint.sort();
+    assert_eq!(
+        addresses(svm.get_token_accounts_by_mint(&mint)),
+        holding_mint
+    );
+    assert_eq!(
+        addresses(svm.get_token_accounts_by_mint(&mint_2022)),
+        [token_2022_account]
+    );
+
+    // handing the account to a new owner moves it in the owner index
+    let new_owner = Pubkey::new_unique();
+    let ix = spl_token::instruction::set_authority(
+        &spl_token::id(),
+        &token_account,
+        Some(&new_owner),
+        AuthorityType::AccountOwner,
+        &wallet.pubkey(),
+        &[],
+    )
+    .unwrap();
+    send(&mut svm, &payer, &[&wallet], &[ix]);
+    assert_eq!(
+        addresses(svm.get_token_accounts_by_owner(&wallet.pubkey())),
+        [token_2022_account]
+    );
+    assert_eq!(
+        addresses(svm.get_token_accounts_by_owner(&new_owner)),
+        [token_account]
+    );
+
+    // closed accounts drop out of both indexes
+    let close = Instruction::new_with_bytes(
+        TOKEN_2022_PROGRAM_ID,
+        &[9],
+        vec![
+            AccountMeta::new(token_2022_account, false),
+            AccountMeta::new(payer.pubkey(), false),
+            AccountMeta::new_readonly(wallet.pubkey(), true),
+        ],
+    );
+    send(&mut svm, &payer, &[&wallet], &[close]);
+    assert!(svm.get_account(&token_2022_account).is_none());
+    assert!(svm.get_token_accounts_by_owner(&wallet.pubkey()).is_empty());
+    assert!(svm.get_token_accounts_by_mint(&mint_2022).is_empty());
+}
+
+#[test]
+fn test_token_indexes_are_rebuilt_from_the_store() {
+    let store = std::sync::Arc::new(litesvm::storage::MemoryStore::new());
+    let mint = Pubkey::new_unique();
+    let wallet = Pubkey::new_unique();
+    let address = Pubkey::new_unique();
+
+    {
+        let mut svm = LiteSVM::open_store(store.clone()).unwrap();
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(wallet.as_ref());
+        svm.set_account(
+            address,
+            solana_account::Account {
+                lamports: 1_000_000_000,
+                data,
+                owner: spl_token::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .unwrap();
+    }
+
+    let svm = LiteSVM::open_store(store).unwrap();
+    assert_eq!(
+        addresses(svm.get_token_accounts_by_owner(&wallet)),
+        [address]
+    );
+    assert_eq!(addresses(svm.get_token_accounts_by_mint(&mint)), [address]);
+}
@@ -23,6 +23,7 @@ export {
 	EpochSchedule,
 	FailedTransactionMetadata,
 	FeatureSet,
+	FeeBreakdown,
 	InnerInstruction,
 	Rent,
 	SlotHash,
@@ -491,6 +491,14 @@ export declare class TransactionReturnData {
   data(): Uint8Array
   toString(): string
 }
+export declare class FeeBreakdown {
+  transactionFee(): bigint
+  prioritizationFee(): bigint
+  burned(): bigint
+  collected(): bigint
+  total(): bigint
+  toString(): string
+}
 export declare class TransactionMetadata {
   signature(): Uint8Array
   logs(): Array<string>
@@ -498,6 +506,7 @@ export declare class TransactionMetadata {
   computeUnitsConsumed(): bigint
   returnData(): TransactionReturnData
   prettyLogs(): string
+  fees(): FeeBreakdown
   toString(): string
 }
 export declare class FailedTransactionMetadata {
@@ -310,7 +310,7 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { Account, ComputeBudget, FeatureSet, Clock, EpochRewards, EpochSchedule, Rent, SlotHash, SlotHistoryCheck, SlotHistory, StakeHistoryEntry, StakeHistory, InstructionErrorCustom, InstructionErrorBorshIo, InstructionErrorFieldless, TransactionErrorFieldless, TransactionErrorInstructionError, TransactionErrorDuplicateInstruction, TransactionErrorInsufficientFundsForRent, TransactionErrorProgramExecutionTemporarilyRestricted, CompiledInstruction, InnerInstruction, TransactionReturnData, TransactionMetadata, FailedTransactionMetadata, AddressAndAccount, SimulatedTransactionInfo, LiteSvm } = nativeBinding
+const { Account, ComputeBudget, FeatureSet, Clock, EpochRewards, EpochSchedule, Rent, SlotHash, SlotHistoryCheck, SlotHistory, StakeHistoryEntry, StakeHistory, InstructionErrorCustom, InstructionErrorBorshIo, InstructionErrorFieldless, TransactionErrorFieldless, TransactionErrorInstructionError, TransactionErrorDuplicateInstruction, TransactionErrorInsufficientFundsForRent, TransactionErrorProgramExecutionTemporarilyRestricted, CompiledInstruction, InnerInstruction, TransactionReturnData, FeeBreakdown, TransactionMetadata, FailedTransactionMetadata, AddressAndAccount, SimulatedTransactionInfo, LiteSvm } = nativeBinding
 
 module.exports.Account = Account
 module.exports.ComputeBudget = ComputeBudget
@@ -335,6 +335,7 @@ module.exports.TransactionErrorProgramExecutionTemporarilyRestricted = Transacti
 module.exports.CompiledInstruction = CompiledInstruction
 module.exports.InnerInstruction = InnerInstruction
 module.exports.TransactionReturnData = TransactionReturnData
+module.exports.FeeBreakdown = FeeBreakdown
 module.exports.TransactionMetadata = TransactionMetadata
 module.exports.FailedTransactionMetadata = FailedTransactionMetadata
 module.exports.AddressAndAccount = AddressAndAccount
@@ -119,8 +119,10 @@ impl LiteSvm {
 
     #[napi]
     /// Includes the default sysvars
-    pub fn set_sysvars(&mut self) {
-        self.0.set_sysvars()
+    pub fn set_sysvars(&mut self) -> Result<()> {
+        self.0
+            .set_sysvars()
+            .map_err(|e| to_js_error(e, "Failed to set sysvars"))
     }
 
     #[napi]
@@ -131,20 +133,26 @@ impl LiteSvm {
 
     #[napi]
     /// Changes the default builtins
-    pub fn set_builtins(&mut self) {
-        self.0.set_builtins();
+    pub fn set_builtins(&mut self) -> Result<()> {
+        self.0
+            .set_builtins()
+            .map_err(|e| to_js_error(e, "Failed to set builtins"))
     }
 
     #[napi]
     /// Changes the initial lamports in LiteSVM's airdrop account
     pub fn set_lamports(&mut self, lamports: BigInt) -> Result<()> {
-        Ok(self.0.set_lamports(bigint_to_u64(&lamports)?))
+        self.0
+            .set_lamports(bigint_to_u64(&lamports)?)
+            .map_err(|e| to_js_error(e, "Failed to set lamports"))
     }
 
     #[napi]
     /// Includes the standard SPL programs
-    pub fn set_spl_programs(&mut self) {
-        self.0.set_spl_programs();
+    pub fn set_spl_programs(&mut self) -> Result<()> {
+        self.0
+            .set_spl_programs()
+            .map_err(|e| to_js_error(e, "Failed to set SPL programs"))
     }
 
     #[napi]
@@ -166,8 +174,10 @@ impl LiteSvm {
     }
 
     #[napi]
-    pub fn set_precompiles(&mut self) {
-        self.0.set_precompiles();
+    pub fn set_precompiles(&mut self) -> Result<()> {
+        self.0
+            .set_precompiles()
+            .map_err(|e| to_js_error(e, "Failed to set precompiles"))
     }
 
     #[napi]
@@ -236,9 +246,10 @@ impl LiteSvm {
 
     #[napi]
     /// Adds am SBF program to the test environment.
-    pub fn add_program(&mut self, program_id: Uint8Array, program_bytes: &[u8]) {
+    pub fn add_program(&mut self, program_id: Uint8Array, program_bytes: &[u8]) -> Result<()> {
         self.0
-            .add_program(convert_pubkey(program_id), program_bytes)
+            .try_add_program(convert_pubkey(program_id), program_bytes)
+            .map_err(|e| to_js_error(e, "Failed to add program"))
     }
 
     #[napi(ts_return_type = "TransactionMetadata | FailedTransactionMetadata")]
@@ -271,14 +282,18 @@ impl LiteSvm {
 
     #[napi]
     /// Expires the current blockhash
-    pub fn expire_blockhash(&mut self) {
-        self.0.expire_blockhash()
+    pub fn expire_blockhash(&mut self) -> Result<()> {
+        self.0
+            .try_expire_blockhash()
+            .map_err(|e| to_js_error(e, "Failed to expire blockhash"))
     }
 
     #[napi]
     /// Warps the clock to the specified slot
     pub fn warp_to_slot(&mut self, slot: BigInt) -> Result<()> {
-        Ok(self.0.warp_to_slot(bigint_to_u64(&slot)?))
+        self.0
+            .try_warp_to_slot(bigint_to_u64(&slot)?)
+            .map_err(|e| to_js_error(e, "Failed to warp to slot"))
     }
 
     #[napi]
@@ -297,8 +312,10 @@ impl LiteSvm {
     }
 
     #[napi]
-    pub fn set_clock(&mut self, clock: &Clock) {
-        self.0.set_sysvar(&clock.0)
+    pub fn set_clock(&mut self, clock: &Clock) -> Result<()> {
+        self.0
+            .try_set_sysvar(&clock.0)
+            .map_err(|e| to_js_error(e, "Failed to set sysvar"))
     }
 
     #[napi]
@@ -307,8 +324,10 @@ impl LiteSvm {
     }
 
     #[napi]
-    pub fn set_rent(&mut self, rent: &Rent) {
-        self.0.set_sysvar(&rent.0)
+    pub fn set_rent(&mut self, rent: &Rent) -> Result<()> {
+        self.0
+            .try_set_sysvar(&rent.0)
+            .map_err(|e| to_js_error(e, "Failed to set sysvar"))
     }
 
     #[napi]
@@ -317,8 +336,10 @@ impl LiteSvm {
     }
 
     #[napi]
-    pub fn set_epoch_rewards(&mut self, rewards: &EpochRewards) {
-        self.0.set_sysvar(&rewards.0)
+    pub fn set_epoch_rewards(&mut self, rewards: &EpochRewards) -> Result<()> {
+        self.0
+            .try_set_sysvar(&rewards.0)
+            .map_err(|e| to_js_error(e, "Failed to set sysvar"))
     }
 
     #[napi]
@@ -327,8 +348,10 @@ impl LiteSvm {
     }
 
     #[napi]
-    pub fn set_epoch_schedule(&mut self, schedule: &EpochSchedule) {
-        self.0.set_sysvar(&schedule.0)
+    pub fn set_epoch_schedule(&mut self, schedule: &EpochSchedule) -> Result<()> {
+        self.0
+            .try_set_sysvar(&schedule.0)
+            .map_err(|e| to_js_error(e, "Failed to set sysvar"))
     }
 
     #[napi]
@@ -338,9 +361,11 @@ impl LiteSvm {
 
     #[napi]
     pub fn set_last_restart_slot(&mut self, slot: BigInt) -> Result<()> {
-        Ok(self.0.set_sysvar::<LastRestartSlot>(&LastRestartSlot {
-            last_restart_slot: bigint_to_u64(&slot)?,
-        }))
+        self.0
+            .try_set_sysvar::<LastRestartSlot>(&LastRestartSlot {
+                last_restart_slot: bigint_to_u64(&slot)?,
+            })
+            .map_err(|e| to_js_error(e, "Failed to set sysvar"))
     }
 
     #[napi]
@@ -364,8 +389,9 @@ impl LiteSvm {
             intermediate.push((bigint_to_u64(&h.slot)?, converted_hash));
         }
         let converted = SlotHashes::from_iter(intermediate);
-        self.0.set_sysvar::<SlotHashes>(&converted);
-        Ok(())
+        self.0
+            .try_set_sysvar::<SlotHashes>(&converted)
+            .map_err(|e| to_js_error(e, "Failed to set sysvar"))
     }
 
     #[napi]
@@ -374,8 +400,10 @@ impl LiteSvm {
     }
 
     #[napi]
-    pub fn set_slot_history(&mut self, history: &SlotHistory) {
-        self.0.set_sysvar::<SlotHistoryOriginal>(&history.0)
+    pub fn set_slot_history(&mut self, history: &SlotHistory) -> Result<()> {
+        self.0
+            .try_set_sysvar::<SlotHistoryOriginal>(&history.0)
+            .map_err(|e| to_js_error(e, "Failed to set sysvar"))
     }
 
     #[napi]
@@ -384,7 +412,9 @@ impl LiteSvm {
     }
 
     #[napi]
-    pub fn set_stake_history(&mut self, history: &StakeHistory) {
-        self.0.set_sysvar::<StakeHistoryOriginal>(&history.0)
+    pub fn set_stake_history(&mut self, history: &StakeHistory) -> Result<()> {
+        self.0
+            .try_set_sysvar::<StakeHistoryOriginal>(&history.0)
+            .map_err(|e| to_js_error(e, "Failed to set sysvar"))
     }
 }
@@ -6,6 +6,7 @@ use {
     },
     litesvm::types::{
         FailedTransactionMetadata as FailedTransactionMetadataOriginal,
+        FeeBreakdown as FeeBreakdownOriginal,
         SimulatedTransactionInfo as SimulatedTransactionInfoOriginal,
         TransactionMetadata as TransactionMetadataOriginal,
     },
@@ -89,6 +90,40 @@ impl TransactionReturnData {
 
 to_string_js!(TransactionReturnData);
 
+#[derive(Debug, Clone)]
+#[napi]
+pub struct FeeBreakdown(FeeBreakdownOriginal);
+
+#[napi]
+impl FeeBreakdown {
+    #[napi]
+    pub fn transaction_fee(&self) -> u64 {
+        self.0.transaction_fee
+    }
+
+    #[napi]
+    pub fn prioritization_fee(&self) -> u64 {
+        self.0.prioritization_fee
+    }
+
+    #[napi]
+    pub fn burned(&self) -> u64 {
+        self.0.burned
+    }
+
+    #[napi]
+    pub fn collected(&self) -> u64 {
+        self.0.collected
+    }
+
+    #[napi]
+    pub fn total(&self) -> u64 {
+        self.0.total()
+    }
+}
+
+to_string_js!(FeeBreakdown);
+
 #[derive(Debug, Clone)]
 #[napi]
 pub struct TransactionMetadata(pub(crate) TransactionMetadataOriginal);
@@ -129,6 +164,11 @@ impl TransactionMetadata {
     pub fn pretty_logs(&self) -> String {
         self.0.pretty_logs()
     }
+
+    #[napi]
+    pub fn fees(&self) -> FeeBreakdown {
+        FeeBreakdown(self.0.fees)
+    }
 }
 
 to_string_js!(TransactionMetadata);